@@ -0,0 +1,98 @@
+use crate::config::{self, Config, SettingsBackend};
+use crate::gui::ThemeDir;
+use crate::i18n::tr;
+use crate::updater::{self, BackendKind};
+use gtk::prelude::*;
+
+/// What's asked for in most bug reports, labels aren't translated so they
+/// can be pasted as they are
+fn diagnostics(config: &Config) -> Vec<(&'static str, String)> {
+    let arch_audit = match (&config.backend, &config.mock) {
+        (BackendKind::ArchAudit, None) => match updater::arch_audit_version(config) {
+            Ok((major, minor, patch)) => {
+                let bin = updater::arch_audit_bin(config);
+                format!("{} {}.{}.{}", bin, major, minor, patch)
+            }
+            Err(err) => format!("{:#}", err),
+        },
+        _ => tr("not used"),
+    };
+
+    let name = config.icon_theme.as_str();
+    let theme = if config.icon_theme.is_system() {
+        tr("{}, the icons of the system icon theme").replace("{}", name)
+    } else {
+        match ThemeDir::find_usable(&config.icon_theme) {
+            Some(dir) => format!("{} ({})", name, dir.path().display()),
+            None => tr("{} not found, using the default theme").replace("{}", name),
+        }
+    };
+
+    let mut files = config::paths()
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    if config.settings_backend == SettingsBackend::GSettings {
+        files.push("GSettings".to_string());
+    }
+    let files = if files.is_empty() {
+        tr("none, using the defaults")
+    } else {
+        files.join("\n")
+    };
+
+    vec![
+        ("Version", env!("CARGO_PKG_VERSION").to_string()),
+        ("arch-audit", arch_audit),
+        ("Backend", updater::backend_name(config).to_string()),
+        ("Icon theme", theme),
+        ("Config", files),
+    ]
+}
+
+/// Open the about dialog, below the usual credits it lists the version of
+/// arch-audit, the backend, the icon theme and the config files
+pub fn show(config: &Config) {
+    let dialog = gtk::AboutDialog::new();
+    dialog.set_program_name(env!("CARGO_PKG_NAME"));
+    dialog.set_version(Some(env!("CARGO_PKG_VERSION")));
+    dialog.set_comments(Some(&tr("Arch Linux Security Update Notifications")));
+    dialog.set_website(Some(env!("CARGO_PKG_REPOSITORY")));
+    dialog.set_license_type(gtk::License::Gpl30);
+
+    let rows = diagnostics(config);
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+    for (top, (label, value)) in rows.iter().enumerate() {
+        let label = gtk::Label::new(Some(&tr(label)));
+        label.set_halign(gtk::Align::Start);
+        label.set_valign(gtk::Align::Start);
+        let value = gtk::Label::new(Some(value));
+        value.set_halign(gtk::Align::Start);
+        value.set_xalign(0.0);
+        value.set_selectable(true);
+        grid.attach(&label, 0, top as i32, 1, 1);
+        grid.attach(&value, 1, top as i32, 1, 1);
+    }
+
+    let report = rows
+        .iter()
+        .map(|(label, value)| format!("{}: {}", label, value.replace('\n', ", ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let copy = gtk::Button::with_mnemonic(&tr("_Copy diagnostics"));
+    copy.set_halign(gtk::Align::Start);
+    copy.connect_clicked(move |_| {
+        let clipboard = gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD);
+        clipboard.set_text(&report);
+    });
+    grid.attach(&copy, 0, rows.len() as i32, 2, 1);
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+    dialog.present();
+}
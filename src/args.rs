@@ -1,4 +1,8 @@
+use crate::backend::MockScenario;
 use crate::gui::{Icon, Theme};
+use crate::report::Format;
+use log::LevelFilter;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -9,20 +13,85 @@ pub struct Args {
     pub pacman_notify: bool,
     #[structopt(long, group = "action")]
     pub debug_inotify: bool,
+    /// Print a pacman hook that notifies running instances after every
+    /// transaction, eg. for a build without the package
+    #[structopt(long, group = "action")]
+    pub print_hook: bool,
+    /// Write a systemd user unit that starts the tray icon with the session
+    #[structopt(long, group = "action")]
+    pub install_service: bool,
+    /// Check once and print a compact status token, eg. for a shell prompt
+    #[structopt(long, group = "action")]
+    pub short: bool,
+    /// Keep checking in the background without a tray icon
+    #[structopt(long, group = "action")]
+    pub daemon: bool,
     /// Show a specific icon to debug your design
     #[structopt(long, group = "action")]
     pub debug_icon: Option<Icon>,
+    /// Cycle through every state of the icon and the menu, a few seconds each
+    #[structopt(long, group = "action")]
+    pub debug_tour: bool,
+    /// Write a commented default config to this path, or stdout
+    #[structopt(long, group = "action")]
+    pub generate_config: Option<Option<PathBuf>>,
+    /// Overwrite an existing file with --generate-config or --install-service
+    #[structopt(long)]
+    pub force: bool,
+    /// Quit an already running tray icon and take over
+    #[structopt(long)]
+    pub replace: bool,
     /// Name of the icon theme
     #[structopt(long)]
     pub icon_theme: Option<Theme>,
+    /// Show made up results instead of checking, eg. `--mock cycle`
+    #[structopt(long)]
+    pub mock: Option<MockScenario>,
+    /// Write logs to this file instead of stderr
+    #[structopt(long)]
+    pub log_file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    pub subcommand: Option<SubCommand>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SubCommand {
+    /// Check once and print the result, exits with 1 if updates are missing
+    /// and 2 if the check failed
+    Check(Check),
+    /// Verify an icon theme contains all required icons
+    CheckTheme(CheckTheme),
+    /// Check arch-audit, the pacman database, icons and inotify
+    SelfTest,
+    /// Validate the config files, exits with 78 if they're invalid
+    CheckConfig,
+    /// Print the summary of the last check, without checking
+    Status,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Check {
+    /// Print the result as json, like the http endpoint
+    #[structopt(long)]
+    pub json: bool,
+    /// Print a report of the advisories instead, either json, csv or markdown
+    #[structopt(long, conflicts_with = "json")]
+    pub report: Option<Format>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CheckTheme {
+    /// Name of the icon theme
+    pub theme: Theme,
 }
 
 impl Args {
-    pub fn log_level(&self) -> &str {
+    /// Level requested with `-v`, if any
+    pub fn log_level(&self) -> Option<LevelFilter> {
         match self.verbose {
-            0 => "off",
-            1 => "info",
-            _ => "debug",
+            0 => None,
+            1 => Some(LevelFilter::Info),
+            _ => Some(LevelFilter::Debug),
         }
     }
 }
@@ -0,0 +1,67 @@
+use crate::errors::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const AUTOSTART_FILE: &str = "autostart/arch-audit-gtk.desktop";
+const DESKTOP_ENTRY: &str = include_str!("../contrib/arch-audit-gtk.desktop");
+
+/// Entry in `$XDG_CONFIG_HOME`, it takes precedence over the system ones
+fn user_path() -> Result<PathBuf> {
+    dirs_next::config_dir()
+        .map(|dir| dir.join(AUTOSTART_FILE))
+        .context("Failed to find the config directory")
+}
+
+/// Entries in `$XDG_CONFIG_DIRS`, the package installs one to `/etc/xdg`
+fn system_enabled() -> bool {
+    let dirs = env::var_os("XDG_CONFIG_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".into());
+    env::split_paths(&dirs).any(|dir| dir.join(AUTOSTART_FILE).is_file())
+}
+
+fn hidden(entry: &str) -> bool {
+    entry.lines().any(|line| {
+        matches!(
+            line.trim(),
+            "Hidden=true" | "X-GNOME-Autostart-enabled=false"
+        )
+    })
+}
+
+/// If arch-audit-gtk is started at login, either by the entry of the
+/// package or by one in the home directory
+pub fn is_enabled() -> bool {
+    match user_path().and_then(|path| Ok(fs::read_to_string(path)?)) {
+        Ok(entry) => !hidden(&entry),
+        Err(_) => system_enabled(),
+    }
+}
+
+/// Add or remove the entry in the home directory. The entry of the package
+/// can't be removed, so it's disabled with a hidden entry instead.
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let path = user_path()?;
+    let entry = match (enabled, system_enabled()) {
+        (true, false) => Some(DESKTOP_ENTRY.to_string()),
+        (false, true) => Some(format!("{}Hidden=true\n", DESKTOP_ENTRY)),
+        _ => None,
+    };
+
+    match entry {
+        Some(entry) => {
+            info!("Writing autostart entry to {:?}", path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| anyhow!("Failed to create {:?}", parent))?;
+            }
+            fs::write(&path, entry).with_context(|| anyhow!("Failed to write {:?}", path))
+        }
+        None if path.exists() => {
+            info!("Removing autostart entry {:?}", path);
+            fs::remove_file(&path).with_context(|| anyhow!("Failed to remove {:?}", path))
+        }
+        None => Ok(()),
+    }
+}
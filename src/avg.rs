@@ -0,0 +1,156 @@
+use crate::errors::*;
+use crate::pacman;
+use alpm::{Alpm, Ver, Version};
+use arch_audit::types::{Avg, Status as AvgStatus};
+use std::fs;
+use std::time::Duration;
+use structopt::StructOpt;
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Suffixes of foreign packages that repackage a release of the same name
+const FOREIGN_SUFFIXES: &[&str] = &["-bin"];
+
+/// Fetch all advisories from the security tracker. The source and proxy are
+/// read from the arch-audit config, so a proxy configured for arch-audit is
+/// used here too. `proxy` of our own config takes precedence.
+fn advisories(proxy: Option<&str>) -> Result<Vec<Avg>> {
+    let settings = settings()?;
+
+    let json = if settings.source.starts_with("https://") || settings.source.starts_with("http://")
+    {
+        download(&settings.source, proxy.or(settings.proxy.as_deref()))?
+    } else {
+        fs::read_to_string(&settings.source)
+            .with_context(|| anyhow!("Failed to read advisories from {:?}", settings.source))?
+    };
+    serde_json::from_str(&json).context("Failed to parse advisories from the security tracker")
+}
+
+/// If a package at `version` is affected by the advisory. Without a released
+/// fix it's only affected with `unfixed`.
+fn is_affected(avg: &Avg, version: &Ver, unfixed: bool) -> bool {
+    match (&avg.status, &avg.fixed) {
+        (AvgStatus::Unknown | AvgStatus::Vulnerable | AvgStatus::Testing, _) => unfixed,
+        (AvgStatus::Fixed, Some(fixed)) => version < Version::new(fixed.as_str()),
+        _ => false,
+    }
+}
+
+/// Fetch all advisories from the security tracker and keep the ones that
+/// affect installed packages and can be fixed by upgrading, like
+/// `arch-audit -u` does. With `unfixed` the ones without a released fix are
+/// kept too, like arch-audit does without `-u`.
+pub fn affected(dbpath: &str, unfixed: bool, proxy: Option<&str>) -> Result<Vec<Avg>> {
+    let mut avgs = advisories(proxy)?;
+
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    let db = alpm.localdb();
+    for avg in &mut avgs {
+        let affected = avg
+            .packages
+            .iter()
+            .filter(|pkg| match db.pkg(pkg.as_str()) {
+                Ok(pkg) => is_affected(avg, pkg.version(), unfixed),
+                Err(_) => false,
+            });
+        avg.packages = affected.cloned().collect();
+    }
+    avgs.retain(|avg| !avg.packages.is_empty());
+
+    Ok(avgs)
+}
+
+/// Like `affected`, but for foreign packages, eg. from the AUR. They're
+/// matched to the packages of the advisories by name, by name without a
+/// `-bin` suffix and by what they provide. VCS packages like `-git` build
+/// the latest source, they never match.
+pub fn affected_foreign(dbpath: &str, unfixed: bool, proxy: Option<&str>) -> Result<Vec<Avg>> {
+    let foreign = pacman::foreign_packages(dbpath)?;
+    if foreign.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut avgs = advisories(proxy)?;
+
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    let db = alpm.localdb();
+    let packages = foreign
+        .iter()
+        .filter_map(|name| db.pkg(name.as_str()).ok())
+        .collect::<Vec<_>>();
+    for avg in &mut avgs {
+        let affected = packages.iter().filter(|pkg| {
+            let name = pkg.name();
+            let base = FOREIGN_SUFFIXES
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix));
+            let by_name = [Some(name), base]
+                .into_iter()
+                .flatten()
+                .any(|name| avg.packages.iter().any(|p| p == name));
+            if by_name {
+                return is_affected(avg, pkg.version(), unfixed);
+            }
+            // provides without a version can't be compared
+            pkg.provides().iter().any(|dep| match dep.version() {
+                Some(version) if avg.packages.iter().any(|p| p == dep.name()) => {
+                    is_affected(avg, version, unfixed)
+                }
+                _ => false,
+            })
+        });
+        avg.packages = affected.map(|pkg| pkg.name().to_string()).collect();
+    }
+    avgs.retain(|avg| !avg.packages.is_empty());
+
+    Ok(avgs)
+}
+
+fn settings() -> Result<arch_audit::config::Config> {
+    let args = arch_audit::args::Args::from_iter(["arch-audit"]);
+    arch_audit::config::Config::load(&args).context("Failed to load arch-audit config")
+}
+
+/// Without a configured proxy reqwest uses `$https_proxy`, `$http_proxy` and
+/// `$all_proxy`, honoring `$no_proxy`. Credentials are taken from the url.
+fn build_client(proxy: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let mut client = reqwest::blocking::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(REQUEST_TIMEOUT);
+    if let Some(proxy) = proxy {
+        debug!("Connecting through proxy {}", redact_proxy(proxy));
+        client = client.proxy(reqwest::Proxy::all(proxy).context("Invalid proxy")?);
+    }
+    Ok(client.build()?)
+}
+
+/// The proxy url without its password, for the log
+fn redact_proxy(proxy: &str) -> String {
+    match proxy.split_once("://").and_then(|(scheme, rest)| {
+        let (auth, host) = rest.split_once('@')?;
+        let user = auth.split_once(':').map_or(auth, |(user, _)| user);
+        Some(format!("{}://{}:***@{}", scheme, user, host))
+    }) {
+        Some(redacted) => redacted,
+        None => proxy.to_string(),
+    }
+}
+
+/// Client for other requests to the security tracker, it uses the proxy of
+/// the arch-audit config too, unless `proxy` is set
+pub fn client(proxy: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let settings = settings()?;
+    build_client(proxy.or(settings.proxy.as_deref()))
+}
+
+fn download(url: &str, proxy: Option<&str>) -> Result<String> {
+    info!("Downloading advisories from {}", url);
+    build_client(proxy)?
+        .get(url)
+        .send()
+        .context("Failed to connect to the security tracker")?
+        .error_for_status()
+        .context("Security tracker replied with an error")?
+        .text()
+        .context("Failed to download advisories")
+}
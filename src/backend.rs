@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::updater::{self, CancelToken, Cve, ErrorKind, InvalidOutput, Status, Update};
+use arch_audit::types::{Severity, Status as AvgStatus};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Package names of the mock advisories
+const MOCK_PACKAGES: &[&str] = &[
+    "openssl", "curl", "glibc", "linux", "sudo", "openssh", "expat", "libxml2", "zlib", "python",
+];
+/// Number of advisories of the `many` scenario, more than fit into the menu
+const MOCK_MANY: usize = 250;
+
+/// Next scenario of `cycle`, shared by all mock backends since a new one is
+/// created for every check
+static CYCLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Source of the missing updates of a check
+pub trait Backend {
+    /// Failed checks are returned as errors, so they're classified and
+    /// retried like failed arch-audit runs
+    fn check(&self, cancel: &CancelToken) -> Result<Status>;
+}
+
+/// Backend selected by the config, `--mock` takes precedence
+pub fn for_config(config: &Config) -> Box<dyn Backend + '_> {
+    match config.mock {
+        Some(scenario) => Box::new(Mock { scenario }),
+        None => Box::new(SecurityTracker { config }),
+    }
+}
+
+/// The advisories of the security tracker, reported by arch-audit or
+/// downloaded directly by the native backend
+pub struct SecurityTracker<'a> {
+    config: &'a Config,
+}
+
+impl Backend for SecurityTracker<'_> {
+    fn check(&self, cancel: &CancelToken) -> Result<Status> {
+        updater::check_for_updates(self.config, cancel).map(Status::MissingUpdates)
+    }
+}
+
+/// Made up results to try every state of the gui without real advisories
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MockScenario {
+    Clean,
+    /// One advisory of each severity
+    Vulnerable,
+    Critical,
+    /// Advisories without a fixed version
+    Unfixed,
+    /// Enough advisories to fill the menu in the background
+    Many,
+    NetworkError,
+    MissingBinary,
+    PermissionDenied,
+    InvalidOutput,
+    /// A failure without a known cause
+    Error,
+    /// Each check returns the next of the other scenarios
+    Cycle,
+}
+
+const SCENARIOS: &[(&str, MockScenario)] = &[
+    ("clean", MockScenario::Clean),
+    ("vulnerable", MockScenario::Vulnerable),
+    ("critical", MockScenario::Critical),
+    ("unfixed", MockScenario::Unfixed),
+    ("many", MockScenario::Many),
+    ("network-error", MockScenario::NetworkError),
+    ("missing-binary", MockScenario::MissingBinary),
+    ("permission-denied", MockScenario::PermissionDenied),
+    ("invalid-output", MockScenario::InvalidOutput),
+    ("error", MockScenario::Error),
+    ("cycle", MockScenario::Cycle),
+];
+
+impl FromStr for MockScenario {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SCENARIOS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, scenario)| *scenario)
+            .with_context(|| {
+                let names = SCENARIOS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                anyhow!(
+                    "Unknown mock scenario {:?}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Returns the results of a scenario without running anything
+pub struct Mock {
+    scenario: MockScenario,
+}
+
+impl Backend for Mock {
+    fn check(&self, _cancel: &CancelToken) -> Result<Status> {
+        let scenario = match self.scenario {
+            MockScenario::Cycle => {
+                // every scenario except cycle itself
+                let i = CYCLE.fetch_add(1, Ordering::Relaxed) % (SCENARIOS.len() - 1);
+                SCENARIOS[i].1
+            }
+            scenario => scenario,
+        };
+        debug!("Returning mock result {:?}", scenario);
+
+        let severities = [
+            Severity::Critical,
+            Severity::High,
+            Severity::Medium,
+            Severity::Low,
+            Severity::Unknown,
+        ];
+        let mut updates = match scenario {
+            MockScenario::Clean => Vec::new(),
+            MockScenario::Vulnerable => severities
+                .iter()
+                .enumerate()
+                .map(|(i, severity)| mock_update(i, *severity, true))
+                .collect(),
+            MockScenario::Critical => vec![mock_update(0, Severity::Critical, true)],
+            MockScenario::Unfixed => vec![
+                mock_update(0, Severity::High, false),
+                mock_update(1, Severity::Medium, false),
+            ],
+            MockScenario::Many => (0..MOCK_MANY)
+                .map(|i| mock_update(i, severities[i % severities.len()], i % 7 != 0))
+                .collect(),
+            MockScenario::NetworkError => {
+                return Err(updater::known_error(
+                    ErrorKind::Network,
+                    "Mock: failed to connect to the security tracker".to_string(),
+                ))
+            }
+            MockScenario::MissingBinary => {
+                return Err(updater::known_error(
+                    ErrorKind::MissingBinary,
+                    "Mock: \"arch-audit\" not found".to_string(),
+                ))
+            }
+            MockScenario::PermissionDenied => {
+                return Err(updater::known_error(
+                    ErrorKind::PermissionDenied,
+                    "Mock: permission denied".to_string(),
+                ))
+            }
+            MockScenario::InvalidOutput => {
+                return Err(anyhow!("Mock: expected value at line 1 column 10").context(
+                    InvalidOutput {
+                        raw: "{\"mock\": invalid json".to_string(),
+                    },
+                ))
+            }
+            MockScenario::Error => bail!("Mock: arch-audit exited with 1"),
+            MockScenario::Cycle => unreachable!(),
+        };
+        updates.sort_by(|a, b| {
+            a.severity
+                .cmp(&b.severity)
+                .reverse()
+                .then(a.pkg.cmp(&b.pkg))
+        });
+        Ok(Status::MissingUpdates(updates))
+    }
+}
+
+pub fn mock_update(i: usize, severity: Severity, fixed: bool) -> Update {
+    let pkg = match i / MOCK_PACKAGES.len() {
+        0 => MOCK_PACKAGES[i].to_string(),
+        n => format!("{}{}", MOCK_PACKAGES[i % MOCK_PACKAGES.len()], n),
+    };
+    let avg = format!("AVG-{}", 9000 + i);
+    let kind = "arbitrary code execution".to_string();
+    let issues = vec![
+        format!("CVE-2099-{:04}", i * 2),
+        format!("CVE-2099-{:04}", i * 2 + 1),
+    ];
+    Update {
+        severity,
+        text: format!("{}: {} ({})", severity, pkg, kind),
+        link: format!("https://security.archlinux.org/{}", avg),
+        pkg,
+        avg,
+        repo: Some(if i.is_multiple_of(2) { "core" } else { "extra" }.to_string()),
+        installed: Some("1.0.0-1".to_string()),
+        kind,
+        avg_status: if fixed {
+            AvgStatus::Fixed
+        } else {
+            AvgStatus::Vulnerable
+        },
+        fixed: fixed.then(|| "1.0.1-1".to_string()),
+        cves: issues.iter().map(|id| Cve::new(id)).collect(),
+        issues,
+        new: false,
+        aur: false,
+        acknowledged: false,
+    }
+}
@@ -0,0 +1,118 @@
+use crate::avg;
+use crate::errors::*;
+use crate::updater::{CancelToken, CveDetails, Update};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const CACHE_DIR: &str = "arch-audit-gtk/cves";
+/// Details rarely change once a CVE is known, so they're kept for a week
+const CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Requests per check, the remaining CVEs are fetched by the next checks
+const MAX_REQUESTS: usize = 20;
+/// Pause between two requests, so the security tracker isn't flooded
+const REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+fn cache_path(id: &str) -> Option<PathBuf> {
+    // ids end up in a path, only accept the usual CVE-2021-1234
+    let valid = id.starts_with("CVE-") && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return None;
+    }
+    dirs_next::cache_dir().map(|dir| dir.join(CACHE_DIR).join(format!("{}.json", id)))
+}
+
+/// Cached details and if they're still fresh
+fn cached(id: &str) -> Option<(CveDetails, bool)> {
+    let path = cache_path(id)?;
+    let json = fs::read_to_string(&path).ok()?;
+    let details = match serde_json::from_str(&json) {
+        Ok(details) => details,
+        Err(err) => {
+            warn!("Ignoring invalid cache entry {:?}: {:#}", path, err);
+            return None;
+        }
+    };
+    let fresh = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < CACHE_TTL);
+    Some((details, fresh))
+}
+
+fn fetch(client: &reqwest::blocking::Client, link: &str, id: &str) -> Result<CveDetails> {
+    let url = format!("{}.json", link);
+    debug!("Fetching details of {} from {}", id, url);
+    let json = client
+        .get(&url)
+        .send()
+        .context("Failed to connect to the security tracker")?
+        .error_for_status()
+        .context("Security tracker replied with an error")?
+        .text()
+        .with_context(|| anyhow!("Failed to download details of {}", id))?;
+    let details = serde_json::from_str(&json)
+        .with_context(|| anyhow!("Failed to parse details of {}", id))?;
+
+    if let Some(path) = cache_path(id) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&path, json).with_context(|| anyhow!("Failed to write {:?}", path))?;
+    }
+    Ok(details)
+}
+
+/// Add the description, severity and attack vector to every CVE. They're
+/// read from the cache if possible, otherwise at most `MAX_REQUESTS` are
+/// downloaded from the security tracker. Outdated entries are still used if
+/// the download fails.
+pub fn enrich(updates: &mut [Update], proxy: Option<&str>, cancel: &CancelToken) {
+    let mut known = HashMap::<String, Option<CveDetails>>::new();
+    let mut client = None;
+    let mut requests = 0;
+
+    for cve in updates.iter_mut().flat_map(|u| u.cves.iter_mut()) {
+        if let Some(details) = known.get(&cve.id) {
+            cve.details = details.clone();
+            continue;
+        }
+        let cached = cached(&cve.id);
+        let details = match cached {
+            Some((details, true)) => Some(details),
+            stale if requests < MAX_REQUESTS && !cancel.is_cancelled() => {
+                if requests > 0 {
+                    thread::sleep(REQUEST_INTERVAL);
+                }
+                requests += 1;
+                let fetched = match &client {
+                    Some(client) => fetch(client, &cve.link, &cve.id),
+                    None => {
+                        avg::client(proxy).and_then(|c| fetch(client.insert(c), &cve.link, &cve.id))
+                    }
+                };
+                match fetched {
+                    Ok(details) => Some(details),
+                    Err(err) => {
+                        warn!("{:#}", err);
+                        // the tracker is probably unreachable, try again with the next check
+                        requests = MAX_REQUESTS;
+                        stale.map(|(details, _)| details)
+                    }
+                }
+            }
+            stale => stale.map(|(details, _)| details),
+        };
+        known.insert(cve.id.clone(), details.clone());
+        cve.details = details;
+    }
+    if requests > 0 {
+        info!(
+            "Fetched details of {} CVEs from the security tracker",
+            requests
+        );
+    }
+}
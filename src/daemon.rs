@@ -0,0 +1,199 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::hooks;
+use crate::metrics::Metrics;
+use crate::notifications::Notifier;
+use crate::notify::{setup_inotify_thread, Event, PidFile};
+use crate::oneshot;
+use crate::state::Acknowledged;
+use crate::updater::{self, severity_key, CancelToken, CheckResult, Snooze, Status};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+// glib only accepts a few signals, their numbers are the same on all linux
+// architectures
+const SIGINT: i32 = 2;
+const SIGUSR1: i32 = 10;
+const SIGTERM: i32 = 15;
+
+/// Run the updater without a tray icon, results are only exported through
+/// the status file, stdout, the http server, the unix socket and D-Bus
+pub fn run(config: &Config) -> Result<()> {
+    let (update_tx, update_rx) = mpsc::channel();
+    let (result_tx, result_rx) = updater::result_channel();
+    let updater_state = result_rx.state();
+
+    // lets the pacman hook signal us
+    let pid_file = PidFile::create();
+    let cancel = CancelToken::default();
+    let inotify_thread = if config.ssh_target.is_some() {
+        None
+    } else {
+        setup_inotify_thread(update_tx.clone(), None, cancel.clone(), config)?
+    };
+
+    #[cfg(feature = "http")]
+    let http = if config.http_enabled {
+        Some(crate::http::Server::spawn(&config.http_bind)?)
+    } else {
+        None
+    };
+    let socket = match (config.socket_enabled, &config.socket_path) {
+        (true, Some(path)) => Some(crate::socket::Server::spawn(
+            path,
+            config.socket_stream,
+            None,
+        )?),
+        (true, None) => {
+            warn!("The unix socket is enabled but $XDG_RUNTIME_DIR is unset, configure a path");
+            None
+        }
+        (false, _) => None,
+    };
+    let dbus = if config.dbus_enabled {
+        Some(crate::dbus::Service::spawn(update_tx.clone())?)
+    } else {
+        None
+    };
+    if socket.is_none()
+        && dbus.is_none()
+        && !config.http_enabled
+        && config.status_file.is_none()
+        && !config.waybar
+        && config.metrics_textfile.is_none()
+    {
+        warn!("No status file, waybar output, metrics, http server, unix socket or D-Bus service is enabled, results are only logged");
+    }
+
+    let updater_config = config.clone();
+    let updater_cancel = cancel.clone();
+    let updater_online = !config.wait_for_network || crate::dbus::watch_network(update_tx.clone());
+    #[cfg(feature = "http")]
+    let updater_http = http.clone();
+    let updater_thread = thread::spawn(move || {
+        updater::background(
+            updater_config,
+            updater_cancel,
+            Snooze::default(),
+            Acknowledged::load(),
+            HashSet::new(),
+            updater_online,
+            update_rx,
+            result_tx,
+        );
+        #[cfg(feature = "http")]
+        if let Some(http) = updater_http {
+            http.set_alive(false);
+        }
+    });
+
+    let main_loop = glib::MainLoop::new(None, false);
+    for signal in [SIGINT, SIGTERM] {
+        let main_loop = main_loop.clone();
+        glib::unix_signal_add_local(signal, move || {
+            info!("Received signal {}, shutting down", signal);
+            main_loop.quit();
+            glib::ControlFlow::Break
+        });
+    }
+    {
+        let update_tx = update_tx.clone();
+        let state = updater_state.clone();
+        glib::unix_signal_add_local(SIGUSR1, move || {
+            info!("Received SIGUSR1, checking for updates");
+            if state.request_check() {
+                update_tx.send(Event::Click).ok();
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    let socket_cleanup = socket.clone();
+    let config = config.clone();
+    let mut notifier = Notifier::default();
+    let mut metrics = Metrics::new(&config);
+    let mut status_hooks = hooks::StatusHooks::default();
+    result_rx.attach(move |msg| {
+        info!("{}", msg.text(&config.count_by));
+        hooks::handle(&config, &msg, false);
+        status_hooks.handle(&config, &msg);
+        notifier.handle(&config, &msg, false);
+        #[cfg(feature = "http")]
+        if let Some(http) = &http {
+            http.set_result(&msg);
+        }
+        if let Some(socket) = &socket {
+            socket.set_result(&msg);
+        }
+        if let Some(dbus) = &dbus {
+            dbus.set_result(&msg, &config);
+        }
+        if let Some(path) = &config.status_file {
+            if let Err(err) = write_status_file(path, &msg) {
+                warn!("Failed to write status file: {:#}", err);
+            }
+        }
+        if config.waybar {
+            println!("{}", waybar_json(&config, &msg));
+        }
+        if let Some(metrics) = &mut metrics {
+            metrics.record(&msg);
+        }
+        crate::motd::write(&config, &msg);
+    });
+
+    crate::systemd::ready();
+    crate::systemd::start_watchdog();
+    main_loop.run();
+    crate::systemd::stopping();
+
+    cancel.cancel();
+    updater_state.close();
+    update_tx.send(Event::Shutdown).ok();
+    if let Some(socket) = &socket_cleanup {
+        socket.cleanup();
+    }
+    if let Some(pid_file) = &pid_file {
+        pid_file.cleanup();
+    }
+    updater_thread.join().ok();
+    if let Some(inotify_thread) = inotify_thread {
+        inotify_thread.join().ok();
+    }
+    debug!("All threads have shut down");
+
+    Ok(())
+}
+
+/// Replace the status file atomically, so readers never see a partial file
+fn write_status_file(path: &Path, result: &CheckResult) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, result.to_json()? + "\n")
+        .with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| anyhow!("Failed to move status file to {:?}", path))
+}
+
+/// Output of a waybar custom module with `return-type = "json"`, the class
+/// is either `clean`, `error`, `offline` or the highest severity
+fn waybar_json(config: &Config, result: &CheckResult) -> serde_json::Value {
+    let (text, _) = oneshot::token(config, result);
+    let class = match &result.status {
+        Status::MissingUpdates(_) => result
+            .status
+            .alert_severity(config)
+            .map(severity_key)
+            .unwrap_or("clean"),
+        Status::Error(_) => "error",
+        Status::Offline => "offline",
+    };
+    serde_json::json!({
+        "text": text,
+        "tooltip": result.text(&config.count_by),
+        "class": class,
+        "alt": class,
+    })
+}
@@ -0,0 +1,490 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::notify::Event;
+use crate::updater::{severity_key, CheckResult, Status};
+use glib::ToVariant;
+use std::sync::{mpsc, Arc, Mutex};
+
+const BUS_NAME: &str = "org.archlinux.ArchAuditGtk";
+const OBJECT_PATH: &str = "/org/archlinux/ArchAuditGtk";
+const INTERFACE: &str = r#"
+<node>
+  <interface name="org.archlinux.ArchAuditGtk">
+    <property name="Status" type="s" access="read"/>
+    <property name="Count" type="u" access="read"/>
+    <property name="Severity" type="s" access="read"/>
+    <property name="Advisories" type="a(sss)" access="read"/>
+    <property name="Json" type="s" access="read"/>
+    <method name="Refresh"/>
+    <signal name="StatusChanged">
+      <arg name="status" type="s"/>
+      <arg name="count" type="u"/>
+      <arg name="severity" type="s"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Claimed by the tray for single instance enforcement, independent of the
+/// optional service above
+const INSTANCE_NAME: &str = "org.archlinux.ArchAuditGtk.Instance";
+const INSTANCE_PATH: &str = "/org/archlinux/ArchAuditGtk/Instance";
+const INSTANCE_INTERFACE: &str = r#"
+<node>
+  <interface name="org.archlinux.ArchAuditGtk.Instance">
+    <method name="Refresh"/>
+  </interface>
+</node>
+"#;
+
+const DBUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+// flags and replies of RequestName
+const ALLOW_REPLACEMENT: u32 = 0x1;
+const REPLACE_EXISTING: u32 = 0x2;
+const DO_NOT_QUEUE: u32 = 0x4;
+const PRIMARY_OWNER: u32 = 1;
+const ALREADY_OWNER: u32 = 4;
+
+const NM_NAME: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+// values of NMState, anything below global connectivity can't reach the
+// security tracker. Unknown is treated as online, so a confused
+// NetworkManager never blocks checks.
+const NM_STATE_UNKNOWN: u32 = 0;
+const NM_STATE_CONNECTED_GLOBAL: u32 = 70;
+// values of NMMetered that mean metered
+const NM_METERED_YES: u32 = 1;
+const NM_METERED_GUESS_YES: u32 = 3;
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE: &str = "org.freedesktop.appearance";
+/// value of color-scheme, the others are no preference and prefer light
+const PREFER_DARK: u32 = 1;
+
+/// power-profiles-daemon, older releases only have the legacy name
+const POWER_PROFILES: &[(&str, &str)] = &[
+    (
+        "org.freedesktop.UPower.PowerProfiles",
+        "/org/freedesktop/UPower/PowerProfiles",
+    ),
+    ("net.hadess.PowerProfiles", "/net/hadess/PowerProfiles"),
+];
+
+/// Properties of the last result
+#[derive(Debug, Clone)]
+struct State {
+    /// Either `unknown`, `clean`, `vulnerable`, `error` or `offline`
+    status: &'static str,
+    /// Number of vulnerable packages or advisories, depending on `count_by`
+    count: u32,
+    /// Highest severity, `none` without advisories
+    severity: &'static str,
+    /// Advisory, package and severity of every missing update
+    advisories: Vec<(String, String, String)>,
+    json: String,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            status: "unknown",
+            count: 0,
+            severity: "none",
+            advisories: Vec::new(),
+            json: "null".to_string(),
+        }
+    }
+}
+
+/// The `org.archlinux.ArchAuditGtk` service on the session bus
+///
+/// It exposes the last result as properties, emits `StatusChanged` after
+/// every check and `Refresh()` requests a new check.
+#[derive(Debug, Clone)]
+pub struct Service {
+    connection: gio::DBusConnection,
+    state: Arc<Mutex<State>>,
+}
+
+impl Service {
+    pub fn spawn(update_tx: mpsc::Sender<Event>) -> Result<Self> {
+        let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)
+            .context("Failed to connect to the session bus")?;
+        let interface = gio::DBusNodeInfo::for_xml(INTERFACE)?
+            .lookup_interface(BUS_NAME)
+            .context("D-Bus interface is missing")?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let properties = state.clone();
+        let update_tx = Mutex::new(update_tx);
+        connection
+            .register_object(
+                OBJECT_PATH,
+                &interface,
+                move |_, _, _, _, method, _, invocation| match method {
+                    "Refresh" => {
+                        info!("Check requested over D-Bus");
+                        update_tx.lock().unwrap().send(Event::Click).ok();
+                        invocation.return_value(None);
+                    }
+                    _ => invocation.return_dbus_error(
+                        "org.freedesktop.DBus.Error.UnknownMethod",
+                        &format!("Unknown method: {}", method),
+                    ),
+                },
+                move |_, _, _, _, property| {
+                    let state = properties.lock().unwrap();
+                    match property {
+                        "Status" => state.status.to_variant(),
+                        "Count" => state.count.to_variant(),
+                        "Severity" => state.severity.to_variant(),
+                        "Advisories" => state.advisories.to_variant(),
+                        _ => state.json.to_variant(),
+                    }
+                },
+                |_, _, _, _, _, _| false,
+            )
+            .context("Failed to register D-Bus object")?;
+
+        gio::bus_own_name_on_connection(
+            &connection,
+            BUS_NAME,
+            gio::BusNameOwnerFlags::NONE,
+            |_, name| info!("Acquired D-Bus name {}", name),
+            |_, name| {
+                warn!(
+                    "Failed to acquire D-Bus name {}, is another instance running?",
+                    name
+                )
+            },
+        );
+
+        Ok(Service { connection, state })
+    }
+
+    pub fn set_result(&self, result: &CheckResult, config: &Config) {
+        let json = match result.to_json() {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("{:#}", err);
+                return;
+            }
+        };
+        let state = match &result.status {
+            Status::MissingUpdates(updates) => State {
+                // acknowledged advisories don't make it vulnerable, like the icon
+                status: match result.status.alert_severity(config) {
+                    Some(_) => "vulnerable",
+                    None => "clean",
+                },
+                count: result.count(&config.count_by) as u32,
+                severity: result
+                    .status
+                    .max_severity()
+                    .map(severity_key)
+                    .unwrap_or("none"),
+                advisories: updates
+                    .iter()
+                    .map(|u| {
+                        (
+                            u.avg.clone(),
+                            u.pkg.clone(),
+                            severity_key(u.severity).to_string(),
+                        )
+                    })
+                    .collect(),
+                json,
+            },
+            Status::Error(_) => State {
+                status: "error",
+                json,
+                ..State::default()
+            },
+            Status::Offline => State {
+                status: "offline",
+                json,
+                ..State::default()
+            },
+        };
+
+        let signal = (state.status, state.count, state.severity).to_variant();
+        *self.state.lock().unwrap() = state;
+        if let Err(err) =
+            self.connection
+                .emit_signal(None, OBJECT_PATH, BUS_NAME, "StatusChanged", Some(&signal))
+        {
+            warn!("Failed to emit D-Bus signal: {:#}", err);
+        }
+    }
+}
+
+/// Make sure only one tray icon is running
+///
+/// Returns false if another instance already owns the name, that instance is
+/// asked to check for updates instead. With `replace` the running instance
+/// quits and we take over. Without a session bus every instance runs.
+pub fn claim_instance(replace: bool, update_tx: mpsc::Sender<Event>) -> Result<bool> {
+    let connection = match gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>) {
+        Ok(connection) => connection,
+        Err(err) => {
+            warn!(
+                "Failed to connect to the session bus, not checking for other instances: {:#}",
+                err
+            );
+            return Ok(true);
+        }
+    };
+
+    let mut flags = ALLOW_REPLACEMENT | DO_NOT_QUEUE;
+    if replace {
+        flags |= REPLACE_EXISTING;
+    }
+    let (reply,) = connection
+        .call_sync(
+            Some(DBUS_NAME),
+            DBUS_PATH,
+            DBUS_NAME,
+            "RequestName",
+            Some(&(INSTANCE_NAME, flags).to_variant()),
+            Some(glib::VariantTy::new("(u)")?),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .context("Failed to request D-Bus name")?
+        .get::<(u32,)>()
+        .context("Invalid reply to RequestName")?;
+
+    if reply != PRIMARY_OWNER && reply != ALREADY_OWNER {
+        info!("arch-audit-gtk is already running, requesting a check instead");
+        connection
+            .call_sync(
+                Some(INSTANCE_NAME),
+                INSTANCE_PATH,
+                INSTANCE_NAME,
+                "Refresh",
+                None,
+                None,
+                gio::DBusCallFlags::NONE,
+                -1,
+                None::<&gio::Cancellable>,
+            )
+            .context("Failed to reach the running instance")?;
+        return Ok(false);
+    }
+
+    let interface = gio::DBusNodeInfo::for_xml(INSTANCE_INTERFACE)?
+        .lookup_interface(INSTANCE_NAME)
+        .context("D-Bus interface is missing")?;
+    let update_tx = Mutex::new(update_tx);
+    connection
+        .register_object(
+            INSTANCE_PATH,
+            &interface,
+            move |_, _, _, _, method, _, invocation| {
+                if method == "Refresh" {
+                    info!("Check requested by another instance");
+                    update_tx.lock().unwrap().send(Event::Click).ok();
+                }
+                invocation.return_value(None);
+            },
+            |_, _, _, _, _| ().to_variant(),
+            |_, _, _, _, _, _| false,
+        )
+        .context("Failed to register D-Bus object")?;
+
+    // started with --replace somewhere else
+    connection.signal_subscribe(
+        Some(DBUS_NAME),
+        Some(DBUS_NAME),
+        Some("NameLost"),
+        Some(DBUS_PATH),
+        Some(INSTANCE_NAME),
+        gio::DBusSignalFlags::NONE,
+        |_, _, _, _, _, _| {
+            info!("Replaced by another instance, quitting");
+            gtk::main_quit();
+        },
+    );
+
+    Ok(true)
+}
+
+fn nm_online(state: u32) -> bool {
+    state == NM_STATE_UNKNOWN || state >= NM_STATE_CONNECTED_GLOBAL
+}
+
+/// Send `Event::Network` whenever NetworkManager goes online or offline, and
+/// return the current state. Without NetworkManager we're always online.
+pub fn watch_network(update_tx: mpsc::Sender<Event>) -> bool {
+    let connection = match gio::bus_get_sync(gio::BusType::System, None::<&gio::Cancellable>) {
+        Ok(connection) => connection,
+        Err(err) => {
+            warn!(
+                "Failed to connect to the system bus, not watching the network: {:#}",
+                err
+            );
+            return true;
+        }
+    };
+
+    let state = system_property(NM_NAME, NM_PATH, NM_NAME, "State")
+        .and_then(|state| state.get::<u32>().context("Invalid NetworkManager state"));
+    let state = match state {
+        Ok(state) => state,
+        Err(err) => {
+            debug!(
+                "NetworkManager isn't available, not watching the network: {:#}",
+                err
+            );
+            return true;
+        }
+    };
+    debug!("NetworkManager state is {}", state);
+
+    let update_tx = Mutex::new(update_tx);
+    connection.signal_subscribe(
+        Some(NM_NAME),
+        Some(NM_NAME),
+        Some("StateChanged"),
+        Some(NM_PATH),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            if let Some((state,)) = params.get::<(u32,)>() {
+                debug!("NetworkManager state changed to {}", state);
+                update_tx
+                    .lock()
+                    .unwrap()
+                    .send(Event::Network(nm_online(state)))
+                    .ok();
+            }
+        },
+    );
+
+    nm_online(state)
+}
+
+/// Read a property of a service on the system bus
+fn system_property(
+    name: &str,
+    path: &str,
+    interface: &str,
+    property: &str,
+) -> Result<glib::Variant> {
+    let connection = gio::bus_get_sync(gio::BusType::System, None::<&gio::Cancellable>)
+        .context("Failed to connect to the system bus")?;
+    let reply = connection
+        .call_sync(
+            Some(name),
+            path,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(interface, property).to_variant()),
+            Some(glib::VariantTy::new("(v)")?),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .with_context(|| anyhow!("Failed to get {} of {}", property, name))?;
+    let (value,) = reply
+        .get::<(glib::Variant,)>()
+        .with_context(|| anyhow!("Invalid reply for {} of {}", property, name))?;
+    Ok(value)
+}
+
+/// If NetworkManager thinks the primary connection is metered, unknown
+/// counts as not metered
+pub fn is_metered() -> bool {
+    match system_property(NM_NAME, NM_PATH, NM_NAME, "Metered").map(|v| v.get::<u32>()) {
+        Ok(Some(metered)) => matches!(metered, NM_METERED_YES | NM_METERED_GUESS_YES),
+        Ok(None) => false,
+        Err(err) => {
+            debug!("Can't tell if the connection is metered: {:#}", err);
+            false
+        }
+    }
+}
+
+/// If power-profiles-daemon is in power saver mode
+pub fn is_power_saver() -> bool {
+    for (name, path) in POWER_PROFILES {
+        match system_property(name, path, name, "ActiveProfile").map(|v| v.get::<String>()) {
+            Ok(profile) => return profile.as_deref() == Some("power-saver"),
+            Err(err) => debug!("Can't read the power profile: {:#}", err),
+        }
+    }
+    false
+}
+
+/// Unwrap the variants around a setting, `Read` of older portals nests them
+fn prefers_dark(mut value: glib::Variant) -> Option<bool> {
+    while let Some(inner) = value.as_variant() {
+        value = inner;
+    }
+    value.get::<u32>().map(|scheme| scheme == PREFER_DARK)
+}
+
+/// The dark mode preference of the desktop, from the settings portal.
+/// Unset if there's no portal or it doesn't know the setting.
+pub fn prefers_dark_scheme() -> Option<bool> {
+    let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>).ok()?;
+    let reply = connection
+        .call_sync(
+            Some(PORTAL_NAME),
+            PORTAL_PATH,
+            PORTAL_SETTINGS,
+            "Read",
+            Some(&(APPEARANCE, "color-scheme").to_variant()),
+            Some(glib::VariantTy::new("(v)").ok()?),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .map_err(|err| {
+            debug!(
+                "Can't read the color scheme from the settings portal: {:#}",
+                err
+            )
+        })
+        .ok()?;
+    let (value,) = reply.get::<(glib::Variant,)>()?;
+    prefers_dark(value)
+}
+
+/// Call `f` whenever the desktop switches between light and dark mode
+pub fn watch_color_scheme(f: impl Fn(bool) + 'static) {
+    let connection = match gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>) {
+        Ok(connection) => connection,
+        Err(err) => {
+            warn!(
+                "Failed to connect to the session bus, not watching the color scheme: {:#}",
+                err
+            );
+            return;
+        }
+    };
+    connection.signal_subscribe(
+        Some(PORTAL_NAME),
+        Some(PORTAL_SETTINGS),
+        Some("SettingChanged"),
+        Some(PORTAL_PATH),
+        Some(APPEARANCE),
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            let Some((_, key, value)) = params.get::<(String, String, glib::Variant)>() else {
+                return;
+            };
+            if key != "color-scheme" {
+                return;
+            }
+            if let Some(dark) = prefers_dark(value) {
+                debug!("Color scheme changed, dark: {}", dark);
+                f(dark);
+            }
+        },
+    );
+}
@@ -0,0 +1,153 @@
+use crate::errors::*;
+use crate::gui::open_link;
+use crate::i18n::tr;
+use crate::updater::{severity_name, Update};
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const PKG: u32 = 0;
+const AVG: u32 = 1;
+const ISSUES: u32 = 2;
+const SEVERITY: u32 = 3;
+const INSTALLED: u32 = 4;
+const FIXED: u32 = 5;
+const STATUS: u32 = 6;
+/// Hidden columns, the severity is sorted by rank instead of by name
+const RANK: u32 = 7;
+const LINK: u32 = 8;
+const TOOLTIP: u32 = 9;
+
+const COLUMNS: &[(&str, u32, u32)] = &[
+    ("Package", PKG, PKG),
+    ("Advisory", AVG, AVG),
+    ("Issues", ISSUES, ISSUES),
+    ("Severity", SEVERITY, RANK),
+    ("Installed", INSTALLED, INSTALLED),
+    ("Fixed in", FIXED, FIXED),
+    ("Status", STATUS, STATUS),
+];
+
+fn store(updates: &[Update]) -> gtk::ListStore {
+    let store = gtk::ListStore::new(&[
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::U32,
+        glib::Type::STRING,
+        glib::Type::STRING,
+    ]);
+    for update in updates {
+        // the tooltip column is markup
+        let tooltip = update
+            .cves
+            .iter()
+            .filter_map(|cve| {
+                let details = cve.details.as_ref()?;
+                Some(format!(
+                    "<b>{}</b>: {}",
+                    cve.id,
+                    glib::markup_escape_text(&details.summary())
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        store.insert_with_values(
+            None,
+            &[
+                (PKG, &update.pkg),
+                (AVG, &update.avg),
+                (ISSUES, &update.issues.join(", ")),
+                (SEVERITY, &severity_name(update.severity)),
+                (INSTALLED, &update.installed.as_deref().unwrap_or("")),
+                (FIXED, &update.fixed.as_deref().unwrap_or("")),
+                (STATUS, &format!("{:?}", update.avg_status)),
+                (RANK, &(update.severity as u32)),
+                (LINK, &update.link),
+                (TOOLTIP, &tooltip),
+            ],
+        );
+    }
+    store
+}
+
+/// Rows that contain the search text in any visible column
+fn matches(model: &gtk::TreeModel, iter: &gtk::TreeIter, needle: &str) -> bool {
+    needle.is_empty()
+        || COLUMNS.iter().any(|(_, column, _)| {
+            model
+                .value(iter, *column as i32)
+                .get::<String>()
+                .is_ok_and(|value| value.to_lowercase().contains(needle))
+        })
+}
+
+/// Open a window listing all advisories in a sortable table
+///
+/// Unlike the menu this stays usable with many affected packages. Activating
+/// a row opens the advisory in the browser.
+pub fn show(updates: &[Update], open_cmd: Option<String>) {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&tr("Security advisories"));
+    window.set_default_size(900, 500);
+
+    let search = gtk::SearchEntry::new();
+    let needle = Rc::new(RefCell::new(String::new()));
+
+    let store = store(updates);
+    let filter = gtk::TreeModelFilter::new(&store, None);
+    {
+        let needle = needle.clone();
+        filter.set_visible_func(move |model, iter| matches(model, iter, &needle.borrow()));
+    }
+    let sorted = gtk::TreeModelSort::new(&filter);
+    sorted.set_sort_column_id(gtk::SortColumn::Index(RANK), gtk::SortType::Descending);
+
+    let view = gtk::TreeView::with_model(&sorted);
+    view.set_tooltip_column(TOOLTIP as i32);
+    for (title, column, sort) in COLUMNS {
+        let renderer = gtk::CellRendererText::new();
+        let col = gtk::TreeViewColumn::with_attributes(
+            &tr(title),
+            &renderer,
+            &[("text", *column as i32)],
+        );
+        col.set_sort_column_id(*sort as i32);
+        col.set_resizable(true);
+        view.append_column(&col);
+    }
+    view.connect_row_activated(move |view, path, _| {
+        let Some(model) = view.model() else {
+            return;
+        };
+        let Some(iter) = model.iter(path) else {
+            return;
+        };
+        if let Ok(link) = model.value(&iter, LINK as i32).get::<String>() {
+            if let Err(err) = open_link(open_cmd.as_deref(), &link) {
+                warn!("Failed to open link: {:#}", err);
+            }
+        }
+    });
+
+    search.connect_search_changed(move |search| {
+        *needle.borrow_mut() = search.text().to_lowercase();
+        filter.refilter();
+    });
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.add(&view);
+    scrolled.set_vexpand(true);
+
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    vbox.pack_start(&search, false, false, 0);
+    vbox.pack_start(&scrolled, true, true, 0);
+    window.add(&vbox);
+
+    window.show_all();
+    window.present();
+}
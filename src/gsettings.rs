@@ -0,0 +1,125 @@
+//! Preferences stored with GSettings instead of the config file
+//!
+//! Only the options of the preferences dialog are part of the schema. Keys
+//! that were changed are applied on top of the config files, everything else
+//! keeps the value of the files.
+
+use crate::config::ConfigFile;
+use crate::errors::*;
+use crate::preferences::Preferences;
+use crate::updater::severity_key;
+use gio::prelude::*;
+
+pub const SCHEMA_ID: &str = "org.archlinux.ArchAuditGtk";
+
+/// GSettings keys and the config options they replace
+const KEYS: &[(&str, &str, &str)] = &[
+    ("icon-theme", "design", "icon_theme"),
+    ("check-interval", "updater", "check_interval"),
+    ("ignore", "updater", "ignore"),
+    ("min-severity", "alerts", "min_severity"),
+    ("notifications-enabled", "notifications", "enabled"),
+    (
+        "notifications-per-advisory",
+        "notifications",
+        "per_advisory",
+    ),
+];
+
+fn settings() -> Result<gio::Settings> {
+    // gio aborts on unknown schemas, so it's looked up first
+    let installed = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(SCHEMA_ID, true))
+        .is_some();
+    if !installed {
+        bail!("The GSettings schema {} is not installed", SCHEMA_ID);
+    }
+    Ok(gio::Settings::new(SCHEMA_ID))
+}
+
+fn to_toml(value: &glib::Variant) -> Option<toml::Value> {
+    if let Some(b) = value.get::<bool>() {
+        Some(toml::Value::Boolean(b))
+    } else if let Some(n) = value.get::<u32>() {
+        Some(toml::Value::Integer(n.into()))
+    } else if let Some(s) = value.str() {
+        Some(toml::Value::String(s.to_string()))
+    } else {
+        let list = value.get::<Vec<String>>()?;
+        Some(toml::Value::Array(
+            list.into_iter().map(toml::Value::String).collect(),
+        ))
+    }
+}
+
+/// Build a config from the keys that were changed by the user
+pub fn load() -> Result<ConfigFile> {
+    let settings = settings()?;
+    let mut table = toml::Table::new();
+
+    for (key, section, option) in KEYS {
+        let Some(value) = settings.user_value(key) else {
+            continue;
+        };
+        let Some(value) = to_toml(&value) else {
+            warn!(
+                "Ignoring gsettings key {} with unexpected type {}",
+                key,
+                value.type_()
+            );
+            continue;
+        };
+        debug!("Applying config from gsettings: {}.{}", section, option);
+        if let toml::Value::Table(section) = table
+            .entry(*section)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            section.insert(option.to_string(), value);
+        }
+    }
+
+    ConfigFile::from_table(table)
+}
+
+/// Write the preferences that differ from `old`, other instances pick them
+/// up through `watch`. Keys that aren't written keep following the files.
+pub fn save(prefs: &Preferences, old: &Preferences) -> Result<()> {
+    let settings = settings()?;
+    info!("Writing preferences to gsettings {}", SCHEMA_ID);
+    if prefs.icon_theme != old.icon_theme {
+        settings.set_string("icon-theme", prefs.icon_theme.as_str())?;
+    }
+    if prefs.check_interval != old.check_interval {
+        let interval = u32::try_from(prefs.check_interval.as_secs()).unwrap_or(u32::MAX);
+        settings.set_uint("check-interval", interval)?;
+    }
+    if prefs.ignore != old.ignore {
+        let ignore = prefs.ignore.iter().map(String::as_str).collect::<Vec<_>>();
+        settings.set_strv("ignore", ignore.as_slice())?;
+    }
+    if prefs.min_severity != old.min_severity {
+        settings.set_string("min-severity", severity_key(prefs.min_severity))?;
+    }
+    if prefs.notifications_enabled != old.notifications_enabled {
+        settings.set_boolean("notifications-enabled", prefs.notifications_enabled)?;
+    }
+    if prefs.notifications_per_advisory != old.notifications_per_advisory {
+        settings.set_boolean(
+            "notifications-per-advisory",
+            prefs.notifications_per_advisory,
+        )?;
+    }
+    gio::Settings::sync();
+    Ok(())
+}
+
+/// Call `f` whenever a key changes, eg. with dconf-editor. The returned
+/// object has to be kept around, the callback stops once it's dropped.
+pub fn watch(f: impl Fn() + 'static) -> Result<gio::Settings> {
+    let settings = settings()?;
+    settings.connect_changed(None, move |_, key| {
+        debug!("Gsettings key {} changed", key);
+        f();
+    });
+    Ok(settings)
+}
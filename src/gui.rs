@@ -1,32 +1,195 @@
+use crate::args::Args;
 use crate::config::Config;
 use crate::errors::*;
-use crate::notify::{setup_inotify_thread, Event};
-use crate::updater::{self, Status};
+use crate::hooks;
+use crate::i18n::{ntr, tr};
+use crate::indicator::Indicator;
+use crate::metrics::Metrics;
+use crate::notifications::{self, Action, Notifier};
+use crate::notify::{setup_inotify_thread, Event, PidFile};
+use crate::state::Acknowledged;
+use crate::updater::{
+    self, CancelToken, CheckResult, ErrorKind, RemoteResult, Snooze, SnoozeUntil, Status, Update,
+    UpdaterState,
+};
+use arch_audit::types::Severity;
 use gtk::prelude::*;
-use libappindicator::{AppIndicator, AppIndicatorStatus};
+use libappindicator::AppIndicatorStatus;
 use serde::{de, Deserialize, Deserializer};
-use std::path::Path;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const CHECK_FOR_UPDATE: &str = "Check for updates";
+// underscores mark the mnemonic, keep them unique within the menu
+const CHECK_FOR_UPDATE: &str = "_Check for updates";
 const CHECKING: &str = "Checking...";
-const QUIT: &str = "Quit";
+const NEWS: &str = "Arch _news";
+const SNOOZE: &str = "_Snooze";
+const SNOOZE_OPTIONS: &[(&str, SnoozeFor)] = &[
+    ("For _1 hour", SnoozeFor::Hours(1)),
+    ("For _4 hours", SnoozeFor::Hours(4)),
+    ("Until _tomorrow", SnoozeFor::Tomorrow),
+    ("Until next _upgrade", SnoozeFor::Upgrade),
+];
+const QUIT: &str = "_Quit";
+const AUTOSTART: &str = "Start at _login";
+const MUTE: &str = "_Mute sounds";
+const HISTORY: &str = "Recent _advisories...";
+const STATISTICS: &str = "S_tatistics...";
+const PREFERENCES: &str = "_Preferences...";
+const ABOUT: &str = "A_bout...";
+const FILTER: &str = "_Filter...";
+const OPEN_ALL: &str = "_Open all advisories";
+const DETAILS: &str = "Show _details...";
+const COPY_CVES: &str = "Copy _CVE IDs";
+const COPY_REPORT: &str = "Copy _report";
+const SAVE_REPORT: &str = "Sa_ve report...";
+const FIXABLE: &str = "Fixable now";
+const UNFIXED: &str = "No fix released yet";
+const UPGRADE: &str = "_Install updates";
+const UPGRADE_VULNERABLE: &str = "Upgrade _vulnerable packages...";
+const UPGRADING: &str = "Installing updates...";
+
+// don't queue another check if the menu is opened repeatedly
+const RECHECK_DEBOUNCE: Duration = Duration::from_secs(60);
+// the start and end of quiet hours are noticed within a minute
+const QUIET_HOURS_INTERVAL: u32 = 60;
+// only offer a filter if the list doesn't fit on a screen anyway
+const FILTER_MIN_ITEMS: usize = 15;
+/// Packages named in the label of an advisory with `[menu] group_packages`
+const GROUP_MAX_PACKAGES: usize = 3;
+// menu rows built immediately and per idle callback, a few hundred items at
+// once noticeably block the main loop. A batch in an idle callback should fit
+// in a frame (16ms at 60Hz) so an open menu keeps redrawing while it grows.
+// There are no timings behind these numbers yet: 100 covers the advisories of
+// a typical system, so those menus are complete right away, and 50 is half of
+// it. `-vv --mock many` logs how long each batch took, to pick them from that.
+const MENU_SYNC_ROWS: usize = 100;
+const MENU_IDLE_ROWS: usize = 50;
+
+/// Reloads the config
+const SIGHUP: i32 = 1;
+const SIGINT: i32 = 2;
+/// Checks for updates
+const SIGUSR1: i32 = 10;
+/// Sent by systemd to stop the unit
+const SIGTERM: i32 = 15;
+
+/// Relative to `$XDG_DATA_HOME` and every entry of `$XDG_DATA_DIRS`
+const ICON_DIR: &str = "arch-audit-gtk/icons";
+/// Uses the named icons of the system icon theme instead of our own
+const SYSTEM_THEME: &str = "system";
+/// Relative to `$XDG_RUNTIME_DIR`, recolored themes are written here
+const RECOLOR_DIR: &str = "arch-audit-gtk/icons";
+/// Icons every theme needs to provide
+const REQUIRED_ICONS: &[Icon] = &[Icon::Check, Icon::Alert, Icon::Cross];
+/// Icons a theme can leave out, the severities fall back to `alert` of the
+/// theme and the others to the system icon theme
+const OPTIONAL_ICONS: &[Icon] = &[
+    Icon::Severity(Severity::Critical),
+    Icon::Severity(Severity::High),
+    Icon::Severity(Severity::Medium),
+    Icon::Severity(Severity::Low),
+    Icon::Attention,
+    Icon::Checking(0),
+    Icon::Checking(1),
+    Icon::Checking(2),
+    Icon::MissingBinary,
+    Icon::NetworkError,
+    Icon::PermissionDenied,
+    Icon::Reboot,
+];
+/// Frames of the animation shown while a check is running
+const CHECKING_FRAMES: &[&str] = &["checking-1", "checking-2", "checking-3"];
+const ANIMATION_INTERVAL: Duration = Duration::from_millis(250);
+
+/// States that can be remapped to a different icon with the `[icons]` table
+pub const ICON_STATES: &[&str] = &[
+    "clean",
+    "alert",
+    "error",
+    "snoozed",
+    "upgrading",
+    "attention",
+    "checking",
+    "critical",
+    "high",
+    "medium",
+    "low",
+    "unknown",
+    "missing_binary",
+    "network_error",
+    "invalid_output",
+    "permission_denied",
+    "reboot",
+];
 
 #[derive(Debug)]
 pub enum Icon {
     Check,
     Alert,
+    /// Alert colored by the highest severity, advisories of unknown severity
+    /// use `Alert`
+    Severity(Severity),
     Cross,
+    /// Not part of the themes, uses the system icon unless configured
+    Upgrading,
+    /// Shown by panels that highlight critical advisories
+    Attention,
+    /// Frame of the checking animation
+    Checking(usize),
+    /// Failed checks with a known cause, the others use `Cross`
+    MissingBinary,
+    NetworkError,
+    PermissionDenied,
+    /// Nothing to install, but upgrades are waiting for a reboot
+    Reboot,
+    /// Icon name configured by the user, validated during config load
+    Custom(String),
 }
 
 impl Icon {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Check => "check",
-            Self::Alert => "alert",
+            Self::Alert | Self::Severity(Severity::Unknown) => "alert",
+            Self::Severity(Severity::Critical) => "alert-critical",
+            Self::Severity(Severity::High) => "alert-high",
+            Self::Severity(Severity::Medium) => "alert-medium",
+            Self::Severity(Severity::Low) => "alert-low",
             Self::Cross => "cross",
+            Self::Upgrading => "upgrading",
+            Self::Attention => "attention",
+            Self::Checking(frame) => CHECKING_FRAMES[frame % CHECKING_FRAMES.len()],
+            Self::MissingBinary => "missing-binary",
+            Self::NetworkError => "network-error",
+            Self::PermissionDenied => "permission-denied",
+            Self::Reboot => "reboot",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Named icon from the freedesktop icon theme, used if our own icons
+    /// are not installed
+    pub fn fallback_name(&self) -> &'static str {
+        match self {
+            Self::Check => "security-high",
+            Self::Alert | Self::Severity(_) | Self::Custom(_) => "dialog-warning",
+            Self::Cross => "dialog-error",
+            Self::Upgrading => "system-software-update",
+            Self::Attention => "software-update-urgent",
+            Self::Checking(_) => "view-refresh",
+            Self::MissingBinary => "system-software-install",
+            Self::NetworkError => "network-error",
+            Self::PermissionDenied => "changes-prevent",
+            Self::Reboot => "system-reboot",
         }
     }
 }
@@ -38,7 +201,12 @@ impl FromStr for Icon {
         match s {
             "check" => Ok(Self::Check),
             "alert" => Ok(Self::Alert),
+            "alert-critical" => Ok(Self::Severity(Severity::Critical)),
+            "alert-high" => Ok(Self::Severity(Severity::High)),
+            "alert-medium" => Ok(Self::Severity(Severity::Medium)),
+            "alert-low" => Ok(Self::Severity(Severity::Low)),
             "cross" => Ok(Self::Cross),
+            "upgrading" => Ok(Self::Upgrading),
             _ => bail!("Invalid icon name: {:?}", s),
         }
     }
@@ -50,9 +218,13 @@ pub struct Theme {
 }
 
 impl Theme {
-    fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &str {
         &self.s
     }
+
+    pub fn is_system(&self) -> bool {
+        self.s == SYSTEM_THEME
+    }
 }
 
 impl Default for Theme {
@@ -88,39 +260,516 @@ impl FromStr for Theme {
     }
 }
 
+/// Which subdirectory of the theme is used, if it has `light` or `dark` icons
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconVariant {
+    /// Follow the dark mode preference of the desktop
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Package,
+    Repo,
+}
+
+/// What happens when an advisory in the menu is clicked
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemAction {
+    #[default]
+    Open,
+    Copy,
+    Command,
+    Details,
+    /// Stop counting and alerting about the advisory, or start again
+    Acknowledge,
+}
+
+impl ItemAction {
+    fn label(&self, update: &Update) -> &'static str {
+        match self {
+            Self::Open => "Open advisory",
+            Self::Copy => "Copy advisory ID",
+            Self::Command => "Run command",
+            Self::Details => "Details",
+            Self::Acknowledge if update.acknowledged => "Alert again",
+            Self::Acknowledge => "Acknowledge",
+        }
+    }
+}
+
+/// Directories that are searched for icon themes, in order. Themes in the
+/// home directory take precedence over the installed ones, so they can be
+/// added without root.
+pub fn icon_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("./icons")];
+    if let Some(dir) = dirs_next::data_dir() {
+        dirs.push(dir.join(ICON_DIR));
+    }
+    let data_dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    dirs.extend(env::split_paths(&data_dirs).map(|dir| dir.join(ICON_DIR)));
+    dirs
+}
+
+/// A theme directory that has been resolved once, with the icons it contains
+#[derive(Debug)]
+pub struct ThemeDir {
+    path: PathBuf,
+    icons: HashSet<String>,
+}
+
+impl ThemeDir {
+    /// Look up a theme in all icon directories, this doesn't require any
+    /// icon to be present
+    pub fn find(theme: &Theme) -> Option<Self> {
+        let path = icon_dirs().iter().find_map(|path| {
+            let theme_path = path.join(theme.as_str()).canonicalize().ok()?;
+            theme_path.is_dir().then_some(theme_path)
+        })?;
+        Some(Self::read(path))
+    }
+
+    fn read(path: PathBuf) -> Self {
+        // read the directory once instead of checking each icon on demand
+        let icons = fs::read_dir(&path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().into_string().ok()?;
+                        name.strip_suffix(".svg").map(String::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ThemeDir { path, icons }
+    }
+
+    /// Write a copy of the theme with `currentColor` replaced by `color`, for
+    /// monochrome themes that follow the foreground color. Unset if the theme
+    /// doesn't use the placeholder.
+    pub fn recolor(&self, color: &str) -> Result<Option<Self>> {
+        let mut svgs = Vec::new();
+        for name in &self.icons {
+            let path = self.path.join(format!("{}.svg", name));
+            let svg =
+                fs::read_to_string(&path).with_context(|| anyhow!("Failed to read {:?}", path))?;
+            svgs.push((name, svg));
+        }
+        if !svgs.iter().any(|(_, svg)| svg.contains("currentColor")) {
+            return Ok(None);
+        }
+
+        // the color is part of the path, panels cache icons by path and name
+        let dir = dirs_next::runtime_dir()
+            .or_else(dirs_next::cache_dir)
+            .context("Failed to find the runtime directory")?
+            .join(RECOLOR_DIR)
+            .join(color.trim_start_matches('#'));
+        fs::create_dir_all(&dir).with_context(|| anyhow!("Failed to create {:?}", dir))?;
+        for (name, svg) in svgs {
+            let path = dir.join(format!("{}.svg", name));
+            fs::write(&path, svg.replace("currentColor", color))
+                .with_context(|| anyhow!("Failed to write {:?}", path))?;
+        }
+        debug!("Recolored {:?} with {} to {:?}", self.path, color, dir);
+        Ok(Some(ThemeDir {
+            path: dir,
+            icons: self.icons.clone(),
+        }))
+    }
+
+    /// The `dark` or `light` subdirectory of the theme, if it has a usable one
+    pub fn variant(self, dark: bool) -> Self {
+        let dir = Self::read(self.path.join(if dark { "dark" } else { "light" }));
+        if dir.contains(Icon::Check.as_str()) {
+            dir
+        } else {
+            self
+        }
+    }
+
+    /// Like `find`, but only returns themes that can actually be displayed
+    pub fn find_usable(theme: &Theme) -> Option<Self> {
+        Self::find(theme).filter(|dir| dir.contains(Icon::Check.as_str()))
+    }
+
+    /// Names of all usable themes and the system theme, for the preferences
+    pub fn available() -> Vec<Theme> {
+        let mut themes = icon_dirs()
+            .iter()
+            .filter_map(|path| fs::read_dir(path).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok()?.parse().ok())
+            .filter(|theme| Self::find_usable(theme).is_some())
+            .collect::<Vec<Theme>>();
+        themes.push(Theme {
+            s: SYSTEM_THEME.to_string(),
+        });
+        themes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        themes.dedup();
+        themes
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.icons.contains(name)
+    }
+}
+
+/// Whether checking for updates has its own item or is done by clicking
+/// the status item
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuLayout {
+    #[default]
+    Separate,
+    Merged,
+}
+
+/// Groups of items in the main menu, shown in the configured order
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuSection {
+    Check,
+    Status,
+    Warnings,
+    Actions,
+    /// History, statistics, autostart, mute, preferences and about
+    Extras,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeFormat {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+/// Format a timestamp for display, relative to now or as local time
+pub fn format_time(time: SystemTime, format: &TimeFormat) -> String {
+    match format {
+        TimeFormat::Relative => {
+            let secs = time.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+            let n = secs as usize;
+            match secs {
+                0..=59 => tr("just now"),
+                60..=3599 => ntr("{} minute ago", "{} minutes ago", n / 60),
+                3600..=86399 => ntr("{} hour ago", "{} hours ago", n / 3600),
+                _ => ntr("{} day ago", "{} days ago", n / 86400),
+            }
+        }
+        TimeFormat::Absolute => {
+            let secs = time
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0);
+            // %X is the preferred time representation of the current locale
+            glib::DateTime::from_unix_local(secs)
+                .and_then(|dt| dt.format("%X"))
+                .map(|s| s.to_string())
+                .unwrap_or_else(|_| humantime::format_rfc3339_seconds(time).to_string())
+        }
+    }
+}
+
+/// Tooltip of the status item, also explains what the displayed number counts
+fn last_checked_text(time: SystemTime, counts: (usize, usize), config: &Config) -> String {
+    let text = tr("Last checked: {}").replace("{}", &format_time(time, &config.time_format));
+    match counts {
+        (0, _) => text,
+        (advisories, packages) => format!(
+            "{}\n{} {}",
+            text,
+            ntr("{} advisory", "{} advisories", advisories),
+            ntr("affecting {} package", "affecting {} packages", packages)
+        ),
+    }
+}
+
+/// The icon and status item while updates are installed from the menu, the
+/// check afterwards restores them
+fn show_upgrading(status_mi: &gtk::MenuItem, tray_icon: &RefCell<TrayIcon>, config: &Config) {
+    status_mi.set_label(&tr(UPGRADING));
+    let mut tray_icon = tray_icon.borrow_mut();
+    tray_icon.set_icon(&updater::upgrading_icon(&config.icons));
+    tray_icon.set_active(true);
+}
+
+/// Ask the updater for a check, unless the running or an already requested
+/// one answers the click anyway
+fn request_check(state: &UpdaterState, tx: &mpsc::Sender<Event>, mi: &gtk::MenuItem) {
+    if state.request_check() {
+        mi.set_label(&tr(CHECKING));
+        tx.send(Event::Click).ok();
+    }
+}
+
+/// Row below the status item, eg. `Last checked: 12:04 (next: 16:31)`
+fn checked_text(result: &CheckResult) -> String {
+    let last = format_time(result.checked_at, &TimeFormat::Absolute);
+    match result.next_check_at {
+        Some(next) => tr("Last checked: {} (next: {})")
+            .replacen("{}", &last, 1)
+            .replacen("{}", &format_time(next, &TimeFormat::Absolute), 1),
+        None => tr("Last checked: {}").replace("{}", &last),
+    }
+}
+
 struct TrayIcon {
-    indicator: AppIndicator,
+    indicator: Indicator,
+    /// Labels scrolling switches between, the first one is the regular label
+    labels: Vec<String>,
+    label_index: usize,
+    /// Unset if no icon directory was found or the system theme is selected
+    theme: Option<ThemeDir>,
+    /// Use the symbolic variants of the system icons
+    symbolic: bool,
+    /// Detected dark mode of the desktop, see `[design] variant`
+    dark: bool,
+    /// Name of the current icon, restored once the animation stops
+    icon: String,
+    active: bool,
+    attention: bool,
+    /// Set while the checking animation replaces the icon
+    animation: Option<glib::SourceId>,
 }
 
 impl TrayIcon {
-    fn create(icon_theme: &Theme, icon: &Icon) -> Self {
-        let mut indicator = AppIndicator::new("arch-audit-gtk", "");
+    fn create(config: &Config, icon: &Icon) -> Self {
+        let mut indicator = Indicator::new("arch-audit-gtk", "");
         indicator.set_status(AppIndicatorStatus::Active);
 
-        'outer: for path in &["./icons", "/usr/share/arch-audit-gtk/icons"] {
-            for theme in &[icon_theme, &Theme::default()] {
-                if let Ok(theme_path) = Path::new(path).join(theme.as_str()).canonicalize() {
-                    let icon = theme_path.join("check.svg");
-                    if icon.exists() {
-                        indicator.set_icon_theme_path(theme_path.to_str().unwrap());
-                        break 'outer;
-                    }
-                }
+        let mut tray_icon = TrayIcon {
+            indicator,
+            labels: Vec::new(),
+            label_index: 0,
+            theme: None,
+            symbolic: false,
+            dark: prefers_dark(),
+            icon: String::new(),
+            active: true,
+            attention: false,
+            animation: None,
+        };
+        tray_icon.set_theme(config);
+        tray_icon.set_icon(icon);
+        tray_icon
+    }
+
+    /// Look up the configured theme, the icon is changed with the next `set_icon`
+    fn set_theme(&mut self, config: &Config) {
+        self.symbolic = config.symbolic;
+        self.theme = if config.icon_theme.is_system() {
+            None
+        } else {
+            let dark = match config.icon_variant {
+                IconVariant::Auto => self.dark,
+                IconVariant::Light => false,
+                IconVariant::Dark => true,
+            };
+            [&config.icon_theme, &Theme::default()]
+                .iter()
+                .find_map(|theme| ThemeDir::find_usable(theme))
+                .map(|theme| theme.variant(dark))
+        };
+        if let (Some(theme), true) = (&self.theme, config.recolor) {
+            let color = config.foreground.clone().unwrap_or_else(foreground_color);
+            match theme.recolor(&color) {
+                Ok(Some(recolored)) => self.theme = Some(recolored),
+                Ok(None) => (),
+                Err(err) => warn!("Failed to recolor the icon theme: {:#}", err),
+            }
+        }
+        match &self.theme {
+            Some(theme) => {
+                self.indicator.set_icon_theme_path(theme.path().to_str().unwrap());
+                Self::validate_overrides(theme, &config.icons);
             }
+            None if config.icon_theme.is_system() => debug!("Using the system icon theme"),
+            None => warn!("No icons found in any of {:?}, the arch-audit-gtk installation seems to be incomplete. Falling back to the system icon theme", icon_dirs()),
         }
+        let attention = self.icon_name(&updater::attention_icon(&config.icons));
+        self.indicator
+            .set_attention_icon_full(&attention, "attention");
+    }
 
-        indicator.set_icon_full(icon.as_str(), "icon");
+    fn validate_overrides(theme: &ThemeDir, icons: &HashMap<String, String>) {
+        for (state, name) in icons {
+            if !theme.contains(name) {
+                warn!(
+                    "Icon {:?} configured for {:?} does not exist in {:?}, using the system icon theme instead",
+                    name, state, theme.path()
+                );
+            }
+        }
+    }
 
-        TrayIcon { indicator }
+    /// Switch between the light and dark icons, the current icon is shown
+    /// again from the other variant
+    pub fn set_dark(&mut self, dark: bool, config: &Config) {
+        if dark != self.dark {
+            self.dark = dark;
+            self.refresh_theme(config);
+        }
+    }
+
+    /// Look up the theme again and show the current icon from it, eg. after
+    /// the gtk theme changed the foreground color
+    pub fn refresh_theme(&mut self, config: &Config) {
+        self.set_theme(config);
+        if self.animation.is_none() {
+            self.indicator.set_icon_full(&self.icon, "icon");
+        }
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        self.update_status();
+    }
+
+    /// Most panels highlight the indicator or show the attention icon, it's
+    /// reset by the next `set_icon`
+    pub fn set_attention(&mut self, attention: bool) {
+        self.attention = attention;
+        self.update_status();
+    }
+
+    fn update_status(&mut self) {
+        // the attention icon would hide the animation
+        let attention = self.attention && self.animation.is_none();
+        self.indicator.set_status(match (self.active, attention) {
+            (false, _) => AppIndicatorStatus::Passive,
+            (true, true) => AppIndicatorStatus::Attention,
+            (true, false) => AppIndicatorStatus::Active,
+        });
+    }
+
+    fn icon_name(&self, icon: &Icon) -> String {
+        // icons missing from our theme always use the system icon theme
+        match &self.theme {
+            Some(theme) if theme.contains(icon.as_str()) => icon.as_str().to_string(),
+            // themes without icons per severity use the same alert for all
+            Some(theme) if matches!(icon, Icon::Severity(_)) && theme.contains("alert") => {
+                Icon::Alert.as_str().to_string()
+            }
+            _ if self.symbolic => format!("{}-symbolic", icon.fallback_name()),
+            _ => icon.fallback_name().to_string(),
+        }
+    }
+
+    /// The icon in menu size, from the same theme as the tray icon
+    fn image(&self, icon: &Icon) -> gtk::Image {
+        let name = self.icon_name(icon);
+        if let Some(theme) = self.theme.as_ref().filter(|theme| theme.contains(&name)) {
+            let path = theme.path().join(format!("{}.svg", name));
+            let size = gtk::IconSize::Menu.lookup().map_or(16, |(width, _)| width);
+            match gtk::gdk_pixbuf::Pixbuf::from_file_at_size(&path, size, size) {
+                Ok(pixbuf) => return gtk::Image::from_pixbuf(Some(&pixbuf)),
+                Err(err) => warn!("Failed to load {:?}: {}", path, err),
+            }
+        }
+        gtk::Image::from_icon_name(Some(&name), gtk::IconSize::Menu)
     }
 
     pub fn set_icon(&mut self, icon: &Icon) {
-        self.indicator.set_icon_full(icon.as_str(), "icon");
+        self.stop_animation();
+        self.icon = self.icon_name(icon);
+        self.indicator.set_icon_full(&self.icon, "icon");
+        self.set_attention(false);
+    }
+
+    /// Cycle through `frames` until the icon is changed or the animation is
+    /// stopped
+    pub fn start_animation(tray_icon: &Rc<RefCell<TrayIcon>>, frames: Vec<Icon>) {
+        if tray_icon.borrow().animation.is_some() {
+            return;
+        }
+        let first = tray_icon.borrow().icon_name(&frames[0]);
+        let weak = Rc::downgrade(tray_icon);
+        let mut frame = 0;
+        let source = glib::timeout_add_local(ANIMATION_INTERVAL, move || {
+            let Some(tray_icon) = weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            frame = (frame + 1) % frames.len();
+            tray_icon.borrow_mut().show_frame(&frames[frame]);
+            glib::ControlFlow::Continue
+        });
+
+        let mut tray_icon = tray_icon.borrow_mut();
+        tray_icon.indicator.set_icon_full(&first, "checking");
+        tray_icon.animation = Some(source);
+        tray_icon.update_status();
+    }
+
+    fn show_frame(&mut self, icon: &Icon) {
+        let name = self.icon_name(icon);
+        self.indicator.set_icon_full(&name, "checking");
+    }
+
+    /// Show the last icon again, if the animation is running
+    pub fn stop_animation(&mut self) {
+        if let Some(source) = self.animation.take() {
+            source.remove();
+            self.indicator.set_icon_full(&self.icon, "icon");
+            self.update_status();
+        }
+    }
+
+    /// Shown as tooltip by most panels
+    pub fn set_title(&mut self, title: &str) {
+        self.indicator.set_title(title);
+    }
+
+    /// Text next to the icon, scrolling over the icon switches between the
+    /// labels, none hides it. The selected label is kept across results.
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+        self.show_label();
+    }
+
+    /// Show the next or the previous label
+    pub fn scroll_label(&mut self, forward: bool) {
+        let len = self.labels.len();
+        if len > 1 {
+            let step = if forward { 1 } else { len - 1 };
+            self.label_index = (self.label_index + step) % len;
+            self.show_label();
+        }
+    }
+
+    fn show_label(&mut self) {
+        let label = match self.labels.len() {
+            0 => "",
+            len => &self.labels[self.label_index % len],
+        };
+        // the guide reserves space so the panel doesn't jump around
+        self.indicator.set_label(label, "999");
+    }
+
+    /// Only some panels forward middle clicks, `None` ignores them
+    pub fn set_middle_click_item(&mut self, mi: Option<&gtk::MenuItem>) {
+        self.indicator.set_secondary_activate_target(mi);
     }
 
     pub fn add_menu(&mut self, m: &mut gtk::Menu) {
         // always append a quit item to the menu
-        let mi = gtk::MenuItem::with_label(QUIT);
+        let mi = gtk::MenuItem::with_mnemonic(&tr(QUIT));
         m.append(&mi);
         mi.connect_activate(|_| {
             gtk::main_quit();
@@ -132,72 +781,1760 @@ impl TrayIcon {
     }
 }
 
-pub fn main(config: &Config) -> Result<()> {
+/// If the desktop prefers dark mode. The settings portal takes precedence,
+/// gtk settings are only used without it.
+fn prefers_dark() -> bool {
+    crate::dbus::prefers_dark_scheme().unwrap_or_else(|| {
+        gtk::Settings::default()
+            .map(|settings| {
+                settings.is_gtk_application_prefer_dark_theme()
+                    || settings
+                        .gtk_theme_name()
+                        .is_some_and(|name| name.to_lowercase().ends_with("dark"))
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// Foreground color of the gtk theme as `#rrggbb`, used to recolor themes
+fn foreground_color() -> String {
+    let rgba = gtk::Label::new(None)
+        .style_context()
+        .color(gtk::StateFlags::NORMAL);
+    let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(rgba.red()),
+        channel(rgba.green()),
+        channel(rgba.blue())
+    )
+}
+
+/// Update the icon variant whenever the desktop switches to or from dark mode,
+/// a different gtk theme may also change the foreground color
+fn watch_desktop_theme(tray_icon: &Rc<RefCell<TrayIcon>>, ui_config: &Rc<RefCell<Config>>) {
+    {
+        let tray_icon = tray_icon.clone();
+        let ui_config = ui_config.clone();
+        crate::dbus::watch_color_scheme(move |dark| {
+            tray_icon.borrow_mut().set_dark(dark, &ui_config.borrow())
+        });
+    }
+    if let Some(settings) = gtk::Settings::default() {
+        let tray_icon = tray_icon.clone();
+        let ui_config = ui_config.clone();
+        let on_change = move |_: &gtk::Settings| {
+            let mut tray_icon = tray_icon.borrow_mut();
+            tray_icon.dark = prefers_dark();
+            tray_icon.refresh_theme(&ui_config.borrow());
+        };
+        settings.connect_gtk_application_prefer_dark_theme_notify(on_change.clone());
+        settings.connect_gtk_theme_name_notify(on_change);
+    }
+}
+
+/// Open a link with `[menu] open_cmd`, or in the default browser if none is
+/// configured
+pub fn open_link(open_cmd: Option<&str>, url: &str) -> Result<()> {
+    let Some(open_cmd) = open_cmd else {
+        opener::open(url)?;
+        return Ok(());
+    };
+    // without a placeholder the link is passed as the last argument
+    let template = if open_cmd.contains("{url}") {
+        open_cmd.to_string()
+    } else {
+        format!("{} {{url}}", open_cmd)
+    };
+    hooks::spawn_template(&template, &[("{url}", url)]).context("Failed to run open_cmd")
+}
+
+/// Open the link of an advisory at most once per session, so an auto-opened
+/// advisory isn't opened again by clicking its notification
+fn open_once(opened: &RefCell<HashSet<String>>, open_cmd: Option<&str>, link: &str) {
+    if !opened.borrow_mut().insert(link.to_string()) {
+        debug!("Not opening {:?} again, it was already opened", link);
+        return;
+    }
+    if let Err(err) = open_link(open_cmd, link) {
+        warn!("Failed to open link: {:#}", err);
+    }
+}
+
+fn run_item_action(
+    action: &ItemAction,
+    update: &Update,
+    command: Option<&str>,
+    open_cmd: Option<&str>,
+) -> Result<()> {
+    match action {
+        ItemAction::Open => open_link(open_cmd, &update.advisory_link())?,
+        ItemAction::Copy => {
+            let clipboard = gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD);
+            clipboard.set_text(&update.avg);
+        }
+        ItemAction::Command => {
+            let command = command.context("No item_command is configured")?;
+            let placeholders = [
+                ("{avg}", update.avg.as_str()),
+                ("{pkg}", update.pkg.as_str()),
+                ("{url}", &update.advisory_link()),
+            ];
+            hooks::spawn_template(command, &placeholders).context("Failed to run item_command")?;
+        }
+        ItemAction::Details => details_dialog(update),
+        // handled by connect_item_action
+        ItemAction::Acknowledge => (),
+    }
+    Ok(())
+}
+
+/// Acknowledges advisories from the menu, main shows the last result again
+/// once `changed` wakes it up
+#[derive(Clone)]
+struct Acknowledger {
+    acknowledged: Acknowledged,
+    #[allow(deprecated)]
+    changed: glib::Sender<()>,
+}
+
+impl Acknowledger {
+    fn toggle(&self, update: &Update) {
+        if self.acknowledged.toggle(update) {
+            info!("Acknowledged {} of {}", update.avg, update.pkg);
+        } else {
+            info!("Alerting about {} of {} again", update.avg, update.pkg);
+        }
+        self.changed.send(()).ok();
+    }
+}
+
+/// Open the links of all advisories, each link only once
+///
+/// If this would open more than `threshold` tabs the user is asked first.
+fn open_all(updates: &[Update], threshold: usize, open_cmd: Option<String>) {
+    let mut seen = HashSet::new();
+    let links = updates
+        .iter()
+        .map(|update| update.advisory_link().into_owned())
+        .filter(|link| seen.insert(link.clone()))
+        .collect::<Vec<_>>();
+
+    let open = move || {
+        for link in &links {
+            if let Err(err) = open_link(open_cmd.as_deref(), link) {
+                warn!("Failed to open {:?}: {:#}", link, err);
+            }
+        }
+    };
+    if seen.len() <= threshold {
+        open();
+        return;
+    }
+
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::empty(),
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &ntr("Open {} link?", "Open {} links?", seen.len()),
+    );
+    let packages = updates
+        .iter()
+        .map(|update| update.pkg.as_str())
+        .collect::<BTreeSet<_>>();
+    dialog.set_secondary_text(Some(&packages.into_iter().collect::<Vec<_>>().join(", ")));
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Ok {
+            open();
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
+
+/// Show everything we know about an advisory without opening the browser
+fn details_dialog(update: &Update) {
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::empty(),
+        gtk::MessageType::Info,
+        gtk::ButtonsType::Close,
+        &format!("{}: {}", update.avg, update.pkg),
+    );
+
+    let not_fixed = tr("not fixed yet");
+    let mut details = vec![
+        tr("Severity: {}").replace("{}", &update.severity.to_string()),
+        tr("Type: {}").replace("{}", &update.kind),
+        tr("Status: {}").replace("{}", &format!("{:?}", update.avg_status)),
+        tr("Fixed in: {}").replace("{}", update.fixed.as_deref().unwrap_or(&not_fixed)),
+    ];
+    if let Some(repo) = &update.repo {
+        details.push(tr("Repository: {}").replace("{}", repo));
+    }
+    if !update.issues.is_empty() {
+        details.push(tr("Issues: {}").replace("{}", &update.issues.join(", ")));
+    }
+    dialog.set_secondary_text(Some(&details.join("\n")));
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+/// Menu label of an advisory, marked if it's acknowledged
+fn advisory_label(update: &Update, label: &str) -> String {
+    if update.acknowledged {
+        tr("{} (acknowledged)").replace("{}", label)
+    } else {
+        label.to_string()
+    }
+}
+
+fn advisory_item(update: &Update, config: &Config, ack: Option<&Acknowledger>) -> gtk::MenuItem {
+    let mi = gtk::MenuItem::with_label(&advisory_label(update, &update.text));
+
+    let mut actions = vec![config.item_action.clone()];
+    if config.item_submenu && !config.compact_menu {
+        for action in [
+            ItemAction::Open,
+            ItemAction::Copy,
+            ItemAction::Command,
+            ItemAction::Details,
+            ItemAction::Acknowledge,
+        ] {
+            if action == ItemAction::Command && config.item_command.is_none() {
+                continue;
+            }
+            // advisories of remote hosts can't be acknowledged
+            if action == ItemAction::Acknowledge && ack.is_none() {
+                continue;
+            }
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+    }
+
+    if actions.len() > 1 {
+        let m = gtk::Menu::new();
+        for action in actions {
+            let action_mi = gtk::MenuItem::with_label(&tr(action.label(update)));
+            m.append(&action_mi);
+            connect_item_action(&action_mi, action, update, config, ack);
+        }
+        mi.set_submenu(Some(&m));
+    } else {
+        connect_item_action(&mi, config.item_action.clone(), update, config, ack);
+    }
+
+    mi
+}
+
+fn connect_item_action(
+    mi: &gtk::MenuItem,
+    action: ItemAction,
+    update: &Update,
+    config: &Config,
+    ack: Option<&Acknowledger>,
+) {
+    let update = update.clone();
+    if action == ItemAction::Acknowledge {
+        let ack = ack.cloned();
+        mi.connect_activate(move |_| match &ack {
+            Some(ack) => ack.toggle(&update),
+            None => debug!("Advisories of remote hosts can't be acknowledged"),
+        });
+        return;
+    }
+    let command = config.item_command.clone();
+    let open_cmd = config.open_cmd.clone();
+    mi.connect_activate(move |_| {
+        let result = run_item_action(&action, &update, command.as_deref(), open_cmd.as_deref());
+        if let Err(err) = result {
+            warn!("Failed to run action: {:#}", err);
+        }
+    });
+}
+
+/// Open a small window to filter the advisory menu by package name
+///
+/// Indicator menus are exported over dbus, which doesn't support embedding
+/// an entry in the menu itself, so the entry lives in a separate window and
+/// only toggles the visibility of the menu items.
+fn filter_window(items: &FilterItems, filter: &Rc<RefCell<String>>) {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&tr("Filter advisories"));
+    window.set_keep_above(true);
+
+    let entry = gtk::Entry::new();
+    entry.set_placeholder_text(Some(&tr("Package name")));
+    entry.set_text(&filter.borrow());
+    window.add(&entry);
+
+    let items = items.clone();
+    let filter = filter.clone();
+    entry.connect_changed(move |entry| {
+        let needle = entry.text().to_lowercase();
+        for (pkg, mi) in items.borrow().iter() {
+            mi.set_visible(pkg.to_lowercase().contains(&needle));
+        }
+        *filter.borrow_mut() = needle;
+    });
+    let w = window.clone();
+    entry.connect_activate(move |_| w.close());
+
+    window.show_all();
+    window.present();
+}
+
+/// Row of the advisory menu, collected first so large menus can be built
+/// incrementally
+enum MenuRow {
+    Separator,
+    Header(String),
+    Advisory(Box<Update>),
+    /// All advisories of a package, with `[menu] package_submenus`
+    Package(Vec<Update>),
+    /// All packages of an advisory, with `[menu] group_packages`
+    Group(Vec<Update>),
+}
+
+type FilterItems = Rc<RefCell<Vec<(String, gtk::MenuItem)>>>;
+
+fn append_row(
+    m: &gtk::Menu,
+    row: MenuRow,
+    config: &Config,
+    ack: Option<&Acknowledger>,
+    items: &FilterItems,
+    filter: &str,
+) {
+    // rows may be added after the menu is shown, so show them individually
+    match row {
+        MenuRow::Separator => {
+            let separator = gtk::SeparatorMenuItem::new();
+            m.append(&separator);
+            separator.show();
+        }
+        MenuRow::Header(label) => {
+            let header = gtk::MenuItem::with_label(&label);
+            header.set_sensitive(false);
+            m.append(&header);
+            header.show();
+        }
+        MenuRow::Advisory(update) => {
+            let mi = advisory_item(&update, config, ack);
+            m.append(&mi);
+            mi.show_all();
+            mi.set_visible(update.pkg.to_lowercase().contains(filter));
+            items.borrow_mut().push((update.pkg, mi));
+        }
+        MenuRow::Package(updates) => {
+            let mi = package_item(&updates, config, ack);
+            m.append(&mi);
+            mi.show_all();
+            let pkg = updates[0].pkg.clone();
+            mi.set_visible(pkg.to_lowercase().contains(filter));
+            items.borrow_mut().push((pkg, mi));
+        }
+        MenuRow::Group(updates) => {
+            let mi = group_item(&updates, config, ack);
+            m.append(&mi);
+            mi.show_all();
+            // the filter matches any of the packages
+            let pkgs = updates
+                .iter()
+                .map(|u| u.pkg.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            mi.set_visible(pkgs.to_lowercase().contains(filter));
+            items.borrow_mut().push((pkgs, mi));
+        }
+    }
+}
+
+/// Item for an advisory and all packages it affects, the actions use the
+/// first package. The tooltip lists every package with its fixed version.
+fn group_item(updates: &[Update], config: &Config, ack: Option<&Acknowledger>) -> gtk::MenuItem {
+    let first = &updates[0];
+    let mut pkgs = updates
+        .iter()
+        .take(GROUP_MAX_PACKAGES)
+        .map(|u| u.pkg.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if updates.len() > GROUP_MAX_PACKAGES {
+        let more = updates.len() - GROUP_MAX_PACKAGES;
+        pkgs = tr("{} and {} more")
+            .replacen("{}", &pkgs, 1)
+            .replacen("{}", &more.to_string(), 1);
+    }
+    let mut update = first.clone();
+    update.text = format!(
+        "{}: {} {} ({})",
+        first.severity, first.avg, pkgs, first.kind
+    );
+    let mi = advisory_item(&update, config, ack);
+
+    let tooltip = updates
+        .iter()
+        .map(|u| match (&u.fixed, u.fixable()) {
+            (Some(fixed), true) => format!("{}: {}", u.pkg, tr("fixed in {}").replace("{}", fixed)),
+            _ => format!("{}: {}", u.pkg, tr("no fix released yet")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    mi.set_tooltip_text(Some(&tooltip));
+    mi
+}
+
+/// Item for all advisories of a package, the submenu links every advisory
+/// and each of its CVEs
+fn package_item(updates: &[Update], config: &Config, ack: Option<&Acknowledger>) -> gtk::MenuItem {
+    // sorted, the first advisory is the most severe
+    let first = &updates[0];
+    let count = ntr("{} advisory", "{} advisories", updates.len());
+    let mi = gtk::MenuItem::with_label(&format!("{}: {} ({})", first.severity, first.pkg, count));
+
+    let m = gtk::Menu::new();
+    for (i, update) in updates.iter().enumerate() {
+        if i > 0 {
+            m.append(&gtk::SeparatorMenuItem::new());
+        }
+        let fixed = match (&update.fixed, update.fixable()) {
+            (Some(fixed), true) => tr("fixed in {}").replace("{}", fixed),
+            _ => tr("no fix released yet"),
+        };
+        let label = format!(
+            "{}: {}, {} ({})",
+            update.avg, update.severity, fixed, update.kind
+        );
+        let avg_mi = gtk::MenuItem::with_label(&advisory_label(update, &label));
+        connect_item_action(&avg_mi, config.item_action.clone(), update, config, ack);
+        m.append(&avg_mi);
+        if ack.is_some() && config.item_action != ItemAction::Acknowledge {
+            let label = format!("    {}", tr(ItemAction::Acknowledge.label(update)));
+            let ack_mi = gtk::MenuItem::with_label(&label);
+            connect_item_action(&ack_mi, ItemAction::Acknowledge, update, config, ack);
+            m.append(&ack_mi);
+        }
+
+        for cve in &update.cves {
+            let cve_mi = gtk::MenuItem::with_label(&format!("    {}", cve.id));
+            if let Some(details) = &cve.details {
+                cve_mi.set_tooltip_text(Some(&details.summary()));
+            }
+            let link = cve.link.clone();
+            let open_cmd = config.open_cmd.clone();
+            cve_mi.connect_activate(move |_| {
+                if let Err(err) = open_link(open_cmd.as_deref(), &link) {
+                    warn!("Failed to open link: {:#}", err);
+                }
+            });
+            m.append(&cve_mi);
+        }
+    }
+    mi.set_submenu(Some(&m));
+    mi
+}
+
+fn advisory_rows(updates: &[&Update], config: &Config) -> Vec<MenuRow> {
+    let mut rows = Vec::new();
+    // fall back to a flat list if we don't know any repositories
+    if !config.compact_menu
+        && config.group_by == GroupBy::Repo
+        && updates.iter().any(|u| u.repo.is_some())
+    {
+        // groups are sorted by their most severe advisory
+        let mut groups = Vec::<(Option<&str>, Vec<&Update>)>::new();
+        for update in updates.iter().copied() {
+            let repo = update.repo.as_deref();
+            match groups.iter_mut().find(|(r, _)| *r == repo) {
+                Some((_, list)) => list.push(update),
+                None => groups.push((repo, vec![update])),
+            }
+        }
+
+        for (i, (repo, list)) in groups.into_iter().enumerate() {
+            if i > 0 {
+                rows.push(MenuRow::Separator);
+            }
+            rows.push(MenuRow::Header(
+                repo.map_or_else(|| tr("unknown repository"), String::from),
+            ));
+            rows.extend(item_rows(&list, config));
+        }
+    } else {
+        rows.extend(item_rows(updates, config));
+    }
+    rows
+}
+
+/// One row per advisory, per package with `[menu] package_submenus` or per
+/// advisory group with `[menu] group_packages`
+fn item_rows(updates: &[&Update], config: &Config) -> Vec<MenuRow> {
+    if config.group_packages && !config.compact_menu {
+        // advisories keep the position of their most severe package
+        let mut groups = Vec::<Vec<Update>>::new();
+        for update in updates.iter().copied() {
+            match groups.iter_mut().find(|list| list[0].avg == update.avg) {
+                Some(list) => list.push(update.clone()),
+                None => groups.push(vec![update.clone()]),
+            }
+        }
+        return groups.into_iter().map(MenuRow::Group).collect();
+    }
+    if !config.package_submenus || config.compact_menu {
+        return updates
+            .iter()
+            .map(|u| MenuRow::Advisory(Box::new((*u).clone())))
+            .collect();
+    }
+    // packages keep the position of their most severe advisory
+    let mut packages = Vec::<Vec<Update>>::new();
+    for update in updates.iter().copied() {
+        match packages.iter_mut().find(|list| list[0].pkg == update.pkg) {
+            Some(list) => list.push(update.clone()),
+            None => packages.push(vec![update.clone()]),
+        }
+    }
+    packages.into_iter().map(MenuRow::Package).collect()
+}
+
+fn advisory_menu(updates: &[Update], config: &Config, ack: Option<&Acknowledger>) -> gtk::Menu {
+    let m = gtk::Menu::new();
+
+    let filter_mi = gtk::MenuItem::with_mnemonic(&tr(FILTER));
+    if !config.compact_menu && updates.len() >= FILTER_MIN_ITEMS {
+        m.append(&filter_mi);
+    }
+    if !config.compact_menu && updates.len() > 1 {
+        let mi = gtk::MenuItem::with_mnemonic(&tr(OPEN_ALL));
+        let updates = updates.to_vec();
+        let threshold = config.open_all_confirm_threshold;
+        let open_cmd = config.open_cmd.clone();
+        mi.connect_activate(move |_| open_all(&updates, threshold, open_cmd.clone()));
+        m.append(&mi);
+    }
+    if !config.compact_menu {
+        let mi = gtk::MenuItem::with_mnemonic(&tr(DETAILS));
+        let updates = updates.to_vec();
+        let open_cmd = config.open_cmd.clone();
+        mi.connect_activate(move |_| crate::details::show(&updates, open_cmd.clone()));
+        m.append(&mi);
+    }
+    if !config.compact_menu && updates.iter().any(|u| !u.issues.is_empty()) {
+        let mi = gtk::MenuItem::with_mnemonic(&tr(COPY_CVES));
+        let cves = crate::report::cve_ids(updates);
+        mi.connect_activate(move |_| {
+            let clipboard = gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD);
+            clipboard.set_text(&cves);
+        });
+        m.append(&mi);
+    }
+    if !config.compact_menu {
+        let mi = gtk::MenuItem::with_mnemonic(&tr(COPY_REPORT));
+        let report = crate::report::markdown(updates);
+        mi.connect_activate(move |_| {
+            let clipboard = gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD);
+            clipboard.set_text(&report);
+        });
+        m.append(&mi);
+
+        let mi = gtk::MenuItem::with_mnemonic(&tr(SAVE_REPORT));
+        let updates = updates.to_vec();
+        mi.connect_activate(move |_| crate::report::save(&updates));
+        m.append(&mi);
+    }
+    if !m.children().is_empty() {
+        m.append(&gtk::SeparatorMenuItem::new());
+    }
+
+    // advisories without a fix are only listed with [updater] show_unfixed
+    let (fixable, unfixed): (Vec<_>, Vec<_>) = updates.iter().partition(|u| u.fixable());
+    let rows = if unfixed.is_empty() {
+        advisory_rows(&fixable, config)
+    } else {
+        let mut rows = Vec::new();
+        for (title, list) in [(FIXABLE, fixable), (UNFIXED, unfixed)] {
+            if list.is_empty() {
+                continue;
+            }
+            if !rows.is_empty() {
+                rows.push(MenuRow::Separator);
+            }
+            rows.push(MenuRow::Header(tr(title)));
+            rows.extend(advisory_rows(&list, config));
+        }
+        rows
+    };
+
+    let items = FilterItems::default();
+    let filter = Rc::new(RefCell::new(String::new()));
+
+    // building hundreds of items at once stalls the main loop, so only the
+    // first rows are added right away and the rest in idle callbacks
+    let mut rows = rows.into_iter();
+    let start = Instant::now();
+    for row in rows.by_ref().take(MENU_SYNC_ROWS) {
+        append_row(&m, row, config, ack, &items, "");
+    }
+    debug!("Added the first menu rows in {:?}", start.elapsed());
+    let mut rows = rows.peekable();
+    if rows.peek().is_some() {
+        debug!("Adding remaining advisories to the menu in the background");
+        let m = m.clone();
+        let config = config.clone();
+        let ack = ack.cloned();
+        let items = items.clone();
+        let filter = filter.clone();
+        glib::idle_add_local(move || {
+            let start = Instant::now();
+            for row in rows.by_ref().take(MENU_IDLE_ROWS) {
+                append_row(&m, row, &config, ack.as_ref(), &items, &filter.borrow());
+            }
+            debug!("Added menu rows in the background in {:?}", start.elapsed());
+            glib::ControlFlow::from(rows.peek().is_some())
+        });
+    }
+
+    filter_mi.connect_activate(move |_| filter_window(&items, &filter));
+
+    m
+}
+
+/// Item for a host of `[remotes]` with the icon of its own result, the
+/// submenu lists its advisories
+fn remote_item(remote: &RemoteResult, config: &Config, tray_icon: &TrayIcon) -> gtk::MenuItem {
+    let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    hbox.pack_start(
+        &tray_icon.image(&remote.status.icon(config)),
+        false,
+        false,
+        0,
+    );
+    let label = gtk::Label::new(Some(&format!("{}: {}", remote.name, remote.status.text())));
+    hbox.pack_start(&label, false, false, 0);
+    let mi = gtk::MenuItem::new();
+    mi.add(&hbox);
+    mi.set_tooltip_text(Some(&remote.target));
+
+    let m = match &remote.status {
+        Status::MissingUpdates(updates) if !updates.is_empty() => {
+            let m = advisory_menu(updates, config, None);
+            m.show_all();
+            Some(m)
+        }
+        Status::Error(err) => error_menu(err.kind.hint().as_deref(), None),
+        _ => None,
+    };
+    mi.set_submenu(m.as_ref());
+    mi
+}
+
+/// Submenu of a failed check, with a hint what to do about it and the raw
+/// output of arch-audit if it couldn't be parsed
+fn error_menu(hint: Option<&str>, raw_output: Option<&str>) -> Option<gtk::Menu> {
+    if hint.is_none() && raw_output.is_none() {
+        return None;
+    }
+    let m = gtk::Menu::new();
+    if let Some(hint) = hint {
+        let mi = gtk::MenuItem::with_label(hint);
+        mi.set_sensitive(false);
+        m.append(&mi);
+    }
+    if let Some(raw_output) = raw_output {
+        let raw_output = raw_output.to_string();
+        let mi = gtk::MenuItem::with_label(&tr("Copy raw output"));
+        mi.connect_activate(move |_| {
+            let clipboard = gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD);
+            clipboard.set_text(&raw_output);
+        });
+        m.append(&mi);
+    }
+    m.show_all();
+    Some(m)
+}
+
+/// Entries of the snooze submenu
+#[derive(Debug, Clone, Copy)]
+enum SnoozeFor {
+    Hours(u64),
+    /// Until midnight in the local timezone
+    Tomorrow,
+    Upgrade,
+}
+
+impl SnoozeFor {
+    fn until(self) -> SnoozeUntil {
+        match self {
+            SnoozeFor::Hours(hours) => {
+                SnoozeUntil::Time(SystemTime::now() + Duration::from_secs(hours * 3600))
+            }
+            SnoozeFor::Tomorrow => match glib::DateTime::now_local()
+                .and_then(|now| now.add_days(1))
+                .and_then(|t| {
+                    glib::DateTime::from_local(t.year(), t.month(), t.day_of_month(), 0, 0, 0.0)
+                }) {
+                Ok(midnight) => {
+                    SnoozeUntil::Time(UNIX_EPOCH + Duration::from_secs(midnight.to_unix() as u64))
+                }
+                Err(err) => {
+                    warn!("Failed to get local time, snoozing for a day: {:#}", err);
+                    SnoozeFor::Hours(24).until()
+                }
+            },
+            SnoozeFor::Upgrade => SnoozeUntil::Upgrade,
+        }
+    }
+}
+
+/// Check item that adds or removes the autostart entry
+fn autostart_item() -> gtk::CheckMenuItem {
+    let mi = gtk::CheckMenuItem::with_mnemonic(&tr(AUTOSTART));
+    mi.set_active(crate::autostart::is_enabled());
+    mi.connect_toggled(|mi| {
+        // also called when the state is restored after an error
+        if mi.is_active() == crate::autostart::is_enabled() {
+            return;
+        }
+        if let Err(err) = crate::autostart::set_enabled(mi.is_active()) {
+            warn!("Failed to change autostart: {:#}", err);
+            mi.set_active(crate::autostart::is_enabled());
+        }
+    });
+    mi
+}
+
+/// Items of the extras section, the mute and preferences items are connected
+/// in main
+fn extra_items(
+    config: &Config,
+    mute_mi: &gtk::CheckMenuItem,
+    preferences_mi: &gtk::MenuItem,
+) -> Vec<gtk::MenuItem> {
+    let mut items = Vec::new();
+    if config.history_enabled {
+        let mi = gtk::MenuItem::with_mnemonic(&tr(HISTORY));
+        let history_config = config.clone();
+        mi.connect_activate(move |_| crate::history::show(&history_config));
+        items.push(mi);
+
+        let mi = gtk::MenuItem::with_mnemonic(&tr(STATISTICS));
+        mi.connect_activate(|_| crate::stats::show());
+        items.push(mi);
+    }
+    items.push(autostart_item().upcast());
+    if config.sound_enabled {
+        items.push(mute_mi.clone().upcast());
+    }
+    items.push(preferences_mi.clone());
+
+    let mi = gtk::MenuItem::with_mnemonic(&tr(ABOUT));
+    let about_config = config.clone();
+    mi.connect_activate(move |_| crate::about::show(&about_config));
+    items.push(mi);
+    items
+}
+
+/// Items of the main menu that are updated later on, items of sections that
+/// are disabled in the config are created but never added to the menu
+struct MainMenu {
+    menu: gtk::Menu,
+    checking_mi: gtk::MenuItem,
+    status_mi: gtk::MenuItem,
+    /// Time of the last and the next scheduled check
+    checked_mi: gtk::MenuItem,
+    news_mi: gtk::MenuItem,
+    snooze_mi: gtk::MenuItem,
+    snooze_items: Vec<(gtk::MenuItem, SnoozeFor)>,
+    upgrade_mi: gtk::MenuItem,
+    /// Installs only the vulnerable packages with pkexec
+    upgrade_vulnerable_mi: gtk::MenuItem,
+    warnings_mi: gtk::MenuItem,
+    /// Upgrades waiting for a reboot
+    reboot_mi: gtk::MenuItem,
+    /// Connected in main, since saving changes the running instance
+    preferences_mi: gtk::MenuItem,
+    /// Only added with `[sound] enabled`
+    mute_mi: gtk::CheckMenuItem,
+    /// Only visible together with one of the actions
+    actions_separator: Option<gtk::SeparatorMenuItem>,
+    /// Set if the installed arch-audit is outdated
+    version_warning: Option<String>,
+}
+
+impl MainMenu {
+    fn build(config: &Config) -> Self {
+        let menu = gtk::Menu::new();
+        let checking_mi = gtk::MenuItem::with_mnemonic(&tr(CHECK_FOR_UPDATE));
+        let status_mi = gtk::MenuItem::with_label(&tr("Starting..."));
+        let checked_mi = gtk::MenuItem::new();
+        checked_mi.set_sensitive(false);
+        checked_mi.set_no_show_all(true);
+        let version_warning = updater::check_arch_audit_version(config);
+
+        // only shown while there are outstanding updates, so the user can check
+        // for manual interventions before upgrading
+        let news_mi = gtk::MenuItem::with_mnemonic(&tr(NEWS));
+        news_mi.set_no_show_all(true);
+        // hide the alert for a while, the items are connected in main
+        let snooze_mi = gtk::MenuItem::with_mnemonic(&tr(SNOOZE));
+        snooze_mi.set_no_show_all(true);
+        let snooze_menu = gtk::Menu::new();
+        let snooze_items = SNOOZE_OPTIONS
+            .iter()
+            .map(|(label, duration)| {
+                let mi = gtk::MenuItem::with_mnemonic(&tr(label));
+                snooze_menu.append(&mi);
+                (mi, *duration)
+            })
+            .collect();
+        snooze_menu.show_all();
+        snooze_mi.set_submenu(Some(&snooze_menu));
+        let upgrade_mi = gtk::MenuItem::with_mnemonic(&tr(UPGRADE));
+        upgrade_mi.set_no_show_all(true);
+        let upgrade_vulnerable_mi = gtk::MenuItem::with_mnemonic(&tr(UPGRADE_VULNERABLE));
+        upgrade_vulnerable_mi.set_no_show_all(true);
+        // warnings that came with the last result
+        let warnings_mi = gtk::MenuItem::new();
+        warnings_mi.set_sensitive(false);
+        warnings_mi.set_no_show_all(true);
+        let reboot_mi = gtk::MenuItem::new();
+        reboot_mi.set_sensitive(false);
+        reboot_mi.set_no_show_all(true);
+        let preferences_mi = gtk::MenuItem::with_mnemonic(&tr(PREFERENCES));
+        let mute_mi = gtk::CheckMenuItem::with_mnemonic(&tr(MUTE));
+
+        let separators = config.menu_separators && !config.compact_menu;
+        let mut actions_separator = None;
+        let mut empty = true;
+        for section in &config.menu_sections {
+            let items = match section {
+                // the status item doubles as the check item
+                MenuSection::Check if config.menu_layout == MenuLayout::Merged => vec![],
+                MenuSection::Check => vec![checking_mi.clone()],
+                MenuSection::Status if config.compact_menu => vec![status_mi.clone()],
+                MenuSection::Status => {
+                    vec![status_mi.clone(), checked_mi.clone(), reboot_mi.clone()]
+                }
+                MenuSection::Warnings => version_warning
+                    .clone()
+                    .filter(|_| !config.compact_menu)
+                    .map(|warning| {
+                        let mi = gtk::MenuItem::with_label(&warning);
+                        mi.set_sensitive(false);
+                        mi
+                    })
+                    .into_iter()
+                    .chain([warnings_mi.clone()])
+                    .collect(),
+                MenuSection::Actions => {
+                    vec![
+                        news_mi.clone(),
+                        upgrade_mi.clone(),
+                        upgrade_vulnerable_mi.clone(),
+                        snooze_mi.clone(),
+                    ]
+                }
+                MenuSection::Extras if config.compact_menu => vec![],
+                MenuSection::Extras => extra_items(config, &mute_mi, &preferences_mi),
+            };
+            if items.is_empty() {
+                continue;
+            }
+
+            if separators && !empty {
+                let separator = gtk::SeparatorMenuItem::new();
+                if *section == MenuSection::Actions {
+                    separator.set_no_show_all(true);
+                    actions_separator = Some(separator.clone());
+                }
+                menu.append(&separator);
+            }
+            for mi in items {
+                menu.append(&mi);
+            }
+            empty = false;
+        }
+
+        MainMenu {
+            menu,
+            checking_mi,
+            status_mi,
+            checked_mi,
+            news_mi,
+            snooze_mi,
+            snooze_items,
+            upgrade_mi,
+            upgrade_vulnerable_mi,
+            warnings_mi,
+            reboot_mi,
+            preferences_mi,
+            mute_mi,
+            actions_separator,
+            version_warning,
+        }
+    }
+}
+
+pub fn main(config: &Config, args: Args) -> Result<()> {
     gtk::init()?;
 
-    // TODO: consider a mutex and condvar so we don't queue multiple updates
+    // queued events are coalesced by the updater
     let (update_tx, update_rx) = mpsc::channel();
-    let (result_tx, result_rx) = glib::MainContext::channel(glib::Priority::DEFAULT);
+    if !crate::dbus::claim_instance(args.replace, update_tx.clone())? {
+        return Ok(());
+    }
+    let (result_tx, result_rx) = updater::result_channel();
+    let updater_state = result_rx.state();
 
-    setup_inotify_thread(update_tx.clone())?;
+    // lets the pacman hook signal us
+    let pid_file = PidFile::create();
+    let cancel = CancelToken::default();
+    if config.ssh_target.is_some() {
+        info!(
+            "Checking {:?} over ssh, not watching the local pacman database",
+            config.ssh_target
+        );
+    }
+    // config changes are applied on the main thread
+    #[allow(deprecated)]
+    let (config_tx, config_rx) = glib::MainContext::channel::<Event>(glib::Priority::DEFAULT);
+    let inotify_thread =
+        setup_inotify_thread(update_tx.clone(), Some(config_tx), cancel.clone(), config)?;
+
+    #[cfg(feature = "http")]
+    let http = if config.http_enabled {
+        Some(crate::http::Server::spawn(&config.http_bind)?)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "http"))]
+    if config.http_enabled {
+        warn!(
+            "The http server on {:?} is enabled but arch-audit-gtk was built without the http feature",
+            config.http_bind
+        );
+    }
 
-    thread::spawn(move || {
-        updater::background(update_rx, result_tx);
+    // icons forced by socket clients, a debug aid for theme authors
+    #[allow(deprecated)]
+    let (icon_tx, icon_rx) = glib::MainContext::channel::<String>(glib::Priority::DEFAULT);
+    let socket = match (config.socket_enabled, &config.socket_path) {
+        (true, Some(path)) => Some(crate::socket::Server::spawn(
+            path,
+            config.socket_stream,
+            config.socket_commands.then(|| icon_tx.clone()),
+        )?),
+        (true, None) => {
+            warn!("The unix socket is enabled but $XDG_RUNTIME_DIR is unset, configure a path");
+            None
+        }
+        (false, _) => None,
+    };
+
+    let dbus = if config.dbus_enabled {
+        Some(crate::dbus::Service::spawn(update_tx.clone())?)
+    } else {
+        None
+    };
+
+    let socket_cleanup = socket.clone();
+    let updater_config = config.clone();
+    let updater_cancel = cancel.clone();
+    let snooze = Snooze::load();
+    let updater_snooze = snooze.clone();
+    // the restored result is shown right away, so its advisories aren't new
+    // mock results are neither restored nor saved
+    let mocked = config.mock.is_some();
+    let acknowledged = if mocked {
+        Acknowledged::default()
+    } else {
+        Acknowledged::load()
+    };
+    let restored = if mocked {
+        None
+    } else {
+        crate::state::load(config, &acknowledged)
+    };
+    let updater_seen = restored
+        .as_ref()
+        .map(|r| r.seen(config))
+        .unwrap_or_default();
+    let mut history = crate::history::History::new(config, restored.as_ref());
+    let updater_online = !config.wait_for_network || crate::dbus::watch_network(update_tx.clone());
+    #[cfg(feature = "http")]
+    let updater_http = http.clone();
+    let updater_acknowledged = acknowledged.clone();
+    let updater_thread = thread::spawn(move || {
+        updater::background(
+            updater_config,
+            updater_cancel,
+            updater_snooze,
+            updater_acknowledged,
+            updater_seen,
+            updater_online,
+            update_rx,
+            result_tx,
+        );
+        #[cfg(feature = "http")]
+        if let Some(http) = updater_http {
+            http.set_alive(false);
+        }
     });
 
-    let mut tray_icon = TrayIcon::create(&config.icon_theme, &Icon::Check);
+    let tray_icon = Rc::new(RefCell::new(TrayIcon::create(config, &Icon::Check)));
+    // in critical_only mode the tray only shows up for critical advisories
+    tray_icon.borrow_mut().set_active(!config.critical_only);
 
-    let mut m = gtk::Menu::new();
+    let MainMenu {
+        mut menu,
+        checking_mi,
+        status_mi,
+        checked_mi,
+        news_mi,
+        snooze_mi,
+        snooze_items,
+        upgrade_mi,
+        upgrade_vulnerable_mi,
+        warnings_mi,
+        reboot_mi,
+        preferences_mi,
+        mute_mi,
+        actions_separator,
+        version_warning,
+    } = MainMenu::build(config);
+    let m = &mut menu;
+
+    let mut notifier = Notifier::default();
+    if let Some(warning) = &version_warning {
+        notifier.warning(config, warning);
+    }
+
+    // with the merged layout the status item shows that a check is running
+    let merged = config.menu_layout == MenuLayout::Merged;
+    let progress_mi = if merged {
+        status_mi.clone()
+    } else {
+        checking_mi.clone()
+    };
 
-    let checking_mi = gtk::MenuItem::with_label(CHECK_FOR_UPDATE);
-    m.append(&checking_mi);
-    let mi = checking_mi.clone();
+    // updates installed from the menu show a distinct icon until the
+    // terminal is closed, a check afterwards restores the regular icon
+    let upgrading = Rc::new(Cell::new(false));
+    #[allow(deprecated)]
+    let (upgrade_tx, upgrade_rx) =
+        glib::MainContext::channel::<Result<(), String>>(glib::Priority::DEFAULT);
+    let vulnerable_tx = upgrade_tx.clone();
+    let start_upgrade = {
+        let upgrading = upgrading.clone();
+        let tray_icon = tray_icon.clone();
+        let status_mi = status_mi.clone();
+        let config = config.clone();
+        Rc::new(move || {
+            if upgrading.get() {
+                return;
+            }
+            match crate::upgrade::spawn(&config) {
+                Ok(mut child) => {
+                    upgrading.set(true);
+                    show_upgrading(&status_mi, &tray_icon, &config);
+                    let tx = upgrade_tx.clone();
+                    thread::spawn(move || {
+                        let result = match child.wait() {
+                            Ok(status) if status.success() => Ok(()),
+                            Ok(status) => Err(status.to_string()),
+                            Err(err) => Err(err.to_string()),
+                        };
+                        tx.send(result).ok();
+                    });
+                }
+                Err(err) => {
+                    warn!("{:#}", err);
+                    status_mi.set_label(&format!("{:#}", err));
+                    tray_icon
+                        .borrow_mut()
+                        .set_icon(&updater::error_icon(&config.icons));
+                }
+            }
+        })
+    };
+    {
+        let upgrading = upgrading.clone();
+        let tray_icon = tray_icon.clone();
+        let status_mi = status_mi.clone();
+        let mi = progress_mi.clone();
+        let tx = update_tx.clone();
+        let state = updater_state.clone();
+        let config = config.clone();
+        upgrade_rx.attach(None, move |result| {
+            upgrading.set(false);
+            match result {
+                Ok(()) => {
+                    info!("Update command finished, checking for updates");
+                    request_check(&state, &tx, &mi);
+                }
+                Err(err) => {
+                    warn!("Update command failed: {}", err);
+                    status_mi.set_label(&tr("Installing updates failed: {}").replace("{}", &err));
+                    tray_icon
+                        .borrow_mut()
+                        .set_icon(&updater::error_icon(&config.icons));
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+    {
+        let start_upgrade = start_upgrade.clone();
+        upgrade_mi.connect_activate(move |_| start_upgrade());
+    }
+
+    let mi = progress_mi.clone();
+    let tx = update_tx.clone();
+    let state = updater_state.clone();
+    let ctrl_upgrade = config.upgrade_enabled;
     checking_mi.connect_activate(move |_| {
-        mi.set_label(CHECKING);
-        update_tx.send(Event::Click).unwrap();
+        // indicator menus usually don't forward the modifiers of the click,
+        // in that case this is a regular check
+        let ctrl = gtk::current_event_state()
+            .is_some_and(|state| state.contains(gtk::gdk::ModifierType::CONTROL_MASK));
+        if ctrl && ctrl_upgrade {
+            start_upgrade();
+            return;
+        }
+        request_check(&state, &tx, &mi);
     });
+    if merged {
+        let tx = update_tx.clone();
+        let state = updater_state.clone();
+        status_mi.connect_activate(move |mi| {
+            // items with a submenu are activated when the submenu opens
+            if mi.submenu().is_none() {
+                request_check(&state, &tx, mi);
+            }
+        });
+    }
 
-    let status_mi = gtk::MenuItem::with_label("Starting...");
-    m.append(&status_mi);
+    let news_url = config.news_url.clone();
+    let open_cmd = config.open_cmd.clone();
+    news_mi.connect_activate(move |_| {
+        if let Err(err) = open_link(open_cmd.as_deref(), &news_url) {
+            warn!("Failed to open link: {:#}", err);
+        }
+    });
 
-    tray_icon.add_menu(&mut m);
+    // a check after a timed snooze ends restores the alert
+    let snooze_timer = {
+        let snooze = snooze.clone();
+        let mi = progress_mi.clone();
+        let tx = update_tx.clone();
+        let state = updater_state.clone();
+        Rc::new(move || {
+            let Some(remaining) = snooze.remaining() else {
+                return;
+            };
+            let snooze = snooze.clone();
+            let mi = mi.clone();
+            let tx = tx.clone();
+            let state = state.clone();
+            // one extra second so the snooze has surely expired
+            glib::timeout_add_local_once(remaining + Duration::from_secs(1), move || {
+                // the snooze may have been extended in the meantime
+                if !snooze.is_snoozed() {
+                    info!("Snooze has ended, checking for updates");
+                    request_check(&state, &tx, &mi);
+                }
+            });
+        })
+    };
+    snooze_timer();
+    for (item, duration) in snooze_items {
+        let snooze = snooze.clone();
+        let snooze_timer = snooze_timer.clone();
+        let tray_icon = tray_icon.clone();
+        let config = config.clone();
+        let snooze_mi = snooze_mi.clone();
+        let news_mi = news_mi.clone();
+        let upgrade_mi = upgrade_mi.clone();
+        let upgrade_vulnerable_mi = upgrade_vulnerable_mi.clone();
+        let separator = actions_separator.clone();
+        item.connect_activate(move |_| {
+            let until = duration.until();
+            info!("Snoozing alerts: {:?}", until);
+            snooze.snooze(until);
+            snooze_timer();
+            snooze_mi.set_visible(false);
+            if let Some(separator) = &separator {
+                separator.set_visible(
+                    news_mi.is_visible()
+                        || upgrade_mi.is_visible()
+                        || upgrade_vulnerable_mi.is_visible(),
+                );
+            }
+            let mut tray_icon = tray_icon.borrow_mut();
+            tray_icon.set_icon(&updater::snoozed_icon(&config.icons));
+            tray_icon.set_labels(Vec::new());
+            if config.critical_only {
+                tray_icon.set_active(false);
+            }
+        });
+    }
+    // changed by the preferences, the updater gets a copy of the new config
+    let ui_config = Rc::new(RefCell::new(config.clone()));
+    watch_desktop_theme(&tray_icon, &ui_config);
+    {
+        let ui_config = ui_config.clone();
+        let tray_icon = tray_icon.clone();
+        let mi = progress_mi.clone();
+        let tx = update_tx.clone();
+        preferences_mi.connect_activate(move |_| {
+            let current = ui_config.borrow().clone();
+            let ui_config = ui_config.clone();
+            let tray_icon = tray_icon.clone();
+            let mi = mi.clone();
+            let tx = tx.clone();
+            crate::preferences::show(&current, move |prefs| {
+                let mut config = ui_config.borrow_mut();
+                prefs.apply(&mut config);
+                // the icon is replaced with the result of the next check
+                tray_icon.borrow_mut().set_theme(&config);
+                mi.set_label(&tr(CHECKING));
+                tx.send(Event::Reload(Box::new(config.clone()))).ok();
+            });
+        });
+    }
+    // menu layout and the servers are still set up once, everything else
+    // picks up a changed config file right away
+    let reload_config = {
+        let ui_config = ui_config.clone();
+        let tray_icon = tray_icon.clone();
+        let mi = progress_mi.clone();
+        let tx = update_tx.clone();
+        Rc::new(move || {
+            let config = match Config::load(&args) {
+                Ok(config) => config,
+                Err(err) => {
+                    warn!("Ignoring invalid config: {:#}", err);
+                    return;
+                }
+            };
+            if config == *ui_config.borrow() {
+                debug!("Config is unchanged");
+                return;
+            }
+            info!("Applying the changed config");
+            crate::logging::reload(&args, &config);
+            tray_icon.borrow_mut().set_theme(&config);
+            *ui_config.borrow_mut() = config.clone();
+            mi.set_label(&tr(CHECKING));
+            tx.send(Event::Reload(Box::new(config))).ok();
+        })
+    };
+    {
+        let reload_config = reload_config.clone();
+        config_rx.attach(None, move |_| {
+            reload_config();
+            glib::ControlFlow::Continue
+        });
+    }
+    // changes with dconf-editor or a synced profile are applied like edits of the file
+    #[cfg(feature = "gsettings")]
+    let _gsettings = match config.settings_backend {
+        crate::config::SettingsBackend::GSettings => {
+            let reload_config = reload_config.clone();
+            crate::gsettings::watch(move || reload_config())
+                .map_err(|err| warn!("Failed to watch gsettings: {:#}", err))
+                .ok()
+        }
+        crate::config::SettingsBackend::File => None,
+    };
+    for signal in [SIGINT, SIGTERM] {
+        glib::unix_signal_add_local(signal, move || {
+            info!("Received signal {}, shutting down", signal);
+            gtk::main_quit();
+            glib::ControlFlow::Break
+        });
+    }
+    // lets scripts poke the tray without D-Bus
+    glib::unix_signal_add_local(SIGHUP, move || {
+        info!("Received SIGHUP, reloading the config");
+        reload_config();
+        glib::ControlFlow::Continue
+    });
+    {
+        let mi = progress_mi.clone();
+        let tx = update_tx.clone();
+        let state = updater_state.clone();
+        glib::unix_signal_add_local(SIGUSR1, move || {
+            info!("Received SIGUSR1, checking for updates");
+            request_check(&state, &tx, &mi);
+            glib::ControlFlow::Continue
+        });
+    }
+
+    // the tooltip is refreshed every time the menu is opened, so relative
+    // times stay accurate
+    let last_checked = Rc::new(Cell::new(None::<(SystemTime, (usize, usize))>));
+    {
+        let last_checked = last_checked.clone();
+        let status_mi = status_mi.clone();
+        let config = config.clone();
+        m.connect_show(move |_| {
+            if let Some((time, counts)) = last_checked.get() {
+                status_mi.set_tooltip_text(Some(&last_checked_text(time, counts, &config)));
+            }
+        });
+    }
+
+    // optionally refresh a stale result when the menu is opened
+    let last_result = Rc::new(Cell::new(None::<Instant>));
+    if config.recheck_on_open {
+        let last_result = last_result.clone();
+        let last_recheck = Cell::new(None::<Instant>);
+        let threshold = config.recheck_after;
+        let mi = progress_mi.clone();
+        let update_tx = update_tx.clone();
+        let state = updater_state.clone();
+        m.connect_show(move |_| {
+            let stale = last_result
+                .get()
+                .map(|t| t.elapsed() >= threshold)
+                .unwrap_or(false);
+            let debounced = last_recheck
+                .get()
+                .map(|t| t.elapsed() < RECHECK_DEBOUNCE)
+                .unwrap_or(false);
+
+            if stale && !debounced {
+                info!("Result is stale, checking for updates");
+                last_recheck.set(Some(Instant::now()));
+                request_check(&state, &update_tx, &mi);
+            }
+        });
+    }
+
+    tray_icon.borrow_mut().add_menu(m);
+
+    // a middle click is a shortcut for the item that starts a check, it has
+    // to be part of the menu
+    if config.middle_click && progress_mi.parent().is_some() {
+        tray_icon
+            .borrow_mut()
+            .set_middle_click_item(Some(&progress_mi));
+    }
+    {
+        let weak = Rc::downgrade(&tray_icon);
+        tray_icon
+            .borrow()
+            .indicator
+            .connect_scroll(move |direction| {
+                if let Some(tray_icon) = weak.upgrade() {
+                    let forward = matches!(
+                        direction,
+                        gtk::gdk::ScrollDirection::Down | gtk::gdk::ScrollDirection::Right
+                    );
+                    tray_icon.borrow_mut().scroll_label(forward);
+                }
+            });
+    }
 
-    result_rx.attach(None, move |msg| {
+    {
+        let tray_icon = tray_icon.clone();
+        let config = config.clone();
+        icon_rx.attach(None, move |state| {
+            match updater::state_icon(&state, &config.icons) {
+                Some(icon) => {
+                    info!("Forcing icon for state {:?} until the next check", state);
+                    let mut tray_icon = tray_icon.borrow_mut();
+                    tray_icon.set_icon(&icon);
+                    tray_icon.set_active(true);
+                }
+                None => warn!("Ignoring unknown icon state: {:?}", state),
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    // animate the icon while a check is running, except while installing updates
+    let on_checking = {
+        let tray_icon = tray_icon.clone();
+        let ui_config = ui_config.clone();
+        let upgrading = upgrading.clone();
+        let mi = progress_mi.clone();
+        move |checking: bool| {
+            // automatic checks too, the result resets the label
+            if checking && !upgrading.get() {
+                mi.set_label(&tr(CHECKING));
+            }
+            let config = ui_config.borrow();
+            if checking && config.animate && !upgrading.get() {
+                TrayIcon::start_animation(&tray_icon, updater::checking_icons(&config.icons));
+            } else if !checking {
+                tray_icon.borrow_mut().stop_animation();
+            }
+        }
+    };
+
+    let click_tx = update_tx.clone();
+    let click_state = updater_state.clone();
+    let restored_mi = progress_mi.clone();
+    let main_menu = menu.clone();
+    // the actions of notifications refer to the last result
+    let shown_updates = Rc::new(RefCell::new(Vec::<Update>::new()));
+    let opened_links = Rc::new(RefCell::new(HashSet::<String>::new()));
+    {
+        let shown_updates = shown_updates.clone();
+        let opened_links = opened_links.clone();
+        let ui_config = ui_config.clone();
+        let mi = progress_mi.clone();
+        let update_tx = update_tx.clone();
+        let state = updater_state.clone();
+        notifier.on_action(move |action| {
+            let open_cmd = ui_config.borrow().open_cmd.clone();
+            match action {
+                Action::Open(link) => open_once(&opened_links, open_cmd.as_deref(), &link),
+                Action::Details => crate::details::show(&shown_updates.borrow(), open_cmd),
+                Action::Check => request_check(&state, &update_tx, &mi),
+            }
+        });
+    }
+    {
+        let shown_updates = shown_updates.clone();
+        let upgrading = upgrading.clone();
+        let tray_icon = tray_icon.clone();
+        let status_mi = status_mi.clone();
+        let config = config.clone();
+        upgrade_vulnerable_mi.connect_activate(move |_| {
+            if upgrading.get() {
+                return;
+            }
+            let started = {
+                let upgrading = upgrading.clone();
+                let tray_icon = tray_icon.clone();
+                let status_mi = status_mi.clone();
+                let config = config.clone();
+                move || {
+                    upgrading.set(true);
+                    show_upgrading(&status_mi, &tray_icon, &config);
+                }
+            };
+            let tx = vulnerable_tx.clone();
+            let done = move |result| {
+                tx.send(result).ok();
+            };
+            if let Err(err) = crate::pkexec::upgrade(&shown_updates.borrow(), started, done) {
+                warn!("{:#}", err);
+                status_mi.set_label(&format!("{:#}", err));
+                tray_icon
+                    .borrow_mut()
+                    .set_icon(&updater::error_icon(&config.icons));
+            }
+        });
+    }
+    // the attention is held back during quiet hours and asked for once they end
+    let critical_alert = Rc::new(Cell::new(false));
+    {
+        let critical_alert = critical_alert.clone();
+        let tray_icon = tray_icon.clone();
+        let ui_config = ui_config.clone();
+        let mut was_quiet = false;
+        glib::timeout_add_seconds_local(QUIET_HOURS_INTERVAL, move || {
+            let config = ui_config.borrow();
+            let quiet = notifications::is_quiet(&config);
+            if quiet != was_quiet {
+                info!("Quiet hours {}", if quiet { "started" } else { "ended" });
+                let attention = critical_alert.get() && config.attention && !quiet;
+                tray_icon.borrow_mut().set_attention(attention);
+                was_quiet = quiet;
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+    // acknowledging an advisory shows the last result again, with new counts
+    #[allow(deprecated)]
+    let (ack_tx, ack_rx) = glib::MainContext::channel::<()>(glib::Priority::DEFAULT);
+    let acknowledger = Acknowledger {
+        acknowledged: acknowledged.clone(),
+        changed: ack_tx,
+    };
+    let displayed = Rc::new(RefCell::new(None::<CheckResult>));
+    let mut metrics = Metrics::new(config);
+    let mut status_hooks = hooks::StatusHooks::default();
+    let mut remote_items = Vec::<gtk::MenuItem>::new();
+    let handle_result = move |msg: CheckResult| {
         log::info!("Received from thread: {:?}", msg);
+        let ui_config = ui_config.borrow();
+        #[cfg(feature = "http")]
+        if let Some(http) = &http {
+            http.set_result(&msg);
+        }
+        if let Some(socket) = &socket {
+            socket.set_result(&msg);
+        }
+        if let Some(dbus) = &dbus {
+            dbus.set_result(&msg, &ui_config);
+        }
+        if let Some(metrics) = &mut metrics {
+            metrics.record(&msg);
+        }
+        crate::motd::write(&ui_config, &msg);
+        if let Status::Offline = msg.status {
+            // the last result is still the best we know, keep its menu and icon
+            checking_mi.set_label(&tr(CHECK_FOR_UPDATE));
+            status_mi.set_label(&msg.text(&ui_config.count_by));
+            checked_mi.hide();
+            let mut tray_icon = tray_icon.borrow_mut();
+            tray_icon.set_title(&msg.text(&ui_config.count_by));
+            if last_checked.get().is_none() {
+                tray_icon.set_icon(&msg.status.icon(&ui_config));
+            }
+            return;
+        }
+        last_result.set(Some(Instant::now()));
+        hooks::handle(&ui_config, &msg, snooze.is_snoozed());
+        status_hooks.handle(&ui_config, &msg);
+        notifier.handle(&ui_config, &msg, snooze.is_snoozed());
+        for link in notifications::auto_open_links(&ui_config, &msg, snooze.is_snoozed()) {
+            open_once(&opened_links, ui_config.open_cmd.as_deref(), &link);
+        }
+        crate::sound::handle(&ui_config, &msg, snooze.is_snoozed(), mute_mi.is_active());
+        let counts = (msg.advisories, msg.packages);
+        last_checked.set(Some((msg.checked_at, counts)));
+        status_mi.set_tooltip_text(Some(&last_checked_text(msg.checked_at, counts, &ui_config)));
+        debug!(
+            "Check by {} backend finished at {} after {}{}",
+            msg.backend,
+            humantime::format_rfc3339_seconds(msg.checked_at),
+            humantime::format_duration(msg.duration),
+            if msg.partial { " (partial result)" } else { "" },
+        );
 
         // update text in main menu
-        checking_mi.set_label(CHECK_FOR_UPDATE);
-        status_mi.set_label(&msg.text());
+        checking_mi.set_label(&tr(CHECK_FOR_UPDATE));
+        // acknowledged advisories only count as clean, like for the icon
+        let vulnerable = msg.status.alert_severity(&ui_config).is_some();
+        match (&msg.status, &ui_config.status_item_format) {
+            (Status::MissingUpdates(_), Some(format)) if vulnerable => {
+                status_mi.set_label(&msg.format(format, &ui_config.count_by));
+            }
+            _ => status_mi.set_label(&msg.text(&ui_config.count_by)),
+        }
 
-        match msg {
+        if let Status::MissingUpdates(updates) = &msg.status {
+            *shown_updates.borrow_mut() = updates.clone();
+        }
+        match msg.status {
             Status::MissingUpdates(ref updates) if !updates.is_empty() => {
-                let m = gtk::Menu::new();
-
-                for update in updates {
-                    let mi = gtk::MenuItem::with_label(&update.text);
-                    m.append(&mi);
-                    let link = update.link.to_string();
-                    mi.connect_activate(move |_| {
-                        if let Err(err) = opener::open(&link) {
-                            eprintln!("Failed to open link: {:#}", err);
-                        }
-                    });
+                let m = advisory_menu(updates, &ui_config, Some(&acknowledger));
+                if merged {
+                    // the status item can't be clicked with a submenu
+                    let mi = gtk::MenuItem::with_mnemonic(&tr(CHECK_FOR_UPDATE));
+                    let status_mi = status_mi.clone();
+                    let tx = click_tx.clone();
+                    let state = click_state.clone();
+                    mi.connect_activate(move |_| request_check(&state, &tx, &status_mi));
+                    m.prepend(&gtk::SeparatorMenuItem::new());
+                    m.prepend(&mi);
                 }
-
                 m.show_all();
                 status_mi.set_submenu(Some(&m));
+                news_mi.set_visible(ui_config.show_news && !ui_config.compact_menu);
+                upgrade_mi.set_visible(
+                    ui_config.upgrade_enabled
+                        && !ui_config.compact_menu
+                        && updates.iter().any(Update::fixable),
+                );
+                upgrade_vulnerable_mi.set_visible(
+                    ui_config.upgrade_vulnerable
+                        && ui_config.ssh_target.is_none()
+                        && !ui_config.compact_menu
+                        && crate::pkexec::any_packages(updates),
+                );
+                snooze_mi.set_visible(!snooze.is_snoozed());
             }
             _ => {
-                status_mi.set_submenu(None::<&gtk::Menu>);
+                let hint = msg.error_kind.and_then(ErrorKind::hint);
+                let m = error_menu(hint.as_deref(), msg.raw_output.as_deref());
+                status_mi.set_submenu(m.as_ref());
+                news_mi.set_visible(false);
+                upgrade_mi.set_visible(false);
+                upgrade_vulnerable_mi.set_visible(false);
+                snooze_mi.set_visible(false);
+            }
+        }
+        if let Some(separator) = &actions_separator {
+            separator.set_visible(
+                news_mi.is_visible()
+                    || upgrade_mi.is_visible()
+                    || upgrade_vulnerable_mi.is_visible()
+                    || snooze_mi.is_visible(),
+            );
+        }
+        checked_mi.set_label(&checked_text(&msg));
+        checked_mi.show();
+        warnings_mi.set_label(&msg.warnings.join("\n"));
+        warnings_mi.set_visible(!msg.warnings.is_empty() && !ui_config.compact_menu);
+        reboot_mi
+            .set_label(&tr("Reboot required: {}").replace("{}", &msg.reboot_required.join(", ")));
+        reboot_mi.set_visible(!msg.reboot_required.is_empty() && !ui_config.compact_menu);
+
+        // the remote hosts follow the local status
+        for mi in remote_items.drain(..) {
+            main_menu.remove(&mi);
+        }
+        let children = main_menu.children();
+        let anchor = [&reboot_mi, &status_mi].into_iter().find_map(|mi| {
+            children
+                .iter()
+                .position(|child| child == mi.upcast_ref::<gtk::Widget>())
+        });
+        if let Some(anchor) = anchor {
+            let tray_icon = tray_icon.borrow();
+            for (i, remote) in msg.remotes.iter().enumerate() {
+                let mi = remote_item(remote, &ui_config, &tray_icon);
+                main_menu.insert(&mi, (anchor + 1 + i) as i32);
+                mi.show_all();
+                remote_items.push(mi);
             }
         }
 
-        tray_icon.set_icon(&msg.icon());
+        let mut tray_icon = tray_icon.borrow_mut();
+        tray_icon.set_title(&format!(
+            "{}\n{}",
+            msg.text(&ui_config.count_by),
+            checked_text(&msg)
+        ));
+        match &msg.status {
+            // the label is hidden while snoozed, like the alert
+            Status::MissingUpdates(_) if ui_config.label && vulnerable && !snooze.is_snoozed() => {
+                let labels = [&ui_config.label_format]
+                    .into_iter()
+                    .chain(&ui_config.scroll_formats)
+                    .map(|format| msg.format(format, &ui_config.count_by))
+                    .collect();
+                tray_icon.set_labels(labels);
+            }
+            _ => tray_icon.set_labels(Vec::new()),
+        }
 
-        glib::ControlFlow::Continue
-    });
+        // errors are still shown while snoozed, advisories of remote hosts alert too
+        let status = msg.combined_status();
+        if upgrading.get() {
+            // pacman triggers checks while it's running, keep the icon until
+            // the terminal is closed
+            debug!("Installing updates, not changing the icon");
+        } else if snooze.is_snoozed() && matches!(*status, Status::MissingUpdates(_)) {
+            tray_icon.set_icon(&updater::snoozed_icon(&ui_config.icons));
+            if ui_config.critical_only {
+                tray_icon.set_active(false);
+            }
+            critical_alert.set(false);
+        } else if matches!(*status, Status::MissingUpdates(_))
+            && !msg.reboot_required.is_empty()
+            && status.alert_severity(&ui_config).is_none()
+        {
+            // nothing to install, but the last upgrade isn't fully active yet
+            tray_icon.set_icon(&updater::reboot_icon(&ui_config.icons));
+            if ui_config.critical_only {
+                tray_icon.set_active(false);
+            }
+            critical_alert.set(false);
+        } else {
+            let critical = status.alert_severity(&ui_config) == Some(Severity::Critical);
+            tray_icon.set_icon(&status.icon(&ui_config));
+            if ui_config.critical_only {
+                tray_icon.set_active(critical);
+            }
+            let quiet = notifications::is_quiet(&ui_config);
+            tray_icon.set_attention(critical && ui_config.attention && !quiet);
+            critical_alert.set(critical);
+        }
+    };
 
+    let handle_result = Rc::new(RefCell::new(handle_result));
+    let saved_acknowledged = acknowledged.clone();
+    {
+        let handle_result = handle_result.clone();
+        let displayed = displayed.clone();
+        ack_rx.attach(None, move |()| {
+            let Some(mut msg) = displayed.borrow().clone() else {
+                return glib::ControlFlow::Continue;
+            };
+            // the advisories were already announced, only counts and icon change
+            if let Status::MissingUpdates(updates) = &mut msg.status {
+                for update in updates {
+                    update.new = false;
+                }
+            }
+            msg.acknowledge(&acknowledged);
+            if !mocked {
+                if let Err(err) = crate::state::save(&msg, &acknowledged) {
+                    warn!("Failed to save the acknowledged advisories: {:#}", err);
+                }
+            }
+            *displayed.borrow_mut() = Some(msg.clone());
+            handle_result.borrow_mut()(msg);
+            glib::ControlFlow::Continue
+        });
+    }
+
+    // show the last known result until the first check is done
+    if let Some(result) = restored {
+        info!(
+            "Showing the result from {}",
+            humantime::format_rfc3339_seconds(result.checked_at)
+        );
+        *displayed.borrow_mut() = Some(result.clone());
+        handle_result.borrow_mut()(result);
+        if config.check_on_startup && !merged {
+            restored_mi.set_label(&tr(CHECKING));
+        }
+    }
+    result_rx.attach_with_checking(
+        move |msg| {
+            if !mocked {
+                if let Err(err) = crate::state::save(&msg, &saved_acknowledged) {
+                    warn!("Failed to save the last result: {:#}", err);
+                }
+                if let Err(err) = history.record(&msg) {
+                    warn!("Failed to update the advisory history: {:#}", err);
+                }
+            }
+            // offline results keep the menu of the last one
+            if !matches!(msg.status, Status::Offline) {
+                *displayed.borrow_mut() = Some(msg.clone());
+            }
+            handle_result.borrow_mut()(msg);
+        },
+        on_checking,
+    );
+
+    crate::systemd::ready();
+    crate::systemd::start_watchdog();
     gtk::main();
+    crate::systemd::stopping();
+
+    // don't leave a running arch-audit process behind, and wait for the
+    // threads so nothing is written after the main thread is gone
+    cancel.cancel();
+    updater_state.close();
+    update_tx.send(Event::Shutdown).ok();
+    if let Some(socket) = &socket_cleanup {
+        socket.cleanup();
+    }
+    if let Some(pid_file) = &pid_file {
+        pid_file.cleanup();
+    }
+    updater_thread.join().ok();
+    if let Some(inotify_thread) = inotify_thread {
+        inotify_thread.join().ok();
+    }
+    debug!("All threads have shut down");
 
     Ok(())
 }
@@ -205,7 +2542,7 @@ pub fn main(config: &Config) -> Result<()> {
 pub fn debug_icon(config: &Config, icon: &Icon) -> Result<()> {
     gtk::init()?;
 
-    let mut tray_icon = TrayIcon::create(&config.icon_theme, icon);
+    let mut tray_icon = TrayIcon::create(config, icon);
 
     let mut m = gtk::Menu::new();
     tray_icon.add_menu(&mut m);
@@ -214,3 +2551,51 @@ pub fn debug_icon(config: &Config, icon: &Icon) -> Result<()> {
 
     Ok(())
 }
+
+pub fn check_theme(config: &Config, theme: &Theme) -> Result<()> {
+    if theme.is_system() {
+        println!(
+            "Theme {:?} uses the icons of the system icon theme",
+            theme.as_str()
+        );
+        return Ok(());
+    }
+    let theme_dir = ThemeDir::find(theme).with_context(|| {
+        anyhow!(
+            "Theme {:?} not found in any of {:?}",
+            theme.as_str(),
+            icon_dirs()
+        )
+    })?;
+    println!("Theme {:?} found in {:?}", theme.as_str(), theme_dir.path());
+
+    let mut missing = 0;
+    let mut check = |name: &str, required: bool| {
+        let exists = theme_dir.contains(name);
+        let state = match (exists, required) {
+            (true, _) => "ok",
+            (false, true) => "MISSING",
+            (false, false) => "missing (optional)",
+        };
+        println!("  {:>20}.svg: {}", name, state);
+        if !exists && required {
+            missing += 1;
+        }
+    };
+
+    for icon in REQUIRED_ICONS {
+        check(icon.as_str(), true);
+    }
+    for icon in OPTIONAL_ICONS {
+        check(icon.as_str(), false);
+    }
+    // icons configured with the [icons] table are only needed with this config
+    for name in config.icons.values().collect::<BTreeSet<_>>() {
+        check(name, false);
+    }
+
+    if missing > 0 {
+        bail!("Theme is missing {} required icon(s)", missing);
+    }
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::gui::open_link;
+use crate::i18n::tr;
+use crate::updater::{severity_key, severity_name, CheckResult, Status, Update};
+use arch_audit::types::Severity;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "arch-audit-gtk/history.jsonl";
+/// Older entries are dropped, so the file doesn't grow forever
+const MAX_ENTRIES: usize = 1000;
+const STATS_FILE: &str = "arch-audit-gtk/stats.jsonl";
+/// Enough for a few months of hourly checks
+const MAX_SAMPLES: usize = 5000;
+
+const TIME: u32 = 0;
+const EVENT: u32 = 1;
+const PKG: u32 = 2;
+const AVG: u32 = 3;
+const ISSUES: u32 = 4;
+const SEVERITY: u32 = 5;
+/// Hidden columns, the time is sorted by timestamp instead of by text
+const TIMESTAMP: u32 = 6;
+const LINK: u32 = 7;
+
+const COLUMNS: &[(&str, u32, u32)] = &[
+    ("Time", TIME, TIMESTAMP),
+    ("Event", EVENT, EVENT),
+    ("Package", PKG, PKG),
+    ("Advisory", AVG, AVG),
+    ("Issues", ISSUES, ISSUES),
+    ("Severity", SEVERITY, SEVERITY),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    /// The advisory affects an installed package
+    Appeared,
+    /// Severity, status or fixed version of the advisory changed
+    Updated,
+    /// The advisory is gone, usually because the package was upgraded
+    Fixed,
+}
+
+impl Transition {
+    fn label(self) -> String {
+        match self {
+            Transition::Appeared => tr("Appeared"),
+            Transition::Updated => tr("Updated"),
+            Transition::Fixed => tr("Fixed"),
+        }
+    }
+}
+
+/// One line of the history file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix timestamp of the check that noticed the transition
+    pub time: u64,
+    pub event: Transition,
+    pub pkg: String,
+    pub avg: String,
+    pub severity: Severity,
+    #[serde(default)]
+    pub issues: Vec<String>,
+    #[serde(default)]
+    pub link: String,
+}
+
+impl Entry {
+    fn new(time: u64, event: Transition, update: &Update) -> Self {
+        Entry {
+            time,
+            event,
+            pkg: update.pkg.clone(),
+            avg: update.avg.clone(),
+            severity: update.severity,
+            issues: update.issues.clone(),
+            link: update.link.clone(),
+        }
+    }
+}
+
+/// Vulnerable packages by their highest severity at the time of a check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub time: u64,
+    pub packages: BTreeMap<String, usize>,
+}
+
+impl Sample {
+    fn new(time: u64, updates: &[Update]) -> Self {
+        // a package counts once, like in the metrics
+        let mut highest = HashMap::<&str, Severity>::new();
+        for update in updates {
+            let severity = highest.entry(&update.pkg).or_insert(update.severity);
+            *severity = (*severity).max(update.severity);
+        }
+        let mut packages = BTreeMap::new();
+        for severity in highest.values() {
+            *packages
+                .entry(severity_key(*severity).to_string())
+                .or_default() += 1;
+        }
+        Sample { time, packages }
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.packages
+            .get(severity_key(severity))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.packages.values().sum()
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    dirs_next::data_local_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+fn stats_path() -> Option<PathBuf> {
+    dirs_next::data_local_dir().map(|dir| dir.join(STATS_FILE))
+}
+
+type Advisories = HashMap<(String, String), Update>;
+
+fn advisories(updates: &[Update]) -> Advisories {
+    updates
+        .iter()
+        .map(|u| ((u.avg.clone(), u.pkg.clone()), u.clone()))
+        .collect()
+}
+
+/// Compares every result to the previous one and appends the differences to
+/// the history file
+pub struct History {
+    enabled: bool,
+    previous: Option<Advisories>,
+}
+
+impl History {
+    /// The restored result is the baseline, so advisories aren't logged again
+    /// on every start
+    pub fn new(config: &Config, restored: Option<&CheckResult>) -> Self {
+        let previous = restored.and_then(|result| match &result.status {
+            Status::MissingUpdates(updates) => Some(advisories(updates)),
+            _ => None,
+        });
+        History {
+            enabled: config.history_enabled,
+            previous,
+        }
+    }
+
+    pub fn record(&mut self, result: &CheckResult) -> Result<()> {
+        // failed checks say nothing about the advisories
+        let Status::MissingUpdates(updates) = &result.status else {
+            return Ok(());
+        };
+        if !self.enabled {
+            return Ok(());
+        }
+        let current = advisories(updates);
+        let previous = self.previous.replace(current.clone()).unwrap_or_default();
+
+        let time = result
+            .checked_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if let Some(path) = stats_path() {
+            let sample = serde_json::to_string(&Sample::new(time, updates))
+                .context("Failed to serialize statistics")?;
+            append(&path, &[sample], MAX_SAMPLES)?;
+        }
+
+        let mut entries = Vec::new();
+        for update in updates {
+            match previous.get(&(update.avg.clone(), update.pkg.clone())) {
+                None => entries.push(Entry::new(time, Transition::Appeared, update)),
+                Some(old)
+                    if old.severity != update.severity
+                        || old.avg_status != update.avg_status
+                        || old.fixed != update.fixed =>
+                {
+                    entries.push(Entry::new(time, Transition::Updated, update))
+                }
+                Some(_) => (),
+            }
+        }
+        for (key, update) in &previous {
+            if !current.contains_key(key) {
+                entries.push(Entry::new(time, Transition::Fixed, update));
+            }
+        }
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let Some(path) = path() else {
+            return Ok(());
+        };
+        let lines = entries
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to serialize history")?;
+        append(&path, &lines, MAX_ENTRIES)
+    }
+}
+
+/// Add lines to a json lines file, keeping at most `max` lines
+fn append(path: &Path, new: &[String], max: usize) -> Result<()> {
+    debug!("Adding {} entries to {:?}", new.len(), path);
+
+    let old = fs::read_to_string(path).unwrap_or_default();
+    let mut lines = old.lines().map(String::from).collect::<Vec<_>>();
+    lines.extend_from_slice(new);
+    let skip = lines.len().saturating_sub(max);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+    }
+    // replace the file atomically, like the state
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut content = lines[skip..].join("\n");
+    content.push('\n');
+    fs::write(&tmp, content).with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| anyhow!("Failed to move history to {:?}", path))
+}
+
+/// The valid lines of a json lines file, newest first, until `stop` returns
+/// true for a line
+fn read_lines<T, F>(path: Option<PathBuf>, mut stop: F) -> Vec<T>
+where
+    T: serde::de::DeserializeOwned,
+    F: FnMut(&T) -> bool,
+{
+    let Some(path) = path else {
+        return Vec::new();
+    };
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let mut values = Vec::new();
+    for line in content.lines().rev() {
+        match serde_json::from_str(line) {
+            Ok(value) => {
+                let done = stop(&value);
+                values.push(value);
+                if done {
+                    break;
+                }
+            }
+            Err(err) => warn!("Ignoring invalid history entry in {:?}: {:#}", path, err),
+        }
+    }
+    values
+}
+
+/// The most recent entries of the history file, newest first
+pub fn load(limit: usize) -> Vec<Entry> {
+    if limit == 0 {
+        return Vec::new();
+    }
+    let mut count = 0;
+    read_lines(path(), |_: &Entry| {
+        count += 1;
+        count >= limit
+    })
+}
+
+/// The samples of all checks since `since`, oldest first. The sample right
+/// before `since` is included, it's the state at the start of the range.
+pub fn load_samples(since: u64) -> Vec<Sample> {
+    let mut samples = read_lines(stats_path(), |sample: &Sample| sample.time < since);
+    samples.reverse();
+    samples
+}
+
+fn format_time(time: u64) -> String {
+    glib::DateTime::from_unix_local(time as i64)
+        .and_then(|dt| dt.format("%x %X"))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|_| {
+            humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(time)).to_string()
+        })
+}
+
+fn store(entries: &[Entry]) -> gtk::ListStore {
+    let store = gtk::ListStore::new(&[
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::STRING,
+        glib::Type::U64,
+        glib::Type::STRING,
+    ]);
+    for entry in entries {
+        store.insert_with_values(
+            None,
+            &[
+                (TIME, &format_time(entry.time)),
+                (EVENT, &entry.event.label()),
+                (PKG, &entry.pkg),
+                (AVG, &entry.avg),
+                (ISSUES, &entry.issues.join(", ")),
+                (SEVERITY, &severity_name(entry.severity)),
+                (TIMESTAMP, &entry.time),
+                (LINK, &entry.link),
+            ],
+        );
+    }
+    store
+}
+
+/// Open a window listing the most recent transitions, newest first.
+/// Activating a row opens the advisory in the browser.
+pub fn show(config: &Config) {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&tr("Recent advisories"));
+    window.set_default_size(800, 400);
+
+    let entries = load(config.history_limit);
+    let store = store(&entries);
+    let sorted = gtk::TreeModelSort::new(&store);
+    sorted.set_sort_column_id(gtk::SortColumn::Index(TIMESTAMP), gtk::SortType::Descending);
+
+    let view = gtk::TreeView::with_model(&sorted);
+    for (title, column, sort) in COLUMNS {
+        let renderer = gtk::CellRendererText::new();
+        let col = gtk::TreeViewColumn::with_attributes(
+            &tr(title),
+            &renderer,
+            &[("text", *column as i32)],
+        );
+        col.set_sort_column_id(*sort as i32);
+        col.set_resizable(true);
+        view.append_column(&col);
+    }
+    let open_cmd = config.open_cmd.clone();
+    view.connect_row_activated(move |view, path, _| {
+        let Some(model) = view.model() else {
+            return;
+        };
+        let Some(iter) = model.iter(path) else {
+            return;
+        };
+        match model.value(&iter, LINK as i32).get::<String>() {
+            Ok(link) if !link.is_empty() => {
+                if let Err(err) = open_link(open_cmd.as_deref(), &link) {
+                    warn!("Failed to open link: {:#}", err);
+                }
+            }
+            _ => (),
+        }
+    });
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    if entries.is_empty() {
+        let label = gtk::Label::new(Some(&tr("No advisories recorded yet")));
+        scrolled.add(&label);
+    } else {
+        scrolled.add(&view);
+    }
+    window.add(&scrolled);
+
+    window.show_all();
+    window.present();
+}
@@ -0,0 +1,206 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::updater::{severity_key, CheckResult, Status, Update};
+use arch_audit::types::Severity;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// States the `[hooks]` commands react to, offline results keep the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusKind {
+    /// Advisories worth an alert are missing updates
+    Vulnerable,
+    Clean,
+    Error,
+}
+
+impl StatusKind {
+    fn of(config: &Config, status: &Status) -> Option<Self> {
+        match status {
+            Status::MissingUpdates(_) if status.alert_severity(config).is_some() => {
+                Some(StatusKind::Vulnerable)
+            }
+            Status::MissingUpdates(_) => Some(StatusKind::Clean),
+            Status::Error(_) => Some(StatusKind::Error),
+            Status::Offline => None,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            StatusKind::Vulnerable => "vulnerable",
+            StatusKind::Clean => "clean",
+            StatusKind::Error => "error",
+        }
+    }
+}
+
+/// Runs the `[hooks]` commands whenever the status changes between
+/// vulnerable, clean and failed, the first result counts as a change
+#[derive(Debug, Default)]
+pub struct StatusHooks {
+    last: Option<StatusKind>,
+}
+
+impl StatusHooks {
+    pub fn handle(&mut self, config: &Config, result: &CheckResult) {
+        // advisories of remote hosts count, like for the icon
+        let status = result.combined_status();
+        let Some(kind) = StatusKind::of(config, &status) else {
+            return;
+        };
+        let previous = self.last.replace(kind);
+        if previous == Some(kind) {
+            return;
+        }
+        let command = match kind {
+            StatusKind::Vulnerable => &config.on_vulnerable,
+            StatusKind::Clean => &config.on_clean,
+            StatusKind::Error => &config.on_error,
+        };
+        let Some(command) = command else {
+            return;
+        };
+        if let Err(err) = run_status_command(command, kind, previous, config, result, &status) {
+            warn!("Failed to run the on_{} command: {:#}", kind.key(), err);
+        }
+    }
+}
+
+/// Runs `command` with a shell, the advisories worth an alert are passed in
+/// environment variables and the whole result as json on stdin
+fn run_status_command(
+    command: &str,
+    kind: StatusKind,
+    previous: Option<StatusKind>,
+    config: &Config,
+    result: &CheckResult,
+    status: &Status,
+) -> Result<()> {
+    let json = result.to_json()?;
+    let alerts = match status {
+        Status::MissingUpdates(updates) => updates.iter().filter(|u| u.alerts(config)).collect(),
+        Status::Error(_) | Status::Offline => Vec::new(),
+    };
+    let join = |field: fn(&Update) -> &str| {
+        let values = alerts.iter().map(|u| field(u)).collect::<BTreeSet<_>>();
+        values.into_iter().collect::<Vec<_>>().join(",")
+    };
+    let error = match status {
+        Status::Error(err) => err.message.as_str(),
+        Status::MissingUpdates(_) | Status::Offline => "",
+    };
+
+    debug!("Running the on_{} command: {:?}", kind.key(), command);
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ARCH_AUDIT_STATUS", kind.key())
+        .env("ARCH_AUDIT_PREVIOUS", previous.map_or("", StatusKind::key))
+        .env("ARCH_AUDIT_COUNT", alerts.len().to_string())
+        .env(
+            "ARCH_AUDIT_SEVERITY",
+            status.alert_severity(config).map_or("none", severity_key),
+        )
+        .env("ARCH_AUDIT_PACKAGES", join(|u| &u.pkg))
+        .env("ARCH_AUDIT_AVGS", join(|u| &u.avg))
+        .env("ARCH_AUDIT_ERROR", error)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run sh")?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Failed to open stdin of the command")?;
+    thread::spawn(move || {
+        // commands that ignore stdin may exit before reading it
+        stdin.write_all(json.as_bytes()).ok();
+        drop(stdin);
+        child.wait()
+    });
+    Ok(())
+}
+
+/// Runs the `[on_severity]` commands for advisories that weren't part of the
+/// previous result
+///
+/// Advisories that show up while snoozed don't run any command, they aren't
+/// new anymore once the snooze ends.
+pub fn handle(config: &Config, result: &CheckResult, snoozed: bool) {
+    let Status::MissingUpdates(updates) = &result.status else {
+        return;
+    };
+    if snoozed {
+        return;
+    }
+
+    let mut new = BTreeMap::<Severity, Vec<&Update>>::new();
+    for update in updates.iter().filter(|update| update.new) {
+        new.entry(update.severity).or_default().push(update);
+    }
+    for (severity, updates) in new.iter().rev() {
+        let Some(command) = config.on_severity.get(severity) else {
+            continue;
+        };
+        if let Err(err) = run_command(command, *severity, updates) {
+            warn!(
+                "Failed to run command for {} advisories: {:#}",
+                severity_key(*severity),
+                err
+            );
+        }
+    }
+}
+
+fn run_command(command: &str, severity: Severity, updates: &[&Update]) -> Result<()> {
+    let packages = updates
+        .iter()
+        .map(|u| u.pkg.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    let avgs = updates
+        .iter()
+        .map(|u| u.avg.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    debug!(
+        "Running {:?} for new {} advisories",
+        command,
+        severity_key(severity)
+    );
+    spawn_template(
+        command,
+        &[
+            ("{severity}", severity_key(severity)),
+            ("{count}", &updates.len().to_string()),
+            ("{packages}", &packages),
+            ("{avgs}", &avgs),
+        ],
+    )
+}
+
+/// Run a configured command in the background, the placeholders are
+/// replaced per argument so no shell is involved
+///
+/// Arguments are split at whitespace, quotes aren't supported, so only a
+/// placeholder can expand to an argument with spaces.
+pub fn spawn_template(command: &str, placeholders: &[(&str, &str)]) -> Result<()> {
+    let args = command
+        .split_whitespace()
+        .map(|arg| {
+            placeholders
+                .iter()
+                .fold(arg.to_string(), |arg, (key, value)| arg.replace(key, value))
+        })
+        .collect::<Vec<_>>();
+    let (bin, args) = args.split_first().context("Command is empty")?;
+    let mut child = Command::new(bin)
+        .args(args)
+        .spawn()
+        .with_context(|| anyhow!("Failed to run {:?}", bin))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::errors::*;
+use crate::updater::CheckResult;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// State shared between the gui thread and the http server
+#[derive(Debug, Clone)]
+pub struct Server {
+    latest: Arc<Mutex<Option<String>>>,
+    alive: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub fn spawn(bind: &str) -> Result<Self> {
+        let listener = TcpListener::bind(bind)
+            .with_context(|| anyhow!("Failed to bind http server to {:?}", bind))?;
+        info!("Serving status on http://{}/status", bind);
+
+        let server = Server {
+            latest: Arc::new(Mutex::new(None)),
+            alive: Arc::new(AtomicBool::new(true)),
+        };
+
+        let s = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = s.handle(stream) {
+                            debug!("Failed to handle http request: {:#}", err);
+                        }
+                    }
+                    Err(err) => warn!("Failed to accept http connection: {:#}", err),
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    pub fn set_result(&self, result: &CheckResult) {
+        match result.to_json() {
+            Ok(json) => *self.latest.lock().unwrap() = Some(json),
+            Err(err) => warn!("{:#}", err),
+        }
+    }
+
+    pub fn set_alive(&self, alive: bool) {
+        self.alive.store(alive, Ordering::SeqCst);
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        let mut parts = request.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let path = parts.next().unwrap_or_default();
+        debug!("Received http request: {} {}", method, path);
+
+        let (status, body) = match (method, path) {
+            ("GET", "/status") => match self.latest.lock().unwrap().clone() {
+                Some(json) => ("200 OK", json),
+                None => ("503 Service Unavailable", "null".to_string()),
+            },
+            ("GET", "/healthz") => {
+                if self.alive.load(Ordering::SeqCst) {
+                    ("200 OK", r#"{"alive":true}"#.to_string())
+                } else {
+                    ("503 Service Unavailable", r#"{"alive":false}"#.to_string())
+                }
+            }
+            ("GET", _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+            _ => (
+                "405 Method Not Allowed",
+                r#"{"error":"method not allowed"}"#.to_string(),
+            ),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        Ok(())
+    }
+}
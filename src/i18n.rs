@@ -0,0 +1,42 @@
+//! Translations of user facing strings with gettext
+//!
+//! Catalogs are looked up as `<locale>/LC_MESSAGES/arch-audit-gtk.mo` in the
+//! system locale directory, English is used if there's no translation.
+
+use std::env;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+const DOMAIN: &str = "arch-audit-gtk";
+const LOCALE_DIR: &str = "/usr/share/locale";
+
+extern "C" {
+    fn bindtextdomain(domain: *const c_char, dir: *const c_char) -> *mut c_char;
+    fn bind_textdomain_codeset(domain: *const c_char, codeset: *const c_char) -> *mut c_char;
+}
+
+/// Select the locale from the environment and bind the catalogs of our
+/// domain, `$ARCH_AUDIT_LOCALEDIR` takes precedence over the system
+/// directory. gtk does the former too, but the cli runs without it.
+pub fn init() {
+    let dir = env::var("ARCH_AUDIT_LOCALEDIR").unwrap_or_else(|_| LOCALE_DIR.to_string());
+    let (Ok(domain), Ok(dir)) = (CString::new(DOMAIN), CString::new(dir)) else {
+        return;
+    };
+    unsafe {
+        libc::setlocale(libc::LC_ALL, c"".as_ptr());
+        bindtextdomain(domain.as_ptr(), dir.as_ptr());
+        // the strings are passed to gtk, which expects utf-8 regardless of the locale
+        bind_textdomain_codeset(domain.as_ptr(), c"UTF-8".as_ptr());
+    }
+}
+
+/// Translate a message
+pub fn tr(msgid: &str) -> String {
+    glib::dgettext(Some(DOMAIN), msgid).into()
+}
+
+/// Translate a message with a number, `{}` is replaced with `n`
+pub fn ntr(singular: &str, plural: &str, n: usize) -> String {
+    glib::dngettext(Some(DOMAIN), singular, plural, n as _).replace("{}", &n.to_string())
+}
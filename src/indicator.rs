@@ -0,0 +1,91 @@
+//! The indicator of the libappindicator crate keeps its object private, this
+//! wrapper also exposes the middle click and scroll events
+use crate::errors::*;
+use glib::translate::{from_glib_full, ToGlibPtr};
+use gtk::gdk::ScrollDirection;
+use gtk::prelude::*;
+use libappindicator::{
+    app_indicator_new, app_indicator_set_attention_icon_full, app_indicator_set_icon_full,
+    app_indicator_set_icon_theme_path, app_indicator_set_label, app_indicator_set_menu,
+    app_indicator_set_secondary_activate_target, app_indicator_set_status, app_indicator_set_title,
+    AppIndicatorCategory, AppIndicatorStatus,
+};
+
+pub struct Indicator {
+    object: glib::Object,
+}
+
+impl Indicator {
+    pub fn new(id: &str, icon: &str) -> Indicator {
+        let object = unsafe {
+            let raw = app_indicator_new(
+                id.to_glib_none().0,
+                icon.to_glib_none().0,
+                AppIndicatorCategory::ApplicationStatus as u32,
+            );
+            from_glib_full(raw as *mut glib::gobject_ffi::GObject)
+        };
+        Indicator { object }
+    }
+
+    fn raw(&self) -> *mut libappindicator::_AppIndicator {
+        self.object.as_ptr() as *mut _
+    }
+
+    pub fn set_status(&mut self, status: AppIndicatorStatus) {
+        unsafe { app_indicator_set_status(self.raw(), status as u32) }
+    }
+
+    pub fn set_menu(&mut self, menu: &mut gtk::Menu) {
+        unsafe { app_indicator_set_menu(self.raw(), menu.to_glib_none().0) }
+    }
+
+    pub fn set_label(&mut self, label: &str, guide: &str) {
+        unsafe {
+            app_indicator_set_label(self.raw(), label.to_glib_none().0, guide.to_glib_none().0)
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        unsafe { app_indicator_set_title(self.raw(), title.to_glib_none().0) }
+    }
+
+    pub fn set_icon_theme_path(&mut self, path: &str) {
+        unsafe { app_indicator_set_icon_theme_path(self.raw(), path.to_glib_none().0) }
+    }
+
+    pub fn set_icon_full(&mut self, name: &str, desc: &str) {
+        unsafe {
+            app_indicator_set_icon_full(self.raw(), name.to_glib_none().0, desc.to_glib_none().0)
+        }
+    }
+
+    pub fn set_attention_icon_full(&mut self, name: &str, desc: &str) {
+        unsafe {
+            let (name, desc) = (name.to_glib_none(), desc.to_glib_none());
+            app_indicator_set_attention_icon_full(self.raw(), name.0, desc.0)
+        }
+    }
+
+    /// Activate `item` of the menu on middle click, `None` disables it
+    pub fn set_secondary_activate_target(&mut self, item: Option<&gtk::MenuItem>) {
+        let widget = item.map(|item| item.upcast_ref::<gtk::Widget>());
+        unsafe { app_indicator_set_secondary_activate_target(self.raw(), widget.to_glib_none().0) }
+    }
+
+    /// Called with the direction of every scroll over the icon
+    pub fn connect_scroll<F: Fn(ScrollDirection) + 'static>(&self, f: F) {
+        self.object
+            .connect_local("scroll-event", false, move |values| {
+                // the arguments are the indicator, the delta and the direction
+                match values.get(2).map(|value| value.get::<ScrollDirection>()) {
+                    Some(Ok(direction)) => f(direction),
+                    _ => debug!(
+                        "Ignoring scroll event with unexpected arguments: {:?}",
+                        values
+                    ),
+                }
+                None
+            });
+    }
+}
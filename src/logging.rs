@@ -0,0 +1,216 @@
+use crate::args::Args;
+use crate::config::Config;
+use crate::errors::*;
+use env_logger::{Env, Target, DEFAULT_FILTER_ENV};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Forwards to env_logger once it's set up, messages logged before that
+/// are kept and written afterwards
+///
+/// The config decides where logs go, so warnings while loading it would get
+/// lost otherwise.
+struct Logger {
+    inner: OnceLock<env_logger::Logger>,
+    /// Level, target and message of everything logged before `init`
+    early: Mutex<Vec<(Level, String, String)>>,
+}
+
+static LOGGER: Logger = Logger {
+    inner: OnceLock::new(),
+    early: Mutex::new(Vec::new()),
+};
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner
+            .get()
+            .is_none_or(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        match self.inner.get() {
+            Some(logger) => logger.log(record),
+            None => {
+                if let Ok(mut early) = self.early.lock() {
+                    let msg = record.args().to_string();
+                    early.push((record.level(), record.target().to_string(), msg));
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = self.inner.get() {
+            logger.flush();
+        }
+    }
+}
+
+impl Logger {
+    /// Write the messages of before `init` that pass the configured level
+    fn replay(&self) {
+        let (Some(logger), Ok(mut early)) = (self.inner.get(), self.early.lock()) else {
+            return;
+        };
+        for (level, target, msg) in early.drain(..) {
+            if level <= log::max_level() {
+                let args = format_args!("{}", msg);
+                logger.log(
+                    &Record::builder()
+                        .level(level)
+                        .target(&target)
+                        .args(args)
+                        .build(),
+                );
+            }
+        }
+    }
+}
+
+/// Keep log messages until `init`, called before the config is loaded
+pub fn init_early() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+}
+
+/// Log file that is moved to `<path>.1` once it grows beyond `max_size`
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_size: u64) -> Result<Self> {
+        let file = Self::open_file(path)
+            .with_context(|| anyhow!("Failed to open log file: {:?}", path))?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".1");
+        fs::rename(&self.path, backup)?;
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size >= self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+const LOG_FILE: &str = "arch-audit-gtk/arch-audit-gtk.log";
+
+/// `$XDG_STATE_HOME`, it's not known to dirs-next
+pub fn state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs_next::home_dir().map(|home| home.join(".local/state")))
+}
+
+fn log_file(args: &Args, config: &Config) -> Result<Option<PathBuf>> {
+    if let Some(path) = args.log_file.as_ref().or(config.log_file.as_ref()) {
+        return Ok(Some(path.clone()));
+    }
+    if !config.log_to_file {
+        return Ok(None);
+    }
+    let path = state_dir()
+        .context("Failed to find the state directory")?
+        .join(LOG_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+    }
+    Ok(Some(path))
+}
+
+/// Level of the config, `-v` and `$RUST_LOG` take precedence
+fn configured_level(args: &Args, config: &Config, file: bool) -> LevelFilter {
+    // a log file is only useful with some messages in it
+    let default = if file {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Off
+    };
+    args.log_level().or(config.log_level).unwrap_or(default)
+}
+
+pub fn init(args: &Args, config: &Config) -> Result<()> {
+    let file = log_file(args, config)?;
+    let level = configured_level(args, config, file.is_some());
+    // without $RUST_LOG everything passes the filter, the max level decides
+    // instead, so a reload of the config can change it
+    let mut builder = env_logger::Builder::from_env(Env::default());
+    if env::var_os(DEFAULT_FILTER_ENV).is_none() {
+        builder.filter_level(LevelFilter::Trace);
+    }
+
+    if let Some(path) = &file {
+        let file = RotatingFile::open(path, config.log_max_size)?;
+        builder.target(Target::Pipe(Box::new(file)));
+    }
+
+    init_early();
+    let logger = builder.build();
+    let filter = logger.filter();
+    LOGGER.inner.set(logger).ok();
+    log::set_max_level(filter);
+    if env::var_os(DEFAULT_FILTER_ENV).is_none() {
+        log::set_max_level(level);
+    }
+    info!("Starting arch-audit-gtk {}", env!("CARGO_PKG_VERSION"));
+    if let Some(path) = &file {
+        debug!("Writing logs to {:?}", path);
+    }
+    LOGGER.replay();
+    Ok(())
+}
+
+/// Apply `[log] level` of a reloaded config, the log file stays the same
+pub fn reload(args: &Args, config: &Config) {
+    if env::var_os(DEFAULT_FILTER_ENV).is_some() {
+        return;
+    }
+    let file = args.log_file.is_some() || config.log_file.is_some() || config.log_to_file;
+    let level = configured_level(args, config, file);
+    if level != log::max_level() {
+        log::set_max_level(level);
+        info!("Log level changed to {}", level);
+    }
+}
@@ -1,32 +1,109 @@
+mod about;
 mod args;
+mod autostart;
+#[cfg(feature = "native")]
+mod avg;
+mod backend;
 mod config;
+#[cfg(feature = "native")]
+mod cve;
+mod daemon;
+mod dbus;
+mod details;
 mod errors;
+#[cfg(feature = "gsettings")]
+mod gsettings;
 mod gui;
+mod history;
+mod hooks;
+#[cfg(feature = "http")]
+mod http;
+mod i18n;
+mod indicator;
+mod logging;
+mod metrics;
+mod motd;
+mod notifications;
 mod notify;
+mod oneshot;
+mod output;
+mod pacman;
+mod pkexec;
+mod preferences;
+mod reboot;
+mod report;
+mod selftest;
+mod socket;
+mod sound;
+mod state;
+mod stats;
+mod systemd;
 mod updater;
+mod upgrade;
 
-use crate::args::Args;
+use crate::args::{Args, SubCommand};
 use crate::config::Config;
 use crate::errors::*;
-use env_logger::Env;
+use std::process;
 use structopt::StructOpt;
 
 fn main() -> Result<()> {
     let args = Args::from_args();
+    logging::init_early();
 
-    env_logger::init_from_env(Env::default()
-        .default_filter_or(args.log_level()));
+    // this shouldn't depend on a working config
+    if let Some(path) = &args.generate_config {
+        return config::generate(path.as_deref(), args.force);
+    }
+    if args.print_hook {
+        return notify::print_hook();
+    }
+    if args.install_service {
+        return systemd::install_service(args.force);
+    }
+    if let Some(SubCommand::CheckConfig) = &args.subcommand {
+        return config::check(&args);
+    }
+    // read from the cache, so it doesn't wait for the config or a check
+    if let Some(SubCommand::Status) = &args.subcommand {
+        return motd::print();
+    }
+
+    // the config may point to a log file, so it's loaded first and its
+    // warnings are logged once it's known where to
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(err) => match err.downcast_ref::<config::ParseError>() {
+            Some(err) => {
+                eprintln!("Error: Invalid config file: {}", err);
+                process::exit(config::EXIT_CONFIG);
+            }
+            None => return Err(err.context("Failed to load config")),
+        },
+    };
 
-    let config = Config::load(&args)
-        .context("Failed to load config")?;
+    logging::init(&args, &config)?;
+    i18n::init();
 
-    if args.pacman_notify {
+    if let Some(SubCommand::Check(check)) = &args.subcommand {
+        oneshot::check(&config, check)
+    } else if let Some(SubCommand::CheckTheme(check)) = &args.subcommand {
+        gui::check_theme(&config, &check.theme)
+    } else if let Some(SubCommand::SelfTest) = &args.subcommand {
+        selftest::run(&args, &config)
+    } else if args.pacman_notify {
         notify::pacman_notify()
     } else if args.debug_inotify {
-        notify::debug_inotify()
+        notify::debug_inotify(&config)
+    } else if args.short {
+        oneshot::short(&config)
+    } else if args.daemon {
+        daemon::run(&config)
     } else if let Some(icon) = &args.debug_icon {
         gui::debug_icon(&config, icon)
+    } else if args.debug_tour {
+        gui::main(&config.for_debug_tour(), args)
     } else {
-        gui::main(&config)
+        gui::main(&config, args)
     }
 }
@@ -0,0 +1,132 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::updater::{severity_key, CheckResult, Status};
+use arch_audit::types::Severity;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+const SEVERITIES: &[Severity] = &[
+    Severity::Critical,
+    Severity::High,
+    Severity::Medium,
+    Severity::Low,
+    Severity::Unknown,
+];
+
+/// Writes a file for the textfile collector of node_exporter after every check
+#[derive(Debug)]
+pub struct Metrics {
+    path: PathBuf,
+    /// Failed checks since the start
+    errors: u64,
+    /// Vulnerable packages per severity of the last successful check, they're
+    /// kept while checks fail
+    packages: Option<BTreeMap<&'static str, usize>>,
+}
+
+impl Metrics {
+    pub fn new(config: &Config) -> Option<Self> {
+        let path = config.metrics_textfile.clone()?;
+        Some(Metrics {
+            path,
+            errors: 0,
+            packages: None,
+        })
+    }
+
+    pub fn record(&mut self, result: &CheckResult) {
+        match &result.status {
+            Status::MissingUpdates(updates) => {
+                // a package counts once, with its most severe advisory
+                let mut highest = BTreeMap::<&str, Severity>::new();
+                for update in updates {
+                    let severity = highest.entry(&update.pkg).or_insert(update.severity);
+                    *severity = (*severity).max(update.severity);
+                }
+                let mut packages = SEVERITIES
+                    .iter()
+                    .map(|s| (severity_key(*s), 0))
+                    .collect::<BTreeMap<_, _>>();
+                for severity in highest.values() {
+                    *packages.entry(severity_key(*severity)).or_default() += 1;
+                }
+                self.packages = Some(packages);
+            }
+            Status::Error(_) => self.errors += 1,
+            // the check didn't run
+            Status::Offline => return,
+        }
+        if let Err(err) = self.write(result) {
+            warn!("Failed to write metrics: {:#}", err);
+        }
+    }
+
+    fn render(&self, result: &CheckResult) -> String {
+        let mut out = String::new();
+        if let Some(packages) = &self.packages {
+            header(
+                &mut out,
+                "arch_audit_vulnerable_packages",
+                "gauge",
+                "Installed packages with missing security updates, by their highest severity",
+            );
+            for (severity, count) in packages {
+                writeln!(
+                    out,
+                    "arch_audit_vulnerable_packages{{severity={:?}}} {}",
+                    severity, count
+                )
+                .ok();
+            }
+        }
+        let timestamp = result
+            .checked_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let success = matches!(result.status, Status::MissingUpdates(_)) as u64;
+        for (name, kind, help, value) in [
+            (
+                "arch_audit_last_check_timestamp",
+                "gauge",
+                "Unix time of the last check",
+                timestamp,
+            ),
+            (
+                "arch_audit_last_check_success",
+                "gauge",
+                "If the last check succeeded",
+                success,
+            ),
+            (
+                "arch_audit_check_errors_total",
+                "counter",
+                "Failed checks since arch-audit-gtk started",
+                self.errors,
+            ),
+        ] {
+            header(&mut out, name, kind, help);
+            writeln!(out, "{} {}", name, value).ok();
+        }
+        out
+    }
+
+    /// Replace the file atomically, the collector may read it at any time
+    fn write(&self, result: &CheckResult) -> Result<()> {
+        debug!("Writing metrics to {:?}", self.path);
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, self.render(result))
+            .with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| anyhow!("Failed to move metrics to {:?}", self.path))
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).ok();
+    writeln!(out, "# TYPE {} {}", name, kind).ok();
+}
@@ -0,0 +1,109 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::i18n::{ntr, tr};
+use crate::logging::state_dir;
+use crate::updater::{severity_name, CheckResult, Status};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const MOTD_FILE: &str = "arch-audit-gtk/motd";
+/// Packages named after the worst severity, the rest is only counted
+const MAX_NAMED: usize = 3;
+
+fn path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join(MOTD_FILE))
+}
+
+/// One line, eg. `3 vulnerable packages, worst: critical (openssl)`.
+/// Offline results say nothing new, the last summary is kept.
+fn summary(result: &CheckResult) -> Option<String> {
+    let updates = match &result.status {
+        Status::MissingUpdates(updates) => updates,
+        Status::Error(err) => {
+            return Some(tr("Checking for security updates failed: {}").replace("{}", &err.message))
+        }
+        Status::Offline => return None,
+    };
+    let updates = updates
+        .iter()
+        .filter(|u| !u.acknowledged)
+        .collect::<Vec<_>>();
+    let Some(worst) = updates.iter().map(|u| u.severity).max() else {
+        return Some(tr("No missing security updates"));
+    };
+    let packages = updates
+        .iter()
+        .map(|u| u.pkg.as_str())
+        .collect::<BTreeSet<_>>();
+    let worst_packages = updates
+        .iter()
+        .filter(|u| u.severity == worst)
+        .map(|u| u.pkg.as_str())
+        .collect::<BTreeSet<_>>();
+    let mut names = worst_packages
+        .iter()
+        .take(MAX_NAMED)
+        .copied()
+        .collect::<Vec<_>>();
+    if worst_packages.len() > MAX_NAMED {
+        names.push("...");
+    }
+    Some(format!(
+        "{}, {} ({})",
+        ntr(
+            "{} vulnerable package",
+            "{} vulnerable packages",
+            packages.len()
+        ),
+        tr("worst: {}").replace("{}", &severity_name(worst)),
+        names.join(", "),
+    ))
+}
+
+/// Replace the summary file after a check, so shell prompts and motd
+/// scripts can show it without running arch-audit
+pub fn write(config: &Config, result: &CheckResult) {
+    if !config.motd_enabled {
+        return;
+    }
+    let Some(text) = summary(result) else {
+        return;
+    };
+    let Some(path) = path() else {
+        return;
+    };
+    let written = (|| -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+        }
+        // replace the file atomically, readers never see a partial line
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, text + "\n").with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &path).with_context(|| anyhow!("Failed to move summary to {:?}", path))
+    })();
+    if let Err(err) = written {
+        warn!("Failed to write the status summary: {:#}", err);
+    }
+}
+
+/// Print the summary of the last check, without checking or loading the
+/// config, so it's fast enough for every new shell
+pub fn print() -> Result<()> {
+    let path = path().context("Failed to find the state directory")?;
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            print!("{}", text);
+            Ok(())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            bail!(
+                "No status in {:?} yet, it's written after every check",
+                path
+            )
+        }
+        Err(err) => Err(err).with_context(|| anyhow!("Failed to read {:?}", path)),
+    }
+}
@@ -0,0 +1,586 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::i18n::{ntr, tr};
+use crate::updater::{severity_name, CheckResult, Status, Update};
+use arch_audit::types::Severity;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::str::FromStr;
+
+const BUS_NAME: &str = "org.freedesktop.Notifications";
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+const ICON: &str = "dialog-warning";
+/// Number of package names listed in a summarized notification
+const SUMMARY_PACKAGES: usize = 3;
+/// Keys of the notification actions, `default` is clicking the body
+const ACTION_DEFAULT: &str = "default";
+const ACTION_DETAILS: &str = "details";
+const ACTION_CHECK: &str = "check";
+/// How long the self-test waits for the notification server, it's started
+/// through D-Bus activation if it isn't running
+const SERVER_INFORMATION_TIMEOUT_MS: i32 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low = 0,
+    Normal = 1,
+    Critical = 2,
+}
+
+impl Urgency {
+    pub fn default_for(severity: Severity) -> Self {
+        match severity {
+            Severity::Critical => Urgency::Critical,
+            Severity::High | Severity::Medium => Urgency::Normal,
+            Severity::Low | Severity::Unknown => Urgency::Low,
+        }
+    }
+}
+
+/// A daily window without notifications and attention, eg. `22:00-08:00`.
+/// The end is excluded and windows that end before they start wrap around
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    /// Minutes since midnight
+    start: u32,
+    end: u32,
+}
+
+impl QuietHours {
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Result<u32> {
+    let (hours, minutes) = s
+        .trim()
+        .split_once(':')
+        .with_context(|| anyhow!("Expected HH:MM, found {:?}", s))?;
+    let hours = hours.parse::<u32>().ok().filter(|h| *h < 24);
+    let minutes = minutes.parse::<u32>().ok().filter(|m| *m < 60);
+    match (hours, minutes) {
+        (Some(hours), Some(minutes)) => Ok(hours * 60 + minutes),
+        _ => bail!("Invalid time: {:?}", s),
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| anyhow!("Expected a range like 22:00-08:00, found {:?}", s))?;
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            bail!("Empty range: {:?}", s);
+        }
+        Ok(QuietHours { start, end })
+    }
+}
+
+/// If the local time is in one of the configured quiet hours
+pub fn is_quiet(config: &Config) -> bool {
+    if config.quiet_hours.is_empty() {
+        return false;
+    }
+    let Ok(now) = glib::DateTime::now_local() else {
+        return false;
+    };
+    let minute = (now.hour() * 60 + now.minute()) as u32;
+    config
+        .quiet_hours
+        .iter()
+        .any(|quiet| quiet.contains(minute))
+}
+
+/// Chosen in a notification about new advisories
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Open the link of the advisory
+    Open(String),
+    /// Show the details window
+    Details,
+    /// Check for updates again
+    Check,
+}
+
+/// Sends a desktop notification when advisories show up that weren't part
+/// of the previous result
+///
+/// Like the `[on_severity]` commands, advisories that show up while snoozed
+/// don't notify. During quiet hours nothing is sent at all.
+#[derive(Default)]
+pub struct Notifier {
+    /// Unset until the first notification, or if the session bus failed
+    connection: Option<gio::DBusConnection>,
+    failed: bool,
+    /// Without a handler notifications don't have any actions
+    on_action: Option<Rc<dyn Fn(Action)>>,
+    /// Advisory of every open notification, by its id. Unset for summaries,
+    /// clicking them opens the details instead.
+    links: Rc<RefCell<HashMap<u32, Option<String>>>>,
+}
+
+impl Notifier {
+    pub fn handle(&mut self, config: &Config, result: &CheckResult, snoozed: bool) {
+        let Status::MissingUpdates(updates) = &result.status else {
+            return;
+        };
+
+        let new = notified(config, updates);
+
+        if !config.notifications_enabled || snoozed || new.is_empty() {
+            return;
+        }
+        if is_quiet(config) {
+            debug!(
+                "Not notifying about {} new advisories during quiet hours",
+                new.len()
+            );
+            return;
+        }
+        if config.notifications_per_advisory {
+            for update in new {
+                let summary = format!("{}: {}", update.avg, update.pkg);
+                let body = format!("{} ({})", update.kind, severity_name(update.severity));
+                let link = update.advisory_link();
+                self.send(config, update.severity, &summary, &body, true, Some(&link));
+            }
+        } else {
+            // updates are sorted, the first one has the highest severity
+            let severity = new[0].severity;
+            let (summary, body) = summary(&new);
+            self.send(config, severity, &summary, &body, true, None);
+        }
+    }
+
+    /// Offer actions in notifications about advisories, `f` is called on the
+    /// main loop once one is chosen
+    pub fn on_action(&mut self, f: impl Fn(Action) + 'static) {
+        self.on_action = Some(Rc::new(f));
+        // otherwise this happens once the connection is opened
+        if let Some(connection) = &self.connection {
+            self.subscribe(connection);
+        }
+    }
+
+    /// Notify about a problem that isn't tied to a check, eg. an outdated
+    /// arch-audit
+    pub fn warning(&mut self, config: &Config, msg: &str) {
+        if config.notifications_enabled && !is_quiet(config) {
+            self.send(
+                config,
+                Severity::Unknown,
+                "arch-audit-gtk",
+                msg,
+                false,
+                None,
+            );
+        }
+    }
+
+    /// Notifications about advisories have `actions`, clicking them opens
+    /// `link` or the details window without one
+    fn send(
+        &mut self,
+        config: &Config,
+        severity: Severity,
+        summary: &str,
+        body: &str,
+        actions: bool,
+        link: Option<&str>,
+    ) {
+        let Some(connection) = self.connection() else {
+            return;
+        };
+        let connection = connection.clone();
+        let actions = actions && self.on_action.is_some();
+        // alternating keys and labels
+        let keys = match actions {
+            true => vec![
+                ACTION_DEFAULT.to_string(),
+                tr("Open advisory"),
+                ACTION_DETAILS.to_string(),
+                tr("Open details"),
+                ACTION_CHECK.to_string(),
+                tr("Check again"),
+            ],
+            false => Vec::new(),
+        };
+
+        let urgency = config
+            .notifications_urgency
+            .get(&severity)
+            .copied()
+            .unwrap_or_else(|| Urgency::default_for(severity));
+        let timeout = match (urgency, config.notifications_critical_persistent) {
+            // 0 means the notification never expires
+            (Urgency::Critical, true) => 0,
+            _ => match config.notifications_timeout_ms {
+                // -1 lets the notification server decide
+                0 => -1,
+                ms => i32::try_from(ms).unwrap_or(i32::MAX),
+            },
+        };
+        let hints = HashMap::from([("urgency".to_string(), glib::Variant::from(urgency as u8))]);
+        let params = glib::Variant::tuple_from_iter([
+            glib::Variant::from("arch-audit-gtk"),
+            glib::Variant::from(0u32),
+            glib::Variant::from(ICON),
+            glib::Variant::from(summary),
+            glib::Variant::from(body),
+            glib::Variant::from(keys),
+            glib::Variant::from(hints),
+            glib::Variant::from(timeout),
+        ]);
+
+        debug!("Sending notification: {:?}", summary);
+        let links = self.links.clone();
+        let link = actions.then(|| link.map(String::from));
+        connection.call(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            BUS_NAME,
+            "Notify",
+            Some(&params),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+            move |result| match result {
+                Ok(reply) => {
+                    if let (Some((id,)), Some(link)) = (reply.get::<(u32,)>(), link) {
+                        links.borrow_mut().insert(id, link);
+                    }
+                }
+                Err(err) => warn!("Failed to send notification: {:#}", err),
+            },
+        );
+    }
+
+    /// Call the action handler when an action of one of our notifications
+    /// is invoked
+    fn subscribe(&self, connection: &gio::DBusConnection) {
+        let Some(on_action) = self.on_action.clone() else {
+            return;
+        };
+        let links = self.links.clone();
+        connection.signal_subscribe(
+            Some(BUS_NAME),
+            Some(BUS_NAME),
+            Some("ActionInvoked"),
+            Some(OBJECT_PATH),
+            None,
+            gio::DBusSignalFlags::NONE,
+            move |_, _, _, _, _, params| {
+                let Some((id, key)) = params.get::<(u32, String)>() else {
+                    return;
+                };
+                // notifications of other applications
+                let Some(link) = links.borrow().get(&id).cloned() else {
+                    return;
+                };
+                debug!("Notification action {:?} invoked", key);
+                match (key.as_str(), link) {
+                    (ACTION_DEFAULT, Some(link)) => on_action(Action::Open(link)),
+                    (ACTION_DEFAULT | ACTION_DETAILS, _) => on_action(Action::Details),
+                    (ACTION_CHECK, _) => on_action(Action::Check),
+                    _ => (),
+                }
+            },
+        );
+        let links = self.links.clone();
+        connection.signal_subscribe(
+            Some(BUS_NAME),
+            Some(BUS_NAME),
+            Some("NotificationClosed"),
+            Some(OBJECT_PATH),
+            None,
+            gio::DBusSignalFlags::NONE,
+            move |_, _, _, _, _, params| {
+                if let Some((id, _)) = params.get::<(u32, u32)>() {
+                    links.borrow_mut().remove(&id);
+                }
+            },
+        );
+    }
+
+    fn connection(&mut self) -> Option<&gio::DBusConnection> {
+        if self.connection.is_none() && !self.failed {
+            match gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>) {
+                Ok(connection) => {
+                    self.subscribe(&connection);
+                    self.connection = Some(connection);
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to connect to the session bus, disabling notifications: {:#}",
+                        err
+                    );
+                    self.failed = true;
+                }
+            }
+        }
+        self.connection.as_ref()
+    }
+}
+
+/// Name, vendor and version of the running notification server, fails if
+/// there's none or it doesn't answer
+pub fn server_information() -> Result<String> {
+    let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)
+        .context("Failed to connect to the session bus")?;
+    let reply = connection
+        .call_sync(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            BUS_NAME,
+            "GetServerInformation",
+            None,
+            glib::VariantTy::new("(ssss)").ok(),
+            gio::DBusCallFlags::NONE,
+            SERVER_INFORMATION_TIMEOUT_MS,
+            None::<&gio::Cancellable>,
+        )
+        .context("No notification server answered")?;
+    let (name, vendor, version, _) = reply
+        .get::<(String, String, String, String)>()
+        .context("Invalid reply of the notification server")?;
+    Ok(format!("{} {} by {}", name, version, vendor))
+}
+
+/// New advisories that are worth a notification
+fn notified<'a>(config: &Config, updates: &'a [Update]) -> Vec<&'a Update> {
+    updates
+        .iter()
+        .filter(|update| update.new && update.alerts(config))
+        .collect()
+}
+
+/// Links of the new advisories that `[notifications] auto_open` opens right
+/// away, held back like notifications while snoozed and during quiet hours
+pub fn auto_open_links<'a>(
+    config: &Config,
+    result: &'a CheckResult,
+    snoozed: bool,
+) -> Vec<Cow<'a, str>> {
+    let Some(threshold) = config.notifications_auto_open else {
+        return Vec::new();
+    };
+    let Status::MissingUpdates(updates) = &result.status else {
+        return Vec::new();
+    };
+    if snoozed || is_quiet(config) {
+        return Vec::new();
+    }
+    let mut links = Vec::new();
+    for update in notified(config, updates) {
+        let link = update.advisory_link();
+        if update.severity >= threshold && !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Summary and body of a single notification for multiple new advisories,
+/// eg. `3 new security advisories` and `1 high, 2 medium: openssl, curl, …`
+///
+/// Both count advisory groups, an advisory affecting several packages is
+/// counted once, with its highest severity.
+///
+/// The body is kept on a single line since some notification servers render
+/// newlines poorly.
+pub fn summary(updates: &[&Update]) -> (String, String) {
+    let mut avgs = HashMap::<&str, Severity>::new();
+    for update in updates {
+        let severity = avgs.entry(update.avg.as_str()).or_insert(update.severity);
+        *severity = (*severity).max(update.severity);
+    }
+    let summary = ntr(
+        "{} new security advisory",
+        "{} new security advisories",
+        avgs.len(),
+    );
+
+    let mut severities = BTreeMap::<Severity, usize>::new();
+    for severity in avgs.values() {
+        *severities.entry(*severity).or_default() += 1;
+    }
+    let severities = severities
+        .iter()
+        .rev()
+        .map(|(severity, count)| format!("{} {}", count, severity_name(*severity)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut packages = Vec::new();
+    for update in updates {
+        if !packages.contains(&update.pkg.as_str()) {
+            packages.push(update.pkg.as_str());
+        }
+    }
+    let mut names = packages
+        .iter()
+        .take(SUMMARY_PACKAGES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if packages.len() > SUMMARY_PACKAGES {
+        names.push_str(", …");
+    }
+
+    (summary, format!("{}: {}", severities, names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock_update;
+
+    fn new_update(i: usize, severity: Severity) -> Update {
+        let mut update = mock_update(i, severity, true);
+        update.new = true;
+        update
+    }
+
+    #[test]
+    fn quiet_hours() {
+        let minute = |s: &str| parse_time(s).unwrap();
+        for (range, quiet, loud) in [
+            (
+                "08:00-17:00",
+                &["08:00", "12:30", "16:59"][..],
+                &["07:59", "17:00", "00:00"][..],
+            ),
+            // wraps around midnight, the end itself is no longer quiet
+            (
+                "22:00-08:00",
+                &["22:00", "23:59", "00:00", "07:59"],
+                &["08:00", "12:00", "21:59"],
+            ),
+            ("23:30-00:00", &["23:30", "23:59"], &["00:00", "23:29"]),
+            ("00:00-00:01", &["00:00"], &["00:01", "23:59"]),
+        ] {
+            let hours = range.parse::<QuietHours>().unwrap();
+            for time in quiet {
+                assert!(hours.contains(minute(time)), "{} in {}", time, range);
+            }
+            for time in loud {
+                assert!(!hours.contains(minute(time)), "{} in {}", time, range);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_quiet_hours() {
+        for range in [
+            "22:00-22:00",
+            "00:00-00:00",
+            "22:00-24:00",
+            "24:00-08:00",
+            "22:60-08:00",
+            "22:00",
+            "22-08",
+            "",
+            "-",
+            "aa:bb-cc:dd",
+        ] {
+            assert!(range.parse::<QuietHours>().is_err(), "{:?}", range);
+        }
+    }
+
+    #[test]
+    fn parse_times() {
+        for (time, minute) in [
+            ("00:00", 0),
+            ("08:05", 485),
+            (" 23:59 ", 1439),
+            ("7:30", 450),
+        ] {
+            assert_eq!(parse_time(time).unwrap(), minute, "{:?}", time);
+        }
+    }
+
+    #[test]
+    fn min_severity_applies_to_notifications() {
+        let config = Config::for_test("[alerts]\nmin_severity = \"high\"\n");
+        let updates = [
+            new_update(0, Severity::Critical),
+            new_update(1, Severity::High),
+            new_update(2, Severity::Medium),
+            new_update(3, Severity::Low),
+        ];
+        let notified = notified(&config, &updates)
+            .iter()
+            .map(|update| update.severity)
+            .collect::<Vec<_>>();
+        assert_eq!(notified, [Severity::Critical, Severity::High]);
+    }
+
+    #[test]
+    fn only_new_advisories_notify() {
+        let config = Config::for_test("");
+        let mut updates = [new_update(0, Severity::High), new_update(1, Severity::High)];
+        updates[1].new = false;
+        let notified = notified(&config, &updates);
+        assert_eq!(notified.len(), 1);
+        assert_eq!(notified[0].pkg, updates[0].pkg);
+    }
+
+    #[test]
+    fn critical_only_notifies_about_critical_advisories() {
+        let config = Config::for_test("[alerts]\ncritical_only = true\n");
+        let updates = [
+            new_update(0, Severity::Critical),
+            new_update(1, Severity::High),
+        ];
+        assert_eq!(notified(&config, &updates).len(), 1);
+    }
+
+    #[test]
+    fn summary_counts_advisories() {
+        let updates = [
+            new_update(0, Severity::High),
+            new_update(1, Severity::Medium),
+            new_update(2, Severity::Medium),
+        ];
+        let (summary, body) = summary(&updates.iter().collect::<Vec<_>>());
+        assert_eq!(summary, "3 new security advisories");
+        assert_eq!(body, "1 high, 2 medium: openssl, curl, glibc");
+    }
+
+    #[test]
+    fn summary_counts_an_advisory_of_several_packages_once() {
+        let mut updates = [
+            new_update(0, Severity::High),
+            new_update(1, Severity::High),
+            new_update(2, Severity::Low),
+        ];
+        updates[1].avg = updates[0].avg.clone();
+        let (summary, body) = summary(&updates.iter().collect::<Vec<_>>());
+        assert_eq!(summary, "2 new security advisories");
+        assert_eq!(body, "1 high, 1 low: openssl, curl, glibc");
+    }
+
+    #[test]
+    fn summary_truncates_the_packages() {
+        let updates = (0..SUMMARY_PACKAGES + 2)
+            .map(|i| new_update(i, Severity::Critical))
+            .collect::<Vec<_>>();
+        let (_, body) = summary(&updates.iter().collect::<Vec<_>>());
+        assert!(body.ends_with(", …"), "{}", body);
+        assert_eq!(body.matches(", ").count(), SUMMARY_PACKAGES);
+    }
+}
@@ -1,59 +1,259 @@
+use crate::config::{self, Config};
 use crate::errors::*;
+use crate::pacman;
+use crate::updater::CancelToken;
 use inotify::{Inotify, WatchMask};
-use std::fs::File;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
+
+/// Directory the pacman hook touches a file in after every transaction
+pub const NOTIFY_DIR: &str = "/run/arch-audit-gtk";
+/// Written to `$XDG_RUNTIME_DIR` by every instance that checks on SIGUSR1
+const PID_FILE: &str = "arch-audit-gtk.pid";
+/// Runtime directories of all logged in users
+const RUNTIME_DIRS: &str = "/run/user";
+const HOOK: &str = include_str!("../contrib/arch-audit-gtk.hook");
+
+// how often the inotify thread checks if it should shut down
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// give up on inotify after this many consecutive read errors
+const MAX_READ_FAILURES: u32 = 5;
+const READ_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub enum Event {
     Click,
     Inotify,
+    /// The inotify thread gave up, pacman transactions aren't noticed anymore
+    InotifyFailed(String),
+    /// A config file was written, the gui loads it again
+    ConfigChanged,
+    /// NetworkManager went online or offline
+    Network(bool),
+    /// Settings were changed in the preferences, checks again with them
+    Reload(Box<Config>),
+    /// Sent on quit so the updater thread leaves its loop
+    Shutdown,
+}
+
+/// Tells the running instances that pacman is done, so they check right away
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create() -> Option<Self> {
+        let path = dirs_next::runtime_dir()?.join(PID_FILE);
+        match fs::write(&path, format!("{}\n", process::id())) {
+            Ok(()) => {
+                debug!("Wrote pid file {:?}", path);
+                Some(PidFile { path })
+            }
+            Err(err) => {
+                warn!("Failed to write pid file {:?}: {:#}", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn cleanup(&self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("Failed to remove pid file {:?}: {:#}", self.path, err);
+        }
+    }
+}
+
+/// Send SIGUSR1 to the instances of all users. Only instances that wrote a
+/// pid file are signaled, older releases would be killed by the signal.
+fn signal_instances() {
+    let Ok(dirs) = fs::read_dir(RUNTIME_DIRS) else {
+        return;
+    };
+    for dir in dirs.filter_map(|entry| entry.ok()) {
+        let path = dir.path().join(PID_FILE);
+        let Some(pid) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|pid| pid.trim().parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+        // the pid may belong to another process after a crash
+        let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+        if comm.trim_end() != "arch-audit-gtk" {
+            debug!("Ignoring stale pid file {:?}", path);
+            continue;
+        }
+        debug!("Sending SIGUSR1 to {}", pid);
+        if unsafe { libc::kill(pid, libc::SIGUSR1) } != 0 {
+            warn!("Failed to signal {}: {}", pid, io::Error::last_os_error());
+        }
+    }
 }
 
 pub fn pacman_notify() -> Result<()> {
-    let path = "/run/arch-audit-gtk/notify";
-    File::create(path)
-        .with_context(|| anyhow!("Failed to touch file: {:?}", path))?;
+    signal_instances();
+    // instances that run without a pid file still notice this
+    let path = Path::new(NOTIFY_DIR).join("notify");
+    File::create(&path).with_context(|| anyhow!("Failed to touch file: {:?}", path))?;
     Ok(())
 }
 
-pub fn setup_inotify_thread(tx: mpsc::Sender<Event>) -> Result<()> {
-    let mut inotify = Inotify::init()
-        .context("Failed to init inotify")?;
-
-    // Watch for modify and close events.
-    let result = inotify
-        .add_watch(
-            "/run/arch-audit-gtk",
-            WatchMask::CLOSE_WRITE,
-        );
-
-    if let Err(err) = result {
-        warn!("Failed to add file watch: {:#}", err);
-    } else {
-        thread::spawn(move || {
-            // Read events that were added with `add_watch` above.
-            let mut buffer = [0; 1024];
-
-            loop {
-                let events = inotify.read_events_blocking(&mut buffer)
-                    .expect("Error while reading events");
-
-                // we don't need to send multiple signals, one is enough
-                debug!("Received events: {:?}", events.collect::<Vec<_>>());
-                if tx.send(Event::Inotify).is_err() {
-                    break;
-                }
+/// The pacman hook of the package, for installs without it. The hook of the
+/// package depends on it, so that's dropped here.
+pub fn print_hook() -> Result<()> {
+    let exe = env::current_exe().context("Failed to find the arch-audit-gtk binary")?;
+    let exe = exe
+        .to_str()
+        .context("Path of the arch-audit-gtk binary isn't utf-8")?;
+    for line in HOOK.lines() {
+        if line == "Depends = arch-audit-gtk" {
+            continue;
+        }
+        println!("{}", line.replace("/usr/bin/arch-audit-gtk", exe));
+    }
+    Ok(())
+}
+
+/// The directory of the pacman hook, the local database as configured in
+/// pacman.conf and any extra paths from the config
+fn watch_paths(config: &Config) -> Vec<(PathBuf, WatchMask)> {
+    // pacman hooks only fire for the local system, remote hosts are polled
+    if config.ssh_target.is_some() {
+        return Vec::new();
+    }
+    let changed = WatchMask::CLOSE_WRITE | WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVE;
+    let mut paths = vec![
+        (PathBuf::from(NOTIFY_DIR), WatchMask::CLOSE_WRITE),
+        // the directory of every installed package is in here
+        (
+            Path::new(&pacman::dbpath()).join("local"),
+            WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVE,
+        ),
+    ];
+    paths.extend(
+        config
+            .watch_paths
+            .iter()
+            .map(|path| (path.clone(), changed)),
+    );
+    paths
+}
+
+/// Watch for pacman transactions and changes of the config files. Config
+/// changes are sent to `config_tx` if set, or to `tx` otherwise.
+pub fn setup_inotify_thread(
+    tx: mpsc::Sender<Event>,
+    config_tx: Option<glib::Sender<Event>>,
+    cancel: CancelToken,
+    config: &Config,
+) -> Result<Option<thread::JoinHandle<()>>> {
+    let mut inotify = Inotify::init().context("Failed to init inotify")?;
+
+    let mut watching = 0;
+    for (path, mask) in watch_paths(config) {
+        match inotify.watches().add(&path, mask) {
+            Ok(_) => {
+                debug!("Watching {:?}", path);
+                watching += 1;
             }
-        });
+            Err(err) => warn!("Failed to add file watch for {:?}: {:#}", path, err),
+        }
     }
 
-    Ok(())
+    // a config written after the start is only noticed if its directory is
+    // watched, so the one in the home directory is created up front
+    if let Some(dir) = config::user_dir() {
+        if let Err(err) = fs::create_dir_all(&dir) {
+            debug!("Failed to create config directory {:?}: {:#}", dir, err);
+        }
+    }
+    // editors often replace the file, so the directory is watched instead
+    let mut config_watches = Vec::new();
+    for path in config::paths() {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE;
+        match inotify.watches().add(dir, mask) {
+            Ok(wd) => {
+                debug!("Watching {:?} for config changes", dir);
+                config_watches.push((wd, name.to_owned()));
+            }
+            // the system directories usually don't exist without a config
+            Err(err) => debug!("Not watching {:?} for config changes: {:#}", dir, err),
+        }
+    }
+    if watching == 0 && config_watches.is_empty() {
+        return Ok(None);
+    }
+
+    let handle = thread::spawn(move || {
+        // Read events that were added with `add` above.
+        let mut buffer = [0; 1024];
+
+        // the inotify fd is non-blocking, so we can check for shutdown in between
+        let mut failures = 0;
+        while !cancel.is_cancelled() {
+            let events = match inotify.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(SHUTDOWN_POLL_INTERVAL);
+                    continue;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    failures += 1;
+                    if failures >= MAX_READ_FAILURES {
+                        warn!("Giving up on inotify after {} errors: {:#}", failures, err);
+                        let msg = format!("Pacman transactions aren't detected: {}", err);
+                        tx.send(Event::InotifyFailed(msg)).ok();
+                        break;
+                    }
+                    warn!("Error while reading inotify events, retrying: {:#}", err);
+                    thread::sleep(READ_RETRY_DELAY);
+                    continue;
+                }
+            };
+            failures = 0;
+
+            let mut config_changed = false;
+            let mut pacman_changed = false;
+            for event in events {
+                debug!("Received event: {:?}", event);
+                match config_watches.iter().find(|(wd, _)| *wd == event.wd) {
+                    Some((_, name)) => config_changed |= event.name == Some(name.as_os_str()),
+                    None => pacman_changed = true,
+                }
+            }
+
+            if config_changed {
+                info!("Config file has changed");
+                match &config_tx {
+                    Some(config_tx) => config_tx.send(Event::ConfigChanged).ok(),
+                    None => tx.send(Event::ConfigChanged).ok(),
+                };
+            }
+            // we don't need to send multiple signals, one is enough, the
+            // updater also coalesces events of the same transaction
+            if pacman_changed && tx.send(Event::Inotify).is_err() {
+                break;
+            }
+        }
+        debug!("Inotify thread has shut down");
+    });
+
+    Ok(Some(handle))
 }
 
-pub fn debug_inotify() -> Result<()> {
+pub fn debug_inotify(config: &Config) -> Result<()> {
     let (tx, rx) = mpsc::channel();
-    setup_inotify_thread(tx)?;
+    setup_inotify_thread(tx, None, CancelToken::default(), config)?;
 
     for event in rx {
         println!("{:?}", event);
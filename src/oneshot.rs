@@ -0,0 +1,87 @@
+use crate::args::Check;
+use crate::backend;
+use crate::config::Config;
+use crate::errors::*;
+use crate::report;
+use crate::updater::{self, CancelToken, CheckError, CheckResult, Status};
+use std::process;
+use std::time::Instant;
+
+pub const EXIT_CLEAN: i32 = 0;
+pub const EXIT_VULNERABLE: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+fn check_once(config: &Config) -> CheckResult {
+    let started = Instant::now();
+    let status = backend::for_config(config)
+        .check(&CancelToken::default())
+        .unwrap_or_else(|err| Status::Error(CheckError::new(&err)));
+    let mut result = CheckResult::new(status, started.elapsed(), updater::backend_name(config));
+    if config.pending_updates && matches!(result.status, Status::MissingUpdates(_)) {
+        result.pending = updater::pending_updates(config, &CancelToken::default())
+            .map_err(|err| warn!("Failed to count pending updates: {:#}", err))
+            .ok();
+    }
+    result.reboot_required = updater::reboot_required(config);
+    result.remotes = updater::check_remotes(config, &CancelToken::default());
+    // cron jobs keep the summary up to date without a running tray icon
+    crate::motd::write(config, &result);
+    result
+}
+
+/// Print a single compact token for shell prompts and status bars
+pub fn short(config: &Config) -> Result<()> {
+    let result = check_once(config);
+    let (token, code) = token(config, &result);
+    println!("{}", token);
+    process::exit(code);
+}
+
+/// Check once and print the result as json, a report or a summary with one
+/// line per advisory, for cron jobs and scripts
+pub fn check(config: &Config, args: &Check) -> Result<()> {
+    let result = check_once(config);
+    let (_, code) = token(config, &result);
+    if args.json {
+        println!("{}", result.to_json()?);
+    } else if let Some(format) = args.report {
+        let updates = match &result.status {
+            Status::MissingUpdates(updates) => updates.as_slice(),
+            // failed checks have no advisories to report
+            Status::Error(err) => {
+                eprintln!("{}", err.message);
+                &[]
+            }
+            Status::Offline => &[],
+        };
+        print!("{}", report::render(format, updates)?);
+    } else {
+        println!("{}", result.text(&config.count_by));
+        if let Status::MissingUpdates(updates) = &result.status {
+            for update in updates {
+                println!("{} {}", update.avg, update.text);
+            }
+        }
+    }
+    process::exit(code);
+}
+
+/// The configured token for a result and the matching exit code, advisories
+/// without a fix are clean unless `[alerts] unfixed` is set
+pub fn token(config: &Config, result: &CheckResult) -> (String, i32) {
+    match &result.status {
+        Status::MissingUpdates(updates) if !updates.iter().any(|u| u.alerts(config)) => {
+            (config.short_clean.clone(), EXIT_CLEAN)
+        }
+        Status::MissingUpdates(_) => (
+            result.format(&config.short_vulnerable, &config.count_by),
+            EXIT_VULNERABLE,
+        ),
+        Status::Error(err) => {
+            debug!("Check failed: {}", err.message);
+            (config.short_error.clone(), EXIT_ERROR)
+        }
+        // nothing is known while offline
+        Status::Offline => (config.short_error.clone(), EXIT_ERROR),
+    }
+}
@@ -0,0 +1,131 @@
+//! A window that shows the output of a command while it runs
+use crate::errors::*;
+use crate::i18n::tr;
+use gtk::prelude::*;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+
+enum Output {
+    Line(String),
+    Exit(io::Result<ExitStatus>),
+}
+
+/// Send every line of a pipe to the window
+fn forward<R: Read + Send + 'static>(pipe: R, tx: glib::Sender<Output>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(io::Result::ok) {
+            if tx.send(Output::Line(line)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Start `cmd` and open a window with its stdout and stderr. The window can
+/// only be closed after the command exited, `done` is called with its exit
+/// status or the error if it couldn't be started.
+pub fn run<F>(title: &str, mut cmd: Command, done: F)
+where
+    F: FnOnce(io::Result<ExitStatus>) + 'static,
+{
+    info!("Running {:?}", cmd);
+    let spawned = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(err) => {
+            let msg = format!("Failed to start {:?}: {}", cmd.get_program(), err);
+            warn!("{}", msg);
+            done(Err(io::Error::new(err.kind(), msg)));
+            return;
+        }
+    };
+
+    #[allow(deprecated)]
+    let (tx, rx) = glib::MainContext::channel::<Output>(glib::Priority::DEFAULT);
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward(stdout, tx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward(stderr, tx.clone()));
+    }
+    thread::spawn(move || {
+        // read everything before reporting the exit
+        for reader in readers {
+            reader.join().ok();
+        }
+        tx.send(Output::Exit(child.wait())).ok();
+    });
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(title);
+    window.set_default_size(700, 400);
+    window.set_deletable(false);
+    window.connect_delete_event(|window, _| {
+        if window.is_deletable() {
+            glib::Propagation::Proceed
+        } else {
+            glib::Propagation::Stop
+        }
+    });
+
+    let buffer = gtk::TextBuffer::new(None::<&gtk::TextTagTable>);
+    let view = gtk::TextView::with_buffer(&buffer);
+    view.set_editable(false);
+    view.set_cursor_visible(false);
+    view.set_monospace(true);
+    // stays at the end while lines are added
+    let end = gtk::TextMark::new(None, false);
+    buffer.add_mark(&end, &buffer.end_iter());
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.set_vexpand(true);
+    scrolled.add(&view);
+
+    let status = gtk::Label::new(Some(&tr("Running...")));
+    status.set_halign(gtk::Align::Start);
+    let close = gtk::Button::with_mnemonic(&tr("_Close"));
+    close.set_sensitive(false);
+    {
+        let window = window.clone();
+        close.connect_clicked(move |_| window.close());
+    }
+    let footer = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+    footer.pack_start(&status, true, true, 0);
+    footer.pack_end(&close, false, false, 0);
+
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    vbox.set_border_width(12);
+    vbox.pack_start(&scrolled, true, true, 0);
+    vbox.pack_start(&footer, false, false, 0);
+    window.add(&vbox);
+    window.show_all();
+    window.present();
+
+    let mut done = Some(done);
+    rx.attach(None, move |output| match output {
+        Output::Line(line) => {
+            buffer.insert(&mut buffer.end_iter(), &line);
+            buffer.insert(&mut buffer.end_iter(), "\n");
+            view.scroll_mark_onscreen(&end);
+            glib::ControlFlow::Continue
+        }
+        Output::Exit(result) => {
+            match &result {
+                Ok(exit) if exit.success() => status.set_text(&tr("Finished")),
+                Ok(exit) => status.set_text(&tr("Failed: {}").replace("{}", &exit.to_string())),
+                Err(err) => status.set_text(&tr("Failed: {}").replace("{}", &err.to_string())),
+            }
+            close.set_sensitive(true);
+            window.set_deletable(true);
+            if let Some(done) = done.take() {
+                done(result);
+            }
+            glib::ControlFlow::Break
+        }
+    });
+}
@@ -0,0 +1,123 @@
+use crate::errors::*;
+use alpm::{Alpm, SigLevel};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+pub const PACMAN_CONF: &str = "/etc/pacman.conf";
+pub const DEFAULT_DBPATH: &str = "/var/lib/pacman";
+
+/// Value of `DBPath` in the `[options]` section of pacman.conf
+pub fn dbpath_from_conf(conf: &str) -> Option<String> {
+    let mut options = false;
+    for line in conf.lines().map(|line| line.trim()) {
+        if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            options = section == "options";
+        } else if let Some((key, value)) = line.split_once('=') {
+            if options && key.trim() == "DBPath" {
+                return Some(value.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Location of the pacman database, `$ARCH_AUDIT_DBPATH` takes precedence
+/// over pacman.conf
+pub fn dbpath() -> String {
+    if let Ok(dbpath) = env::var("ARCH_AUDIT_DBPATH") {
+        return dbpath;
+    }
+    fs::read_to_string(PACMAN_CONF)
+        .ok()
+        .and_then(|conf| dbpath_from_conf(&conf))
+        .unwrap_or_else(|| DEFAULT_DBPATH.to_string())
+}
+
+/// Names of the sync repositories configured in pacman.conf, in order
+pub fn repos_from_conf(conf: &str) -> Vec<String> {
+    conf.lines()
+        .map(|line| line.trim())
+        .filter_map(|line| line.strip_prefix('[')?.strip_suffix(']'))
+        .filter(|section| *section != "options")
+        .map(String::from)
+        .collect()
+}
+
+/// Find the sync repository each package is provided by. Packages that don't
+/// exist in any sync repository (eg. installed from the AUR) are omitted.
+pub fn package_repos<'a, I>(dbpath: &str, pkgs: I) -> Result<HashMap<String, String>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let conf = fs::read_to_string(PACMAN_CONF)
+        .with_context(|| anyhow!("Failed to read {:?}", PACMAN_CONF))?;
+
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    for repo in repos_from_conf(&conf) {
+        alpm.register_syncdb(repo.as_str(), SigLevel::USE_DEFAULT)
+            .with_context(|| anyhow!("Failed to register sync database: {:?}", repo))?;
+    }
+
+    let mut repos = HashMap::new();
+    for pkg in pkgs {
+        // the first repository wins, same as with pacman
+        if let Some(db) = alpm.syncdbs().into_iter().find(|db| db.pkg(pkg).is_ok()) {
+            repos.insert(pkg.to_string(), db.name().to_string());
+        }
+    }
+
+    Ok(repos)
+}
+
+/// Installed version of each package, packages that aren't installed are
+/// omitted
+pub fn installed_versions<'a, I>(dbpath: &str, pkgs: I) -> Result<HashMap<String, String>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    let db = alpm.localdb();
+    Ok(pkgs
+        .into_iter()
+        .filter_map(|pkg| {
+            let version = db.pkg(pkg).ok()?.version().to_string();
+            Some((pkg.to_string(), version))
+        })
+        .collect())
+}
+
+/// Unix timestamp each package was installed or upgraded at, packages that
+/// aren't installed are omitted
+pub fn install_dates<'a, I>(dbpath: &str, pkgs: I) -> Result<HashMap<String, i64>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    let db = alpm.localdb();
+    Ok(pkgs
+        .into_iter()
+        .filter_map(|pkg| Some((pkg.to_string(), db.pkg(pkg).ok()?.install_date()?)))
+        .collect())
+}
+
+/// Installed packages that aren't in any sync repository, like `pacman -Qm`
+#[cfg(feature = "native")]
+pub fn foreign_packages(dbpath: &str) -> Result<Vec<String>> {
+    let conf = fs::read_to_string(PACMAN_CONF)
+        .with_context(|| anyhow!("Failed to read {:?}", PACMAN_CONF))?;
+
+    let alpm = Alpm::new("/", dbpath).context("Failed to open pacman database")?;
+    for repo in repos_from_conf(&conf) {
+        alpm.register_syncdb(repo.as_str(), SigLevel::USE_DEFAULT)
+            .with_context(|| anyhow!("Failed to register sync database: {:?}", repo))?;
+    }
+
+    Ok(alpm
+        .localdb()
+        .pkgs()
+        .iter()
+        .filter(|pkg| alpm.syncdbs().iter().all(|db| db.pkg(pkg.name()).is_err()))
+        .map(|pkg| pkg.name().to_string())
+        .collect())
+}
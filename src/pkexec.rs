@@ -0,0 +1,137 @@
+use crate::errors::*;
+use crate::i18n::{ntr, tr};
+use crate::updater::Update;
+use crate::upgrade::in_path;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::process::{Command, ExitStatus};
+
+const PKEXEC: &str = "pkexec";
+/// The path in the polkit policy, pkexec only uses its message for this path
+const PACMAN: &str = "/usr/bin/pacman";
+/// pkexec exits with these if the password prompt was dismissed or the user
+/// isn't allowed to run pacman
+const EXIT_DISMISSED: i32 = 126;
+const EXIT_NOT_AUTHORIZED: i32 = 127;
+/// Shown below the packages in the confirmation dialog
+const PARTIAL_UPGRADE: &str = "Only these packages are upgraded. This is a partial upgrade, \
+    which Arch Linux doesn't support: if they need newer dependencies, pacman may fail or \
+    break them.";
+
+/// Installed and fixed versions of every package that an upgrade fixes,
+/// foreign packages can't be installed with pacman
+fn packages(updates: &[Update]) -> BTreeMap<&str, (Option<&str>, BTreeSet<&str>)> {
+    let mut packages = BTreeMap::<_, (_, BTreeSet<_>)>::new();
+    for update in updates
+        .iter()
+        .filter(|update| update.fixable() && !update.aur)
+    {
+        let entry = packages.entry(update.pkg.as_str()).or_default();
+        entry.0 = update.installed.as_deref();
+        entry.1.extend(update.fixed.as_deref());
+    }
+    packages
+}
+
+/// If there's anything `upgrade` would install
+pub fn any_packages(updates: &[Update]) -> bool {
+    !packages(updates).is_empty()
+}
+
+fn command<'a>(packages: impl Iterator<Item = &'a str>) -> Command {
+    let mut cmd = Command::new(PKEXEC);
+    // pacman has no terminal to ask, the dialog was the confirmation
+    cmd.args([
+        PACMAN,
+        "-S",
+        "--needed",
+        "--noconfirm",
+        "--noprogressbar",
+        "--color",
+        "never",
+    ]);
+    cmd.args(packages);
+    cmd
+}
+
+fn exit_result(result: io::Result<ExitStatus>) -> Result<(), String> {
+    match result {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) if status.code() == Some(EXIT_DISMISSED) => {
+            Err(tr("the authentication was dismissed"))
+        }
+        Ok(status) if status.code() == Some(EXIT_NOT_AUTHORIZED) => Err(tr("not authorized")),
+        Ok(status) => Err(status.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Ask before installing the fixed versions of the vulnerable packages, then
+/// install only them with `pkexec pacman -S` and show the output of pacman.
+/// `started` is called once confirmed, `done` after pacman exited.
+pub fn upgrade<S, D>(updates: &[Update], started: S, done: D) -> Result<()>
+where
+    S: FnOnce() + 'static,
+    D: FnOnce(Result<(), String>) + 'static,
+{
+    if !in_path(PKEXEC) {
+        bail!(
+            "{}",
+            tr("pkexec not found, install polkit to upgrade vulnerable packages")
+        );
+    }
+    let packages = packages(updates);
+    if packages.is_empty() {
+        bail!(
+            "{}",
+            tr("None of the vulnerable packages can be upgraded with pacman")
+        );
+    }
+    let list = packages
+        .iter()
+        .map(|(pkg, (installed, fixed))| {
+            let fixed = fixed.iter().copied().collect::<Vec<_>>().join(", ");
+            match installed {
+                Some(installed) => format!("{} {} → {}", pkg, installed, fixed),
+                None => format!("{} → {}", pkg, fixed),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let names = packages
+        .keys()
+        .map(|pkg| pkg.to_string())
+        .collect::<Vec<_>>();
+
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::empty(),
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &ntr(
+            "Upgrade {} vulnerable package?",
+            "Upgrade {} vulnerable packages?",
+            names.len(),
+        ),
+    );
+    dialog.set_secondary_text(Some(&format!("{}\n\n{}", list, tr(PARTIAL_UPGRADE),)));
+    let callbacks = Cell::new(Some((started, done)));
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response != gtk::ResponseType::Ok {
+            return;
+        }
+        let Some((started, done)) = callbacks.take() else {
+            return;
+        };
+        started();
+        let cmd = command(names.iter().map(String::as_str));
+        crate::output::run(&tr("Upgrading vulnerable packages"), cmd, move |result| {
+            done(exit_result(result))
+        });
+    });
+    dialog.show_all();
+    Ok(())
+}
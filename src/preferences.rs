@@ -0,0 +1,293 @@
+use crate::config::{self, Config, SettingsBackend};
+use crate::errors::*;
+use crate::gui::{Theme, ThemeDir};
+use crate::i18n::tr;
+use crate::updater::severity_key;
+use arch_audit::types::Severity;
+use gtk::prelude::*;
+use std::fs;
+use std::time::Duration;
+use toml_edit::{table, value, Array, Document};
+
+/// Choices of the severity threshold, see `[alerts] min_severity`
+const SEVERITIES: &[(Severity, &str)] = &[
+    (Severity::Unknown, "All advisories"),
+    (Severity::Low, "Low and above"),
+    (Severity::Medium, "Medium and above"),
+    (Severity::High, "High and above"),
+    (Severity::Critical, "Only critical"),
+];
+/// Upper limit of the check interval, one week
+const MAX_INTERVAL_MINUTES: f64 = 7.0 * 24.0 * 60.0;
+
+/// The settings that can be changed in the dialog
+#[derive(Debug, Clone)]
+pub struct Preferences {
+    pub icon_theme: Theme,
+    pub check_interval: Duration,
+    pub ignore: Vec<String>,
+    pub min_severity: Severity,
+    pub notifications_enabled: bool,
+    pub notifications_per_advisory: bool,
+}
+
+impl Preferences {
+    fn from_config(config: &Config) -> Self {
+        Preferences {
+            icon_theme: config.icon_theme.clone(),
+            check_interval: config.check_interval,
+            ignore: config.ignore.clone(),
+            min_severity: config.min_severity,
+            notifications_enabled: config.notifications_enabled,
+            notifications_per_advisory: config.notifications_per_advisory,
+        }
+    }
+
+    /// Change a running config, so nothing has to be restarted
+    pub fn apply(&self, config: &mut Config) {
+        config.icon_theme = self.icon_theme.clone();
+        config.check_interval = self.check_interval;
+        config.ignore = self.ignore.clone();
+        config.min_severity = self.min_severity;
+        config.notifications_enabled = self.notifications_enabled;
+        config.notifications_per_advisory = self.notifications_per_advisory;
+    }
+
+    /// Write the settings that differ from `old` to the configured backend,
+    /// the others keep coming from the config files
+    fn save(&self, old: &Preferences, backend: &SettingsBackend) -> Result<()> {
+        match backend {
+            SettingsBackend::File => self.save_file(old),
+            #[cfg(feature = "gsettings")]
+            SettingsBackend::GSettings => crate::gsettings::save(self, old),
+            // replaced with the file when loading the config
+            #[cfg(not(feature = "gsettings"))]
+            SettingsBackend::GSettings => self.save_file(old),
+        }
+    }
+
+    /// Write the changed settings to the config file in the home directory.
+    /// The file is edited in place, other options and comments are kept.
+    fn save_file(&self, old: &Preferences) -> Result<()> {
+        let path = config::user_path().context("Failed to find the config directory")?;
+        let file = match fs::read_to_string(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).with_context(|| anyhow!("Failed to read {:?}", path)),
+        };
+        let mut doc = file
+            .parse::<Document>()
+            .with_context(|| anyhow!("Failed to parse {:?}", path))?;
+        self.edit(old, &mut doc);
+
+        info!("Writing preferences to {:?}", path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, doc.to_string()).with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &path).with_context(|| anyhow!("Failed to move config to {:?}", path))
+    }
+
+    /// Set the options that differ from `old` in a parsed config file
+    fn edit(&self, old: &Preferences, doc: &mut Document) {
+        // new sections are added as regular tables instead of inline ones
+        let mut set = |section: &str, option: &str, item| {
+            doc.entry(section).or_insert(table())[option] = item;
+        };
+        if self.icon_theme != old.icon_theme {
+            set("design", "icon_theme", value(self.icon_theme.as_str()));
+        }
+        if self.check_interval != old.check_interval {
+            set(
+                "updater",
+                "check_interval",
+                value(self.check_interval.as_secs() as i64),
+            );
+        }
+        if self.ignore != old.ignore {
+            let ignore = self.ignore.iter().map(String::as_str).collect::<Array>();
+            set("updater", "ignore", value(ignore));
+        }
+        if self.min_severity != old.min_severity {
+            set(
+                "alerts",
+                "min_severity",
+                value(severity_key(self.min_severity)),
+            );
+        }
+        if self.notifications_enabled != old.notifications_enabled {
+            set(
+                "notifications",
+                "enabled",
+                value(self.notifications_enabled),
+            );
+        }
+        if self.notifications_per_advisory != old.notifications_per_advisory {
+            set(
+                "notifications",
+                "per_advisory",
+                value(self.notifications_per_advisory),
+            );
+        }
+    }
+}
+
+fn row(grid: &gtk::Grid, top: i32, label: &str, widget: &impl IsA<gtk::Widget>) {
+    let label = gtk::Label::with_mnemonic(&tr(label));
+    label.set_halign(gtk::Align::Start);
+    label.set_mnemonic_widget(Some(widget));
+    grid.attach(&label, 0, top, 1, 1);
+    grid.attach(widget, 1, top, 1, 1);
+}
+
+fn error_dialog(parent: &gtk::Dialog, err: &Error) {
+    let dialog = gtk::MessageDialog::new(
+        Some(parent),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        &tr("Failed to save the preferences"),
+    );
+    dialog.set_secondary_text(Some(&format!("{:#}", err)));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+/// Open a dialog to edit the most common settings, `on_save` applies them to
+/// the running instance once they're written to the config file
+pub fn show(config: &Config, on_save: impl Fn(&Preferences) + 'static) {
+    let current = Preferences::from_config(config);
+    let backend = config.settings_backend.clone();
+
+    let dialog = gtk::Dialog::new();
+    dialog.set_title(&tr("Preferences"));
+    dialog.add_button(&tr("_Cancel"), gtk::ResponseType::Cancel);
+    dialog.add_button(&tr("_Save"), gtk::ResponseType::Accept);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let theme = gtk::ComboBoxText::new();
+    let mut themes = ThemeDir::available();
+    if !themes.contains(&current.icon_theme) {
+        themes.push(current.icon_theme.clone());
+    }
+    for name in &themes {
+        theme.append(Some(name.as_str()), name.as_str());
+    }
+    theme.set_active_id(Some(current.icon_theme.as_str()));
+
+    let interval = gtk::SpinButton::with_range(1.0, MAX_INTERVAL_MINUTES, 1.0);
+    let interval_minutes = (current.check_interval.as_secs() / 60).max(1) as i32;
+    interval.set_value(interval_minutes as f64);
+
+    let ignore = gtk::Entry::new();
+    ignore.set_text(&current.ignore.join(" "));
+    ignore.set_placeholder_text(Some(&tr("Package names separated by spaces")));
+    ignore.set_activates_default(true);
+
+    let severity = gtk::ComboBoxText::new();
+    for (value, label) in SEVERITIES {
+        severity.append(Some(severity_key(*value)), &tr(label));
+    }
+    severity.set_active_id(Some(severity_key(current.min_severity)));
+
+    let notifications = gtk::CheckButton::with_mnemonic(&tr("Show _notifications"));
+    notifications.set_active(current.notifications_enabled);
+    let per_advisory = gtk::CheckButton::with_mnemonic(&tr("One notification _per advisory"));
+    per_advisory.set_active(current.notifications_per_advisory);
+    per_advisory.set_sensitive(current.notifications_enabled);
+    {
+        let per_advisory = per_advisory.clone();
+        notifications.connect_toggled(move |button| per_advisory.set_sensitive(button.is_active()));
+    }
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+    row(&grid, 0, "Icon _theme", &theme);
+    row(&grid, 1, "Check _interval (minutes)", &interval);
+    row(&grid, 2, "_Ignored packages", &ignore);
+    row(&grid, 3, "_Alert for", &severity);
+    grid.attach(&notifications, 0, 4, 2, 1);
+    grid.attach(&per_advisory, 0, 5, 2, 1);
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(move |dialog, response| {
+        if response != gtk::ResponseType::Accept {
+            dialog.close();
+            return;
+        }
+        let prefs = Preferences {
+            icon_theme: theme
+                .active_id()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or_else(|| current.icon_theme.clone()),
+            // an interval that isn't whole minutes is kept unless it's changed
+            check_interval: match interval.value_as_int() {
+                minutes if minutes == interval_minutes => current.check_interval,
+                minutes => Duration::from_secs(minutes as u64 * 60),
+            },
+            ignore: ignore.text().split_whitespace().map(String::from).collect(),
+            min_severity: severity
+                .active_id()
+                .and_then(|id| config::parse_severity(&id).ok())
+                .unwrap_or(current.min_severity),
+            notifications_enabled: notifications.is_active(),
+            notifications_per_advisory: per_advisory.is_active(),
+        };
+        match prefs.save(&current, &backend) {
+            Ok(()) => {
+                on_save(&prefs);
+                dialog.close();
+            }
+            Err(err) => {
+                warn!("{:#}", err);
+                error_dialog(dialog, &err);
+            }
+        }
+    });
+    dialog.show_all();
+    dialog.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preferences() -> Preferences {
+        Preferences {
+            icon_theme: Theme::default(),
+            check_interval: Duration::from_secs(90),
+            ignore: vec!["linux".to_string()],
+            min_severity: Severity::Unknown,
+            notifications_enabled: true,
+            notifications_per_advisory: false,
+        }
+    }
+
+    #[test]
+    fn only_changed_options_are_written() {
+        let old = preferences();
+        let mut new = old.clone();
+        new.min_severity = Severity::High;
+        let mut doc = "# keep me\n[updater]\nignore = [\"glibc\"]\n"
+            .parse()
+            .unwrap();
+        new.edit(&old, &mut doc);
+        assert_eq!(
+            doc.to_string(),
+            "# keep me\n[updater]\nignore = [\"glibc\"]\n\n[alerts]\nmin_severity = \"high\"\n"
+        );
+    }
+
+    #[test]
+    fn unchanged_preferences_leave_the_file_alone() {
+        let old = preferences();
+        let file = "[design]\nicon_theme = \"papirus\" # from the system\n";
+        let mut doc = file.parse().unwrap();
+        old.edit(&old, &mut doc);
+        assert_eq!(doc.to_string(), file);
+    }
+}
@@ -0,0 +1,48 @@
+//! Detect upgrades that only take effect after a reboot
+
+use crate::errors::*;
+use crate::pacman;
+use std::fs;
+use std::path::Path;
+
+/// Modules of every installed kernel, pacman removes the directory of the
+/// old release when the kernel is upgraded
+const MODULES_DIR: &str = "/usr/lib/modules";
+const OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+const PROC_STAT: &str = "/proc/stat";
+/// Running processes keep using the old version until they're restarted
+const REBOOT_PACKAGES: &[&str] = &["systemd", "glibc"];
+
+/// If the modules of the running kernel are gone
+fn kernel_upgraded() -> Result<bool> {
+    let release =
+        fs::read_to_string(OSRELEASE).with_context(|| anyhow!("Failed to read {:?}", OSRELEASE))?;
+    Ok(!Path::new(MODULES_DIR).join(release.trim()).is_dir())
+}
+
+/// Unix timestamp of the boot
+fn boot_time() -> Result<i64> {
+    let stat =
+        fs::read_to_string(PROC_STAT).with_context(|| anyhow!("Failed to read {:?}", PROC_STAT))?;
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|btime| btime.trim().parse().ok())
+        .with_context(|| anyhow!("No boot time in {:?}", PROC_STAT))
+}
+
+/// Upgrades that need a reboot, eg. `["kernel", "systemd"]`, empty if
+/// everything that's installed is also running
+pub fn required(dbpath: &str) -> Result<Vec<String>> {
+    let mut reasons = Vec::new();
+    if kernel_upgraded()? {
+        reasons.push("kernel".to_string());
+    }
+    let boot = boot_time()?;
+    let installed = pacman::install_dates(dbpath, REBOOT_PACKAGES.iter().copied())?;
+    for pkg in REBOOT_PACKAGES {
+        if installed.get(*pkg).is_some_and(|date| *date > boot) {
+            reasons.push(pkg.to_string());
+        }
+    }
+    Ok(reasons)
+}
@@ -0,0 +1,209 @@
+use crate::errors::*;
+use crate::i18n::{ntr, tr};
+use crate::updater::{severity_key, severity_name, Update};
+use gtk::prelude::*;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+const DEFAULT_NAME: &str = "arch-audit-report.md";
+const FILTERS: &[(&str, &str)] = &[("Markdown", "*.md"), ("CSV", "*.csv"), ("JSON", "*.json")];
+const CSV_HEADER: &[&str] = &[
+    "package",
+    "installed",
+    "fixed",
+    "severity",
+    "advisory",
+    "status",
+    "kind",
+    "issues",
+    "link",
+];
+
+/// Format of a saved or printed report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// The advisories like in the json export, without the rest of the result
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl Format {
+    /// Select the format by the extension of a file name
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        ext.parse()
+            .with_context(|| anyhow!("Unknown report format {:?}, use .json, .csv or .md", ext))
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "md" | "markdown" => Ok(Format::Markdown),
+            _ => bail!("Invalid report format: {:?}", s),
+        }
+    }
+}
+
+/// Serialize the advisories in a format, used by `check --report` and the
+/// menu
+pub fn render(format: Format, updates: &[Update]) -> Result<String> {
+    match format {
+        Format::Json => {
+            let json =
+                serde_json::to_string_pretty(updates).context("Failed to serialize report")?;
+            Ok(json + "\n")
+        }
+        Format::Csv => Ok(csv(updates)),
+        Format::Markdown => Ok(markdown(updates)),
+    }
+}
+
+/// CVE IDs of all advisories, one per line and without duplicates
+pub fn cve_ids(updates: &[Update]) -> String {
+    let cves = updates
+        .iter()
+        .flat_map(|u| &u.issues)
+        .map(String::as_str)
+        .collect::<BTreeSet<_>>();
+    cves.into_iter().collect::<Vec<_>>().join("\n")
+}
+
+/// Markdown list of all advisories, readable as plain text too, for pasting
+/// into tickets or chats
+pub fn markdown(updates: &[Update]) -> String {
+    let packages = updates
+        .iter()
+        .map(|u| u.pkg.as_str())
+        .collect::<BTreeSet<_>>();
+    let mut report = format!(
+        "# {}\n\n",
+        ntr(
+            "{} package with missing security updates",
+            "{} packages with missing security updates",
+            packages.len(),
+        )
+    );
+    for update in updates {
+        let installed = update.installed.as_deref().unwrap_or("?");
+        let fixed = match (&update.fixed, update.fixable()) {
+            (Some(fixed), true) => fixed.clone(),
+            _ => tr("no fix released yet"),
+        };
+        report.push_str(&format!(
+            "- **{}** {} → {}: {}, [{}]({}) ({})\n",
+            update.pkg,
+            installed,
+            fixed,
+            severity_name(update.severity),
+            update.avg,
+            update.link,
+            update.kind
+        ));
+        if !update.issues.is_empty() {
+            report.push_str(&format!("  {}\n", update.issues.join(", ")));
+        }
+    }
+    report
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One row per advisory and package, the CVEs are separated by spaces
+fn csv(updates: &[Update]) -> String {
+    let mut report = CSV_HEADER.join(",") + "\n";
+    for update in updates {
+        let status = format!("{:?}", update.avg_status);
+        let issues = update.issues.join(" ");
+        let row = [
+            update.pkg.as_str(),
+            update.installed.as_deref().unwrap_or(""),
+            update.fixed.as_deref().unwrap_or(""),
+            severity_key(update.severity),
+            update.avg.as_str(),
+            status.as_str(),
+            update.kind.as_str(),
+            issues.as_str(),
+            update.link.as_str(),
+        ];
+        report.push_str(&row.map(csv_field).join(","));
+        report.push('\n');
+    }
+    report
+}
+
+fn write(path: &Path, updates: &[Update]) -> Result<()> {
+    let report = render(Format::from_path(path)?, updates)?;
+    info!("Writing report to {:?}", path);
+    fs::write(path, report).with_context(|| anyhow!("Failed to write {:?}", path))
+}
+
+fn error_dialog(parent: &gtk::FileChooserDialog, err: &Error) {
+    let dialog = gtk::MessageDialog::new(
+        Some(parent),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        &tr("Failed to save the report"),
+    );
+    dialog.set_secondary_text(Some(&format!("{:#}", err)));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+/// Ask for a file and write the advisories to it, the format is selected by
+/// the extension
+pub fn save(updates: &[Update]) {
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(&tr("Save report")),
+        None::<&gtk::Window>,
+        gtk::FileChooserAction::Save,
+        &[
+            (&tr("_Cancel"), gtk::ResponseType::Cancel),
+            (&tr("_Save"), gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_do_overwrite_confirmation(true);
+    dialog.set_current_name(DEFAULT_NAME);
+    for (name, pattern) in FILTERS {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some(name));
+        filter.add_pattern(pattern);
+        dialog.add_filter(filter);
+    }
+
+    let updates = updates.to_vec();
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Accept {
+            let Some(path) = dialog.filename() else {
+                return;
+            };
+            if let Err(err) = write(&path, &updates) {
+                // keep the dialog open, eg. to pick a known extension
+                warn!("{:#}", err);
+                error_dialog(dialog, &err);
+                return;
+            }
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+    dialog.present();
+}
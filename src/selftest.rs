@@ -0,0 +1,117 @@
+use crate::args::Args;
+use crate::config::{self, Config};
+use crate::errors::*;
+use crate::gui::ThemeDir;
+use crate::notifications;
+use crate::notify::NOTIFY_DIR;
+use crate::pacman;
+use crate::updater::{self, BackendKind, MIN_ARCH_AUDIT_VERSION};
+use inotify::{Inotify, WatchMask};
+use std::path::Path;
+
+/// Check the environment and print a report, fails if anything is broken
+/// that prevents arch-audit-gtk from working at all
+pub fn run(args: &Args, config: &Config) -> Result<()> {
+    let mut failed = 0;
+    let mut report = |name: &str, result: Result<String>, critical: bool| {
+        let state = match (&result, critical) {
+            (Ok(_), _) => "ok",
+            (Err(_), true) => "FAIL",
+            (Err(_), false) => "warn",
+        };
+        let detail = match result {
+            Ok(detail) => detail,
+            Err(err) => format!("{:#}", err),
+        };
+        println!("{:>10}: {:<4} {}", name, state, detail);
+        if state == "FAIL" {
+            failed += 1;
+        }
+    };
+
+    // the config is loaded before any subcommand runs, so it parses and
+    // only problems that don't keep it from working are left
+    report("config", check_config(args), false);
+    // the native backend doesn't need the arch-audit binary
+    report(
+        "arch-audit",
+        check_arch_audit(config),
+        config.backend == BackendKind::ArchAudit,
+    );
+    report(
+        "pacman",
+        check_pacman_db(config),
+        config.ssh_target.is_none(),
+    );
+    report("theme", check_theme(config), false);
+    report("inotify", check_inotify(), false);
+    report("notify", check_notifications(config), false);
+
+    if failed > 0 {
+        bail!("{} critical check(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn check_config(args: &Args) -> Result<String> {
+    let problems = config::problems(args)?;
+    if !problems.is_empty() {
+        bail!("{}, see check-config", problems.join("; "));
+    }
+    Ok("valid".to_string())
+}
+
+fn check_arch_audit(config: &Config) -> Result<String> {
+    let bin = updater::arch_audit_bin(config);
+    let version = updater::arch_audit_version(config)?;
+    if version < MIN_ARCH_AUDIT_VERSION {
+        let (major, minor, patch) = MIN_ARCH_AUDIT_VERSION;
+        bail!(
+            "{} {}.{}.{} is older than {}.{}.{}",
+            bin,
+            version.0,
+            version.1,
+            version.2,
+            major,
+            minor,
+            patch
+        );
+    }
+    Ok(format!("{} {}.{}.{}", bin, version.0, version.1, version.2))
+}
+
+fn check_pacman_db(config: &Config) -> Result<String> {
+    if let Some(target) = &config.ssh_target {
+        return Ok(format!("not needed, checking {:?} over ssh", target));
+    }
+    let path = Path::new(&pacman::dbpath()).join("local");
+    path.read_dir()
+        .with_context(|| anyhow!("Failed to read pacman database {:?}", path))?;
+    Ok(format!("{:?} is readable", path))
+}
+
+fn check_theme(config: &Config) -> Result<String> {
+    let name = config.icon_theme.as_str();
+    if config.icon_theme.is_system() {
+        return Ok("system icon theme".to_string());
+    }
+    let theme = ThemeDir::find_usable(&config.icon_theme)
+        .with_context(|| anyhow!("Theme {:?} not found, using the default theme", name))?;
+    Ok(format!("{:?}", theme.path()))
+}
+
+fn check_inotify() -> Result<String> {
+    let inotify = Inotify::init().context("Failed to init inotify")?;
+    inotify
+        .watches()
+        .add(NOTIFY_DIR, WatchMask::CLOSE_WRITE)
+        .with_context(|| anyhow!("Failed to watch {:?}, is the hook installed?", NOTIFY_DIR))?;
+    Ok(format!("watching {:?}", NOTIFY_DIR))
+}
+
+fn check_notifications(config: &Config) -> Result<String> {
+    if !config.notifications_enabled {
+        return Ok("disabled in [notifications]".to_string());
+    }
+    notifications::server_information()
+}
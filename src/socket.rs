@@ -0,0 +1,169 @@
+use crate::errors::*;
+use crate::updater::CheckResult;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Results queued for a streaming client before it's dropped as too slow
+const CLIENT_QUEUE: usize = 4;
+
+/// State shared between the gui thread and the socket server
+///
+/// Every client receives the latest result as a single line of json when
+/// connecting. With `stream` enabled the connection is kept open and every
+/// new result is written as another line. If debug commands are enabled,
+/// clients may send `set-icon <state>` to force an icon until the next check.
+///
+/// Each client has a thread of its own that writes the results, so a slow
+/// client never blocks the gui thread.
+#[derive(Debug, Clone)]
+pub struct Server {
+    path: PathBuf,
+    stream: bool,
+    commands: Option<glib::Sender<String>>,
+    latest: Arc<Mutex<Option<Arc<String>>>>,
+    /// Queues of the writer threads of streaming clients
+    clients: Arc<Mutex<Vec<mpsc::SyncSender<Arc<String>>>>>,
+}
+
+impl Server {
+    pub fn spawn(
+        path: &Path,
+        stream: bool,
+        commands: Option<glib::Sender<String>>,
+    ) -> Result<Self> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)
+            .with_context(|| anyhow!("Failed to bind unix socket to {:?}", path))?;
+        info!("Serving status on unix socket {:?}", path);
+
+        let server = Server {
+            path: path.to_path_buf(),
+            stream,
+            commands,
+            latest: Arc::new(Mutex::new(None)),
+            clients: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let s = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = s.handle(stream) {
+                            debug!("Failed to handle socket client: {:#}", err);
+                        }
+                    }
+                    Err(err) => warn!("Failed to accept socket connection: {:#}", err),
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    pub fn set_result(&self, result: &CheckResult) {
+        let json = match result.to_json() {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("{:#}", err);
+                return;
+            }
+        };
+
+        // drop clients that disconnected or don't keep up, their writer
+        // thread exits once it's done with the queue
+        let json = Arc::new(json);
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| client.try_send(json.clone()).is_ok());
+
+        *self.latest.lock().unwrap() = Some(json);
+    }
+
+    /// Remove the socket file, clients that are still connected keep working
+    pub fn cleanup(&self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("Failed to remove unix socket {:?}: {:#}", self.path, err);
+        }
+    }
+
+    fn handle(&self, stream: UnixStream) -> Result<()> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+        if let Some(commands) = &self.commands {
+            let reader = stream.try_clone()?;
+            let writer = stream.try_clone()?;
+            let commands = commands.clone();
+            thread::spawn(move || read_commands(reader, writer, commands));
+        }
+
+        let (tx, rx) = mpsc::sync_channel(CLIENT_QUEUE);
+        {
+            // hold the lock so we don't miss a result between both steps
+            let mut clients = self.clients.lock().unwrap();
+            let latest = self.latest.lock().unwrap().clone();
+            // the queue is still empty, this can't fail
+            tx.try_send(latest.unwrap_or_else(|| Arc::new("null".to_string())))
+                .ok();
+            if self.stream {
+                clients.push(tx);
+            }
+        }
+        thread::spawn(move || write_results(stream, rx));
+        Ok(())
+    }
+}
+
+/// Remove a socket file that was left behind by a previous instance
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(()),
+    };
+
+    if !metadata.file_type().is_socket() {
+        bail!("Refusing to replace {:?}, it's not a unix socket", path);
+    }
+    if UnixStream::connect(path).is_ok() {
+        bail!(
+            "Unix socket {:?} is already in use by another process",
+            path
+        );
+    }
+
+    debug!("Removing stale unix socket {:?}", path);
+    fs::remove_file(path).with_context(|| anyhow!("Failed to remove stale unix socket {:?}", path))
+}
+
+/// Write every queued result as a line of json, the connection is closed
+/// once the queue is dropped or the client doesn't accept the writes
+fn write_results(mut stream: UnixStream, results: mpsc::Receiver<Arc<String>>) {
+    for json in results {
+        if let Err(err) = writeln!(stream, "{}", json) {
+            debug!("Dropping socket client: {:#}", err);
+            break;
+        }
+    }
+}
+
+fn read_commands(reader: UnixStream, mut writer: UnixStream, commands: glib::Sender<String>) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        let reply = match line.split_once(' ') {
+            Some(("set-icon", state)) => match commands.send(state.trim().to_string()) {
+                Ok(_) => "ok".to_string(),
+                Err(_) => break,
+            },
+            _ => format!("error: unknown command: {:?}", line),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+}
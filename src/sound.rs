@@ -0,0 +1,65 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::i18n::tr;
+use crate::notifications;
+use crate::updater::{severity_key, severity_name, CheckResult, Status};
+use arch_audit::types::Severity;
+use std::process::Command;
+use std::thread;
+
+/// Plays the configured sound with canberra-gtk-play of libcanberra
+pub const PLAYER: &str = "canberra-gtk-play";
+
+/// Play a sound once if advisories showed up that weren't part of the
+/// previous result, chosen by the highest severity among them. Without a
+/// `[sounds]` entry only critical advisories play the `[sound]`. Like
+/// notifications, nothing is played while snoozed, muted, during quiet
+/// hours or for acknowledged advisories.
+pub fn handle(config: &Config, result: &CheckResult, snoozed: bool, muted: bool) {
+    let Status::MissingUpdates(updates) = &result.status else {
+        return;
+    };
+    if !config.sound_enabled || snoozed || muted {
+        return;
+    }
+    let severity = updates
+        .iter()
+        .filter(|u| u.new && u.alerts(config))
+        .map(|u| u.severity)
+        .max();
+    let Some(severity) = severity else {
+        return;
+    };
+    if severity != Severity::Critical && !config.sound_files.contains_key(&severity) {
+        return;
+    }
+    if notifications::is_quiet(config) {
+        return;
+    }
+    if let Err(err) = play(config, severity) {
+        warn!("Failed to play sound: {:#}", err);
+    }
+}
+
+fn play(config: &Config, severity: Severity) -> Result<()> {
+    let mut cmd = Command::new(PLAYER);
+    match config
+        .sound_files
+        .get(&severity)
+        .or(config.sound_file.as_ref())
+    {
+        Some(file) => cmd.arg("--file").arg(file),
+        None => cmd.args(["--id", &config.sound_name]),
+    };
+    cmd.arg("--description")
+        .arg(tr("New {} security advisory").replace("{}", &severity_name(severity)));
+    debug!(
+        "Playing sound for new {} advisories",
+        severity_key(severity)
+    );
+    let mut child = cmd
+        .spawn()
+        .with_context(|| anyhow!("Failed to run {:?}, is libcanberra installed?", PLAYER))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::config::Config;
+use crate::errors::*;
+use crate::updater::{self, CheckResult, Cve, Status, Update};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+const STATE_FILE: &str = "arch-audit-gtk/state.json";
+
+/// The last result, so the gui can show it while the first check is running
+#[derive(Debug, Serialize, Deserialize)]
+struct State<S> {
+    /// Unix timestamp of the check
+    checked_at: u64,
+    status: S,
+    #[serde(default)]
+    acknowledged: BTreeSet<Acknowledgement>,
+}
+
+/// An advisory acknowledged from the menu, a different installed or fixed
+/// version or new CVEs make it a different advisory that alerts again
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Acknowledgement {
+    avg: String,
+    pkg: String,
+    installed: Option<String>,
+    fixed: Option<String>,
+    issues: BTreeSet<String>,
+}
+
+impl Acknowledgement {
+    fn of(update: &Update) -> Self {
+        Acknowledgement {
+            avg: update.avg.clone(),
+            pkg: update.pkg.clone(),
+            installed: update.installed.clone(),
+            fixed: update.fixed.clone(),
+            issues: update.issues.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Acknowledged advisories of the local system, shared between the gui and
+/// the updater thread and saved with the last result
+#[derive(Debug, Clone, Default)]
+pub struct Acknowledged {
+    inner: Arc<Mutex<BTreeSet<Acknowledgement>>>,
+}
+
+impl Acknowledged {
+    /// Acknowledgements of the last run
+    pub fn load() -> Self {
+        let acknowledged = path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str::<State<serde_json::Value>>(&json).ok())
+            .map(|state| state.acknowledged)
+            .unwrap_or_default();
+        if !acknowledged.is_empty() {
+            debug!("Restored {} acknowledged advisories", acknowledged.len());
+        }
+        Acknowledged {
+            inner: Arc::new(Mutex::new(acknowledged)),
+        }
+    }
+
+    /// Acknowledge the update or take it back if it already is, returns if
+    /// it's acknowledged now
+    pub fn toggle(&self, update: &Update) -> bool {
+        let acknowledgement = Acknowledgement::of(update);
+        let mut acknowledged = self.inner.lock().unwrap();
+        if acknowledged.remove(&acknowledgement) {
+            false
+        } else {
+            acknowledged.insert(acknowledgement)
+        }
+    }
+
+    pub fn mark(&self, updates: &mut [Update]) {
+        let acknowledged = self.inner.lock().unwrap();
+        for update in updates {
+            update.acknowledged = acknowledged.contains(&Acknowledgement::of(update));
+        }
+    }
+
+    /// Forget acknowledgements that don't match any of the updates anymore,
+    /// eg. after an upgrade
+    pub fn prune(&self, updates: &[Update]) {
+        let current = updates
+            .iter()
+            .map(Acknowledgement::of)
+            .collect::<BTreeSet<_>>();
+        self.inner
+            .lock()
+            .unwrap()
+            .retain(|acknowledgement| current.contains(acknowledgement));
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    dirs_next::cache_dir().map(|dir| dir.join(STATE_FILE))
+}
+
+/// Remember a result and the acknowledged advisories for the next start,
+/// failed checks aren't saved since they're usually not meaningful anymore
+/// after a restart
+pub fn save(result: &CheckResult, acknowledged: &Acknowledged) -> Result<()> {
+    if !matches!(result.status, Status::MissingUpdates(_)) {
+        return Ok(());
+    }
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    let state = State {
+        checked_at: result
+            .checked_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        status: &result.status,
+        acknowledged: acknowledged.inner.lock().unwrap().clone(),
+    };
+    let json = serde_json::to_string(&state).context("Failed to serialize state")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+    }
+    // replace the file atomically, a crash never leaves a partial state behind
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, json + "\n").with_context(|| anyhow!("Failed to write {:?}", tmp))?;
+    fs::rename(&tmp, &path).with_context(|| anyhow!("Failed to move state to {:?}", path))
+}
+
+/// Result saved by the last run, if any
+pub fn load(config: &Config, acknowledged: &Acknowledged) -> Option<CheckResult> {
+    let path = path()?;
+    let json = fs::read_to_string(&path).ok()?;
+    let state = match serde_json::from_str::<State<Status>>(&json) {
+        Ok(state) => state,
+        Err(err) => {
+            warn!("Ignoring invalid state in {:?}: {:#}", path, err);
+            return None;
+        }
+    };
+    debug!("Restored last result from {:?}", path);
+
+    let mut status = state.status;
+    // these were handled by the last run
+    if let Status::MissingUpdates(updates) = &mut status {
+        for update in updates.iter_mut() {
+            update.new = false;
+            // saved before the links were part of the result
+            if update.cves.is_empty() {
+                update.cves = update.issues.iter().map(|id| Cve::new(id)).collect();
+            }
+        }
+        acknowledged.mark(updates);
+    }
+    let mut result = CheckResult::new(status, Duration::ZERO, updater::backend_name(config));
+    result.checked_at = UNIX_EPOCH + Duration::from_secs(state.checked_at);
+    Some(result)
+}
@@ -0,0 +1,256 @@
+use crate::errors::*;
+use crate::history::{self, Sample};
+use crate::i18n::{ntr, tr};
+use arch_audit::types::Severity;
+use gtk::cairo;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+/// Ranges the window can show, in days
+const RANGES: &[(&str, u64)] = &[
+    ("Last week", 7),
+    ("Last 4 weeks", 28),
+    ("Last 12 weeks", 84),
+];
+const DEFAULT_RANGE: usize = 1;
+/// From the bottom to the top of the stacked chart, so the critical
+/// packages are always on the axis
+const LAYERS: &[(Severity, (f64, f64, f64))] = &[
+    (Severity::Critical, (0.80, 0.00, 0.00)),
+    (Severity::High, (0.96, 0.47, 0.00)),
+    (Severity::Medium, (0.93, 0.75, 0.00)),
+    (Severity::Low, (0.20, 0.40, 0.80)),
+    (Severity::Unknown, (0.55, 0.55, 0.55)),
+];
+/// Space for the axis labels on the left and bottom, and the legend on top
+const LEFT: f64 = 40.0;
+const RIGHT: f64 = 12.0;
+const TOP: f64 = 32.0;
+const BOTTOM: f64 = 24.0;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The samples that matter for a range, the first one may be older and is
+/// the state at the start
+fn visible(samples: &[Sample], start: u64) -> &[Sample] {
+    let first = samples.iter().rposition(|s| s.time <= start).unwrap_or(0);
+    &samples[first..]
+}
+
+/// Midnights in local time, going back from today every `step` days
+fn day_ticks(start: u64, step: u64) -> Vec<(u64, String)> {
+    let Ok(now) = glib::DateTime::now_local() else {
+        return Vec::new();
+    };
+    let Ok(midnight) =
+        glib::DateTime::from_local(now.year(), now.month(), now.day_of_month(), 0, 0, 0.0)
+    else {
+        return Vec::new();
+    };
+    let mut ticks = Vec::new();
+    for n in 0.. {
+        let Ok(day) = midnight.add_days(-((n * step) as i32)) else {
+            break;
+        };
+        let time = day.to_unix().max(0) as u64;
+        if time < start {
+            break;
+        }
+        let label = day
+            .format("%b %e")
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        ticks.push((time, label));
+    }
+    ticks
+}
+
+fn draw(
+    area: &gtk::DrawingArea,
+    cr: &cairo::Context,
+    samples: &[Sample],
+    days: u64,
+) -> Result<(), cairo::Error> {
+    let fg = area.style_context().color(gtk::StateFlags::NORMAL);
+    let (width, height) = (
+        area.allocated_width() as f64,
+        area.allocated_height() as f64,
+    );
+    let (plot_w, plot_h) = (width - LEFT - RIGHT, height - TOP - BOTTOM);
+    if plot_w <= 0.0 || plot_h <= 0.0 {
+        return Ok(());
+    }
+    cr.set_font_size(11.0);
+
+    let now = now();
+    let start = now.saturating_sub(days * DAY);
+    let samples = visible(samples, start);
+    let max = samples.iter().map(Sample::total).max().unwrap_or(0).max(1);
+    let x = |time: u64| LEFT + (time.max(start) - start) as f64 / (now - start) as f64 * plot_w;
+    let y = |count: usize| TOP + plot_h - count as f64 / max as f64 * plot_h;
+
+    // grid and labels
+    cr.set_line_width(1.0);
+    let step = max.div_ceil(5);
+    for count in (0..=max).step_by(step) {
+        cr.set_source_rgba(fg.red(), fg.green(), fg.blue(), 0.15);
+        cr.move_to(LEFT, y(count).round() + 0.5);
+        cr.line_to(LEFT + plot_w, y(count).round() + 0.5);
+        cr.stroke()?;
+        let label = count.to_string();
+        let extents = cr.text_extents(&label)?;
+        cr.set_source_rgba(fg.red(), fg.green(), fg.blue(), fg.alpha());
+        cr.move_to(
+            LEFT - 6.0 - extents.width(),
+            y(count) + extents.height() / 2.0,
+        );
+        cr.show_text(&label)?;
+    }
+    let day_step = if days > 7 { 7 } else { 1 };
+    for (time, label) in day_ticks(start, day_step) {
+        cr.set_source_rgba(fg.red(), fg.green(), fg.blue(), 0.15);
+        cr.move_to(x(time).round() + 0.5, TOP);
+        cr.line_to(x(time).round() + 0.5, TOP + plot_h);
+        cr.stroke()?;
+        let extents = cr.text_extents(&label)?;
+        cr.set_source_rgba(fg.red(), fg.green(), fg.blue(), fg.alpha());
+        cr.move_to(x(time) - extents.width() / 2.0, height - 6.0);
+        cr.show_text(&label)?;
+    }
+
+    // one step area per severity, stacked on the ones below
+    let mut legend_x = LEFT;
+    for (layer, (severity, (r, g, b))) in LAYERS.iter().enumerate() {
+        let below = |sample: &Sample| -> usize {
+            LAYERS[..layer]
+                .iter()
+                .map(|(severity, _)| sample.count(*severity))
+                .sum()
+        };
+        let ends = samples
+            .iter()
+            .skip(1)
+            .map(|s| s.time)
+            .chain(std::iter::once(now))
+            .collect::<Vec<_>>();
+        if !samples.is_empty() {
+            cr.move_to(x(samples[0].time), y(below(&samples[0])));
+            for (sample, end) in samples.iter().zip(&ends) {
+                let top = below(sample) + sample.count(*severity);
+                cr.line_to(x(sample.time), y(top));
+                cr.line_to(x(*end), y(top));
+            }
+            for (sample, end) in samples.iter().zip(&ends).rev() {
+                cr.line_to(x(*end), y(below(sample)));
+                cr.line_to(x(sample.time), y(below(sample)));
+            }
+            cr.close_path();
+            cr.set_source_rgb(*r, *g, *b);
+            cr.fill()?;
+        }
+
+        let label = severity.to_string();
+        let extents = cr.text_extents(&label)?;
+        cr.set_source_rgb(*r, *g, *b);
+        cr.rectangle(legend_x, 10.0, 10.0, 10.0);
+        cr.fill()?;
+        cr.set_source_rgba(fg.red(), fg.green(), fg.blue(), fg.alpha());
+        cr.move_to(legend_x + 14.0, 19.0);
+        cr.show_text(&label)?;
+        legend_x += 14.0 + extents.x_advance() + 16.0;
+    }
+    Ok(())
+}
+
+fn summary(samples: &[Sample], days: u64) -> String {
+    let start = now().saturating_sub(days * DAY);
+    let samples = visible(samples, start);
+    let current = samples.last().map(Sample::total).unwrap_or(0);
+    let most = samples.iter().map(Sample::total).max().unwrap_or(0);
+    format!(
+        "{}, {}",
+        ntr(
+            "{} vulnerable package now",
+            "{} vulnerable packages now",
+            current
+        ),
+        ntr(
+            "at most {} during this range",
+            "at most {} during this range",
+            most
+        ),
+    )
+}
+
+/// Open a window with a chart of the vulnerable packages by severity over
+/// the last weeks, from the samples recorded after every check
+pub fn show() {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&tr("Statistics"));
+    window.set_default_size(800, 400);
+
+    let longest = RANGES.iter().map(|(_, days)| *days).max().unwrap_or(0);
+    let samples = Rc::new(history::load_samples(now().saturating_sub(longest * DAY)));
+    if samples.is_empty() {
+        let label = gtk::Label::new(Some(&tr("No checks recorded yet")));
+        window.add(&label);
+        window.show_all();
+        window.present();
+        return;
+    }
+
+    let days = Rc::new(Cell::new(RANGES[DEFAULT_RANGE].1));
+    let range = gtk::ComboBoxText::new();
+    for (label, _) in RANGES {
+        range.append_text(&tr(label));
+    }
+    range.set_active(Some(DEFAULT_RANGE as u32));
+    let label = gtk::Label::new(Some(&summary(&samples, days.get())));
+    label.set_halign(gtk::Align::Start);
+
+    let area = gtk::DrawingArea::new();
+    area.set_vexpand(true);
+    area.set_hexpand(true);
+    {
+        let samples = samples.clone();
+        let days = days.clone();
+        area.connect_draw(move |area, cr| {
+            if let Err(err) = draw(area, cr, &samples, days.get()) {
+                warn!("Failed to draw the statistics: {:#}", err);
+            }
+            glib::Propagation::Stop
+        });
+    }
+    {
+        let area = area.clone();
+        let label = label.clone();
+        range.connect_changed(move |range| {
+            let Some((_, selected)) = range.active().and_then(|i| RANGES.get(i as usize)) else {
+                return;
+            };
+            days.set(*selected);
+            label.set_text(&summary(&samples, *selected));
+            area.queue_draw();
+        });
+    }
+
+    let header = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+    header.pack_start(&label, true, true, 0);
+    header.pack_end(&range, false, false, 0);
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    vbox.set_border_width(12);
+    vbox.pack_start(&header, false, false, 0);
+    vbox.pack_start(&area, true, true, 0);
+    window.add(&vbox);
+
+    window.show_all();
+    window.present();
+}
@@ -0,0 +1,103 @@
+use crate::errors::*;
+use std::env;
+use std::fs;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+const SERVICE_FILE: &str = "systemd/user/arch-audit-gtk.service";
+const SERVICE_UNIT: &str = include_str!("../contrib/arch-audit-gtk.service");
+const PACKAGE_EXEC: &str = "/usr/bin/arch-audit-gtk";
+
+/// Send a state change to the service manager, without `$NOTIFY_SOCKET`
+/// we weren't started by a `Type=notify` unit and this does nothing
+fn notify(state: &str) {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = (|| -> Result<()> {
+        let path = path.to_str().context("Path isn't utf8")?;
+        // a leading @ is a socket in the abstract namespace
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        warn!("Failed to notify systemd about {:?}: {:#}", state, err);
+    }
+}
+
+/// The tray icon or daemon is set up, ends the startup of the unit
+pub fn ready() {
+    notify("READY=1");
+}
+
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// How often the main loop has to ping the watchdog, unset unless the unit
+/// has `WatchdogSec=` and the watchdog is meant for this process
+fn watchdog_interval() -> Option<Duration> {
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+    // ping twice per period, like sd_watchdog_enabled recommends
+    Some(Duration::from_micros(usec) / 2).filter(|interval| !interval.is_zero())
+}
+
+/// Ping the watchdog from the main loop, so systemd restarts us if it hangs
+pub fn start_watchdog() {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    debug!(
+        "Pinging the systemd watchdog every {}",
+        humantime::format_duration(interval)
+    );
+    glib::timeout_add_local(interval, || {
+        notify("WATCHDOG=1");
+        glib::ControlFlow::Continue
+    });
+}
+
+/// Write a user unit that starts this binary, the packaged unit is used
+/// for `/usr/bin/arch-audit-gtk`
+pub fn install_service(force: bool) -> Result<()> {
+    let path = dirs_next::config_dir()
+        .map(|dir| dir.join(SERVICE_FILE))
+        .context("Failed to find the config directory")?;
+    if path.exists() && !force {
+        bail!(
+            "Refusing to overwrite existing file {:?}, use --force",
+            path
+        );
+    }
+    let exe = env::current_exe().context("Failed to find the path of arch-audit-gtk")?;
+    let exe = exe.to_str().context("Path of arch-audit-gtk isn't utf8")?;
+    let unit = SERVICE_UNIT.replace(
+        &format!("ExecStart={}", PACKAGE_EXEC),
+        &format!("ExecStart={}", exe),
+    );
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create {:?}", parent))?;
+    }
+    fs::write(&path, unit).with_context(|| anyhow!("Failed to write {:?}", path))?;
+    println!("Wrote {}", path.display());
+    if Path::new(PACKAGE_EXEC) != Path::new(exe) {
+        println!("The unit starts {}", exe);
+    }
+    println!("Enable it with: systemctl --user daemon-reload && systemctl --user enable --now arch-audit-gtk.service");
+    println!("Disable \"Start at login\" in the menu, so there's only one instance");
+    Ok(())
+}
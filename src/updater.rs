@@ -1,153 +1,2205 @@
+use crate::backend;
+use crate::config::Config;
 use crate::errors::*;
 use crate::gui::Icon;
+use crate::i18n::{ntr, tr};
 use crate::notify::Event;
-use arch_audit::types::{Avg, Severity};
+use crate::pacman;
+use crate::reboot;
+use crate::state::Acknowledged;
+use arch_audit::types::{Avg, Severity, Status as AvgStatus};
 use rand::Rng;
+use serde::{Deserialize, Serialize, Serializer};
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::env;
-use std::process::Command;
-use std::sync::mpsc;
-use std::time::{Duration, Instant};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-// TODO: there should be a startup delay so we check after eg 5min
-// TODO: we should check how long ago the last update check was
-const CHECK_INTERVAL: u64 = 3600 * 2; // 2 hours
-const CHECK_JITTER: u64 = 3600 * 4; // 4 hours
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often a postponed check tests the metered and power saver policy again
+const POSTPONE_DELAY: Duration = Duration::from_secs(15 * 60);
+/// Checks requested right after a result wait this long, so a burst of
+/// clicks and pacman events results in a single check
+const CHECK_COOLDOWN: Duration = Duration::from_secs(5);
+const SNOOZE_FILE: &str = "arch-audit-gtk/snooze";
+// older versions are known to produce output we can't parse
+pub const MIN_ARCH_AUDIT_VERSION: (u64, u64, u64) = (0, 1, 20);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
     MissingUpdates(Vec<Update>),
-    Error(String),
+    Error(CheckError),
+    /// NetworkManager reports no connection, the check was deferred
+    Offline,
 }
 
 impl Status {
     pub fn text(&self) -> Cow<'_, str> {
         match self {
-            Status::MissingUpdates(list) => match list.len() {
-                0 => Cow::Borrowed("No missing security updates"),
-                1 => Cow::Borrowed("1 missing security update"),
-                n => Cow::Owned(format!("{} missing security updates", n)),
+            Status::MissingUpdates(list) => match list.iter().filter(|u| !u.acknowledged).count() {
+                0 => Cow::Owned(tr("No missing security updates")),
+                n => Cow::Owned(ntr(
+                    "{} missing security update",
+                    "{} missing security updates",
+                    n,
+                )),
             },
-            Status::Error(err) => Cow::Owned(format!("ERROR: {}", err)),
+            Status::Error(err) => Cow::Owned(tr("ERROR: {}").replace("{}", &err.message)),
+            Status::Offline => Cow::Owned(tr("Offline, waiting for the network")),
         }
     }
 
-    pub fn icon(&self) -> Icon {
+    /// Highest severity of all missing updates, if any
+    pub fn max_severity(&self) -> Option<Severity> {
         match self {
+            // the list is sorted, the first entry has the highest severity
             Status::MissingUpdates(list) => {
-                if list.is_empty() {
-                    Icon::Check
-                } else {
-                    Icon::Alert
-                }
+                list.iter().find(|u| !u.acknowledged).map(|u| u.severity)
+            }
+            Status::Error(_) | Status::Offline => None,
+        }
+    }
+
+    /// Highest severity of the missing updates that are worth an alert,
+    /// advisories without a fix only count with `[alerts] unfixed`
+    pub fn alert_severity(&self, config: &Config) -> Option<Severity> {
+        match self {
+            Status::MissingUpdates(list) => {
+                list.iter().find(|u| u.alerts(config)).map(|u| u.severity)
             }
-            Status::Error(_) => Icon::Cross,
+            Status::Error(_) | Status::Offline => None,
         }
     }
+
+    /// Select the icon for this status, consulting the user configured
+    /// overrides before falling back to the built-in icons
+    pub fn icon(&self, config: &Config) -> Icon {
+        let (states, icon): (&[&str], _) = match self {
+            Status::MissingUpdates(_) => match self.alert_severity(config) {
+                Some(severity) => (&[severity_key(severity), "alert"], Icon::Severity(severity)),
+                None => (&["clean"], Icon::Check),
+            },
+            Status::Error(err) => (err.kind.icon_states(), err.kind.icon()),
+            // the gui keeps the icon of the last result, this is only shown
+            // before the first one, where looking clean would be misleading
+            Status::Offline => (ErrorKind::Network.icon_states(), ErrorKind::Network.icon()),
+        };
+
+        icon_override(states, icon, &config.icons)
+    }
+}
+
+/// Cause of a failed check, selects the icon and a hint in the menu
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// arch-audit, the wrapper or ssh isn't installed
+    MissingBinary,
+    /// The security tracker or the remote host couldn't be reached
+    Network,
+    /// arch-audit succeeded, but its output couldn't be parsed
+    InvalidOutput,
+    PermissionDenied,
+    Other,
 }
 
+impl ErrorKind {
+    /// Classify the error of a failed check
+    pub fn of(err: &Error) -> Self {
+        if let Some(err) = err.chain().find_map(|e| e.downcast_ref::<KnownError>()) {
+            return err.kind;
+        }
+        if err.downcast_ref::<InvalidOutput>().is_some() {
+            return ErrorKind::InvalidOutput;
+        }
+        #[cfg(feature = "native")]
+        if err.chain().any(|e| e.is::<reqwest::Error>()) {
+            return ErrorKind::Network;
+        }
+        for err in err
+            .chain()
+            .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        {
+            match err.kind() {
+                std::io::ErrorKind::PermissionDenied => return ErrorKind::PermissionDenied,
+                std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::TimedOut => return ErrorKind::Network,
+                _ => (),
+            }
+        }
+        ErrorKind::Other
+    }
+
+    /// What the user can do about it, shown in the menu
+    pub fn hint(self) -> Option<String> {
+        match self {
+            ErrorKind::MissingBinary => Some(tr("Install the arch-audit package")),
+            ErrorKind::Network => Some(tr("Check the network connection, the check is retried")),
+            ErrorKind::InvalidOutput => Some(tr(
+                "Upgrade arch-audit, or report a bug with the raw output",
+            )),
+            ErrorKind::PermissionDenied => Some(tr(
+                "Check the permissions of the pacman database and the wrapper command",
+            )),
+            ErrorKind::Other => None,
+        }
+    }
+
+    fn icon_states(self) -> &'static [&'static str] {
+        match self {
+            ErrorKind::MissingBinary => &["missing_binary", "error"],
+            ErrorKind::Network => &["network_error", "error"],
+            ErrorKind::InvalidOutput => &["invalid_output", "error"],
+            ErrorKind::PermissionDenied => &["permission_denied", "error"],
+            ErrorKind::Other => &["error"],
+        }
+    }
+
+    fn icon(self) -> Icon {
+        match self {
+            ErrorKind::MissingBinary => Icon::MissingBinary,
+            ErrorKind::Network => Icon::NetworkError,
+            ErrorKind::PermissionDenied => Icon::PermissionDenied,
+            ErrorKind::InvalidOutput | ErrorKind::Other => Icon::Cross,
+        }
+    }
+}
+
+/// A failed check. Only the message is serialized, so the json export keeps
+/// its format, the kind is exported separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct CheckError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CheckError {
+    pub fn new(err: &Error) -> Self {
+        CheckError {
+            kind: ErrorKind::of(err),
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+impl From<String> for CheckError {
+    fn from(message: String) -> Self {
+        CheckError {
+            kind: ErrorKind::Other,
+            message,
+        }
+    }
+}
+
+impl From<CheckError> for String {
+    fn from(err: CheckError) -> Self {
+        err.message
+    }
+}
+
+/// Error with a known cause, anything else is classified by `ErrorKind::of`
 #[derive(Debug)]
+struct KnownError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl fmt::Display for KnownError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for KnownError {}
+
+pub fn known_error(kind: ErrorKind, message: String) -> Error {
+    Error::new(KnownError { kind, message })
+}
+
+/// Icon shown if a check or the update command failed
+pub fn error_icon(overrides: &HashMap<String, String>) -> Icon {
+    icon_override(&["error"], Icon::Cross, overrides)
+}
+
+/// Icon shown while updates are installed from the menu
+pub fn upgrading_icon(overrides: &HashMap<String, String>) -> Icon {
+    icon_override(&["upgrading"], Icon::Upgrading, overrides)
+}
+
+/// Icon shown while alerts are snoozed, unless configured it looks clean
+pub fn snoozed_icon(overrides: &HashMap<String, String>) -> Icon {
+    icon_override(&["snoozed", "clean"], Icon::Check, overrides)
+}
+
+/// Icon shown if there's nothing to install, but a reboot is required
+pub fn reboot_icon(overrides: &HashMap<String, String>) -> Icon {
+    icon_override(&["reboot", "clean"], Icon::Reboot, overrides)
+}
+
+/// Icon panels show instead of the regular one for critical advisories
+pub fn attention_icon(overrides: &HashMap<String, String>) -> Icon {
+    icon_override(&["attention"], Icon::Attention, overrides)
+}
+
+/// Frames of the animation while a check is running, a configured icon
+/// replaces the animation
+pub fn checking_icons(overrides: &HashMap<String, String>) -> Vec<Icon> {
+    match overrides.get("checking") {
+        Some(name) => vec![Icon::Custom(name.to_string())],
+        None => (0..3).map(Icon::Checking).collect(),
+    }
+}
+
+/// Icon for a state name as used in the `[icons]` table
+pub fn state_icon(state: &str, overrides: &HashMap<String, String>) -> Option<Icon> {
+    let (states, icon): (&[&str], _) = match state {
+        "clean" => (&["clean"], Icon::Check),
+        "snoozed" => (&["snoozed", "clean"], Icon::Check),
+        "error" => (&["error"], Icon::Cross),
+        "upgrading" => (&["upgrading"], Icon::Upgrading),
+        "attention" => (&["attention"], Icon::Attention),
+        "reboot" => (&["reboot", "clean"], Icon::Reboot),
+        "checking" => (&["checking"], Icon::Checking(0)),
+        "alert" => (&["alert"], Icon::Alert),
+        "critical" | "high" | "medium" | "low" | "unknown" => {
+            let severity = crate::config::parse_severity(state).ok()?;
+            (&[state, "alert"], Icon::Severity(severity))
+        }
+        _ => return None,
+    };
+    Some(icon_override(states, icon, overrides))
+}
+
+fn icon_override(states: &[&str], icon: Icon, overrides: &HashMap<String, String>) -> Icon {
+    states
+        .iter()
+        .find_map(|state| overrides.get(*state))
+        .map(|name| Icon::Custom(name.to_string()))
+        .unwrap_or(icon)
+}
+
+/// Unit of the number of missing updates that is displayed
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountBy {
+    #[default]
+    Package,
+    Advisory,
+}
+
+/// Source of the advisories
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// Run the arch-audit binary
+    #[default]
+    ArchAudit,
+    /// Query the security tracker directly, requires the native feature
+    Native,
+}
+
+pub fn severity_key(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Unknown => "unknown",
+        Severity::Low => "low",
+        Severity::Medium => "medium",
+        Severity::High => "high",
+        Severity::Critical => "critical",
+    }
+}
+
+/// Translated name of a severity, `severity_key` is used in config files and
+/// the json export
+pub fn severity_name(severity: Severity) -> String {
+    match severity {
+        Severity::Unknown => tr("unknown"),
+        Severity::Low => tr("low"),
+        Severity::Medium => tr("medium"),
+        Severity::High => tr("high"),
+        Severity::Critical => tr("critical"),
+    }
+}
+
+fn serialize_time<S: Serializer>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(&humantime::format_rfc3339_seconds(*time))
+}
+
+fn serialize_duration<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+fn serialize_opt_time<S: Serializer>(time: &Option<SystemTime>, s: S) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => s.serialize_some(&humantime::format_rfc3339_seconds(*time).to_string()),
+        None => s.serialize_none(),
+    }
+}
+
+fn serialize_retry<S: Serializer>(retry: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    match retry {
+        Some(duration) => s.serialize_some(&duration.as_secs()),
+        None => s.serialize_none(),
+    }
+}
+
+/// Version of the json export, only increased for incompatible changes
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Result of a check, also the json document exported over http and the
+/// unix socket. Fields may be added, but existing ones keep their meaning.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub schema_version: u32,
+    pub status: Status,
+    /// Cause of a failed check, unset otherwise
+    pub error_kind: Option<ErrorKind>,
+    /// Wall clock time the check finished
+    #[serde(serialize_with = "serialize_time")]
+    pub checked_at: SystemTime,
+    /// How long the check took
+    #[serde(serialize_with = "serialize_duration")]
+    pub duration: Duration,
+    /// Name of the backend that produced this result
+    pub backend: &'static str,
+    /// Set if the result is incomplete, eg. some sources failed
+    pub partial: bool,
+    /// Number of missing updates per severity, the values add up to `total`.
+    /// Acknowledged advisories aren't counted.
+    pub severities: BTreeMap<&'static str, usize>,
+    pub total: usize,
+    /// Number of distinct advisory groups
+    pub advisories: usize,
+    /// Number of distinct vulnerable packages
+    pub packages: usize,
+    /// Number of missing updates with a fixed version in the repos, the
+    /// others are still unpatched, see `[updater] show_unfixed`
+    pub fixable: usize,
+    /// Number of regular updates reported by checkupdates, unset unless
+    /// `[updater] pending_updates` is enabled and it succeeded
+    pub pending: Option<usize>,
+    /// Upgrades that only take effect after a reboot, eg. `kernel`
+    pub reboot_required: Vec<String>,
+    /// Results of the hosts in `[remotes]`, the other fields only describe
+    /// the local system
+    pub remotes: Vec<RemoteResult>,
+    /// Problems that don't affect this result, but should be shown
+    pub warnings: Vec<String>,
+    /// Seconds until a failed check is retried
+    #[serde(serialize_with = "serialize_retry")]
+    pub retry_in: Option<Duration>,
+    /// Wall clock time of the next scheduled check, pacman transactions or
+    /// clicks may trigger one earlier
+    #[serde(serialize_with = "serialize_opt_time")]
+    pub next_check_at: Option<SystemTime>,
+    /// Output of arch-audit if it couldn't be parsed
+    #[serde(skip)]
+    pub raw_output: Option<String>,
+}
+
+impl CheckResult {
+    pub fn new(status: Status, duration: Duration, backend: &'static str) -> Self {
+        let error_kind = match &status {
+            Status::Error(err) => Some(err.kind),
+            Status::MissingUpdates(_) | Status::Offline => None,
+        };
+
+        let mut result = CheckResult {
+            schema_version: SCHEMA_VERSION,
+            status,
+            error_kind,
+            checked_at: SystemTime::now(),
+            duration,
+            backend,
+            partial: false,
+            severities: BTreeMap::new(),
+            total: 0,
+            advisories: 0,
+            packages: 0,
+            fixable: 0,
+            pending: None,
+            reboot_required: Vec::new(),
+            remotes: Vec::new(),
+            warnings: Vec::new(),
+            retry_in: None,
+            next_check_at: None,
+            raw_output: None,
+        };
+        result.count_updates();
+        result
+    }
+
+    /// Mark the acknowledged advisories again after they changed, eg. when
+    /// one is acknowledged from the menu
+    pub fn acknowledge(&mut self, acknowledged: &Acknowledged) {
+        if let Status::MissingUpdates(updates) = &mut self.status {
+            acknowledged.mark(updates);
+        }
+        self.count_updates();
+    }
+
+    fn count_updates(&mut self) {
+        let mut severities = [
+            Severity::Critical,
+            Severity::High,
+            Severity::Medium,
+            Severity::Low,
+            Severity::Unknown,
+        ]
+        .iter()
+        .map(|s| (severity_key(*s), 0))
+        .collect::<BTreeMap<_, _>>();
+
+        let mut total = 0;
+        let mut fixable = 0;
+        let mut advisories = BTreeSet::new();
+        let mut packages = BTreeSet::new();
+        if let Status::MissingUpdates(updates) = &self.status {
+            for update in updates.iter().filter(|update| !update.acknowledged) {
+                *severities.entry(severity_key(update.severity)).or_default() += 1;
+                total += 1;
+                if update.fixable() {
+                    fixable += 1;
+                }
+                advisories.insert(update.avg.as_str());
+                packages.insert(update.pkg.as_str());
+            }
+        }
+        (self.advisories, self.packages) = (advisories.len(), packages.len());
+        self.severities = severities;
+        self.total = total;
+        self.fixable = fixable;
+    }
+
+    /// The status with the missing updates of the remote hosts added, so the
+    /// icon alerts for all of them. Failed remote checks are only shown in
+    /// their submenu.
+    pub fn combined_status(&self) -> Cow<'_, Status> {
+        let remote = self
+            .remotes
+            .iter()
+            .filter_map(|remote| match &remote.status {
+                Status::MissingUpdates(updates) if !updates.is_empty() => Some(updates),
+                _ => None,
+            });
+        match &self.status {
+            Status::MissingUpdates(updates) if remote.clone().next().is_some() => {
+                let mut all = updates.clone();
+                all.extend(remote.flatten().cloned());
+                all.sort_by(|a, b| a.severity.cmp(&b.severity).reverse());
+                Cow::Owned(Status::MissingUpdates(all))
+            }
+            status => Cow::Borrowed(status),
+        }
+    }
+
+    /// Advisory and package of every missing update worth an alert
+    pub fn seen(&self, config: &Config) -> HashSet<(String, String)> {
+        match &self.status {
+            Status::MissingUpdates(updates) => updates
+                .iter()
+                .filter(|update| update.alerts(config))
+                .map(|update| (update.avg.clone(), update.pkg.clone()))
+                .collect(),
+            Status::Error(_) | Status::Offline => HashSet::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Failed to serialize check result")
+    }
+
+    pub fn count(&self, count_by: &CountBy) -> usize {
+        match count_by {
+            CountBy::Package => self.packages,
+            CountBy::Advisory => self.advisories,
+        }
+    }
+
+    pub fn text(&self, count_by: &CountBy) -> Cow<'_, str> {
+        if let (Status::Error(_), Some(retry)) = (&self.status, self.retry_in) {
+            // rounded up, so this never says 0 minutes
+            let minutes = retry.as_secs().div_ceil(60);
+            return Cow::Owned(format!(
+                "{} ({})",
+                self.status.text(),
+                ntr(
+                    "retrying in {} minute",
+                    "retrying in {} minutes",
+                    minutes as usize
+                )
+            ));
+        }
+        let text = match (&self.status, count_by, self.count(count_by)) {
+            (Status::MissingUpdates(_), _, 0) | (Status::Error(_) | Status::Offline, _, _) => {
+                self.status.text()
+            }
+            (_, CountBy::Package, n) => Cow::Owned(ntr(
+                "{} package with missing security updates",
+                "{} packages with missing security updates",
+                n,
+            )),
+            (_, CountBy::Advisory, n) => Cow::Owned(ntr(
+                "{} security advisory affects this system",
+                "{} security advisories affect this system",
+                n,
+            )),
+        };
+        match (&self.status, self.pending) {
+            (Status::MissingUpdates(_), Some(pending)) => Cow::Owned(format!(
+                "{}, {}",
+                text,
+                ntr("{} pending update", "{} pending updates", pending)
+            )),
+            _ => text,
+        }
+    }
+
+    /// Fill a user configured template, `{count}` is replaced with the number
+    /// of vulnerable packages or advisories, `{packages}` and `{advisories}`
+    /// with either of them, `{total}` with the number of missing updates,
+    /// `{fixable}` and `{unfixed}` with those with and without a fix, `{new}`
+    /// with the new ones, `{pending}` with the number of regular updates,
+    /// `{status}` with the default status text, `{severity}` with the highest
+    /// severity and `{critical}`, `{high}` etc. with the number of updates of
+    /// that severity
+    pub fn format(&self, template: &str, count_by: &CountBy) -> String {
+        let severity = self
+            .status
+            .max_severity()
+            .map(severity_key)
+            .unwrap_or("none");
+        let new = match &self.status {
+            Status::MissingUpdates(updates) => updates.iter().filter(|update| update.new).count(),
+            Status::Error(_) | Status::Offline => 0,
+        };
+        let mut text = template
+            .replace("{count}", &self.count(count_by).to_string())
+            .replace("{packages}", &self.packages.to_string())
+            .replace("{advisories}", &self.advisories.to_string())
+            .replace("{total}", &self.total.to_string())
+            .replace("{fixable}", &self.fixable.to_string())
+            .replace("{unfixed}", &(self.total - self.fixable).to_string())
+            .replace("{new}", &new.to_string())
+            .replace("{pending}", &self.pending.unwrap_or(0).to_string())
+            .replace("{severity}", severity);
+        for (severity, count) in &self.severities {
+            text = text.replace(&format!("{{{}}}", severity), &count.to_string());
+        }
+        // last, so the translated text isn't searched for placeholders
+        if text.contains("{status}") {
+            text = text.replace("{status}", &self.text(count_by));
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Update {
     pub severity: Severity,
     pub pkg: String,
+    /// Name of the advisory group, eg. AVG-1234
+    pub avg: String,
+    /// Sync repository providing the package, if known
+    pub repo: Option<String>,
+    /// Installed version of the package, unknown over ssh
+    pub installed: Option<String>,
     pub text: String,
     pub link: String,
+    /// Type of vulnerability, eg. arbitrary code execution
+    pub kind: String,
+    pub avg_status: AvgStatus,
+    /// Version that fixes the advisory, if any
+    pub fixed: Option<String>,
+    /// CVEs of this advisory
+    pub issues: Vec<String>,
+    /// The same CVEs with a link to each of them
+    #[serde(default)]
+    pub cves: Vec<Cve>,
+    /// Set if the advisory wasn't part of the previous result
+    #[serde(default)]
+    pub new: bool,
+    /// Set for foreign packages, eg. from the AUR
+    #[serde(default)]
+    pub aur: bool,
+    /// Acknowledged from the menu, still listed but neither counted nor
+    /// alerting until the installed or fixed version or the CVEs change
+    #[serde(default)]
+    pub acknowledged: bool,
+}
+
+/// A single vulnerability of an advisory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cve {
+    pub id: String,
+    /// Page of the CVE on the security tracker
+    pub link: String,
+    /// Fetched from the security tracker with `[updater] cve_details`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<CveDetails>,
+}
+
+impl Cve {
+    pub fn new(id: &str) -> Self {
+        Cve {
+            id: id.to_string(),
+            link: format!("https://security.archlinux.org/{}", id),
+            details: None,
+        }
+    }
+}
+
+/// What the security tracker knows about a CVE, it doesn't publish CVSS scores
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CveDetails {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub severity: String,
+    /// Either local or remote
+    pub vector: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl CveDetails {
+    /// Eg. `High, remote: arbitrary code execution` followed by the description
+    pub fn summary(&self) -> String {
+        let vector = self.vector.to_lowercase();
+        let mut summary = format!("{}, {}: {}", self.severity, vector, self.kind);
+        if let Some(description) = &self.description {
+            summary.push_str("\n\n");
+            summary.push_str(description.trim());
+        }
+        summary
+    }
+}
+
+impl Update {
+    /// A fixed version is in the repos, upgrading resolves the advisory
+    pub fn fixable(&self) -> bool {
+        self.avg_status == AvgStatus::Fixed && self.fixed.is_some()
+    }
+
+    /// If the advisory changes the icon and the label, triggers
+    /// notifications and hooks and counts as vulnerable for D-Bus, the
+    /// daemon and `check`. Advisories below `min_severity`, or anything but
+    /// critical ones with `critical_only`, are only listed in the menu.
+    pub fn alerts(&self, config: &Config) -> bool {
+        !self.acknowledged
+            && (self.fixable() || config.alert_unfixed)
+            && self.severity >= config.min_severity
+            && (!config.critical_only || self.severity == Severity::Critical)
+    }
+
+    /// Link of the advisory, or the package on the security tracker if the
+    /// advisory group isn't a valid `AVG-<number>`, eg. in an outdated cache
+    pub fn advisory_link(&self) -> Cow<'_, str> {
+        let valid = self
+            .avg
+            .strip_prefix("AVG-")
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()));
+        if valid && (self.link.starts_with("https://") || self.link.starts_with("http://")) {
+            Cow::Borrowed(&self.link)
+        } else {
+            warn!(
+                "Advisory {:?} has no usable link: {:?}",
+                self.avg, self.link
+            );
+            Cow::Owned(format!(
+                "https://security.archlinux.org/package/{}",
+                self.pkg
+            ))
+        }
+    }
+}
+
+/// Only the most recent result is meaningful to the gui, so instead of
+/// queueing results the sender overwrites a single slot and wakes up the
+/// main loop through a channel with room for one notification. If the main
+/// loop is blocked, intermediate results are dropped instead of piling up.
+pub fn result_channel() -> (ResultSender, ResultReceiver) {
+    let latest = Arc::new(Mutex::new(None));
+    let state = UpdaterState::default();
+    #[allow(deprecated)]
+    let (tx, rx) = glib::MainContext::sync_channel(glib::Priority::DEFAULT, 1);
+    (
+        ResultSender {
+            latest: latest.clone(),
+            state: state.clone(),
+            tx,
+        },
+        ResultReceiver { latest, state, rx },
+    )
+}
+
+pub struct ResultSender {
+    latest: Arc<Mutex<Option<CheckResult>>>,
+    state: UpdaterState,
+    tx: glib::SyncSender<()>,
+}
+
+impl ResultSender {
+    /// Tell the receiver a check started, the next result ends it
+    pub fn checking(&self) -> Result<()> {
+        self.state.set(Phase::Checking);
+        self.wakeup()
+    }
+
+    /// Results start the cooldown, checks requested in the meantime wait
+    /// for it to end
+    pub fn send(&self, result: CheckResult) -> Result<()> {
+        if self.latest.lock().unwrap().replace(result).is_some() {
+            debug!("Dropping stale result that was never displayed");
+        }
+        self.state
+            .set(Phase::Cooldown(Instant::now() + CHECK_COOLDOWN));
+        self.wakeup()
+    }
+
+    fn wakeup(&self) -> Result<()> {
+        match self.tx.try_send(()) {
+            // if the channel is full a wakeup is already pending
+            Ok(_) | Err(mpsc::TrySendError::Full(_)) => Ok(()),
+            Err(mpsc::TrySendError::Disconnected(_)) => bail!("Result receiver has been dropped"),
+        }
+    }
+}
+
+pub struct ResultReceiver {
+    latest: Arc<Mutex<Option<CheckResult>>>,
+    state: UpdaterState,
+    rx: glib::Receiver<()>,
+}
+
+impl ResultReceiver {
+    pub fn state(&self) -> UpdaterState {
+        self.state.clone()
+    }
+
+    pub fn attach<F: FnMut(CheckResult) + 'static>(self, func: F) {
+        self.attach_with_checking(func, |_| ());
+    }
+
+    /// Like `attach`, `checking` is called on every wakeup with whether a
+    /// check is running, after the result if there's one
+    pub fn attach_with_checking<F, C>(self, mut func: F, mut checking: C)
+    where
+        F: FnMut(CheckResult) + 'static,
+        C: FnMut(bool) + 'static,
+    {
+        let latest = self.latest;
+        let state = self.state;
+        self.rx.attach(None, move |_| {
+            let result = latest.lock().unwrap().take();
+            if let Some(result) = result {
+                func(result);
+            }
+            checking(state.phase() == Phase::Checking);
+            glib::ControlFlow::Continue
+        });
+    }
+}
+
+/// What the updater thread is doing, as far as clicks are concerned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Phase {
+    #[default]
+    Idle,
+    Checking,
+    /// A result was just sent, a requested check waits until this instant
+    Cooldown(Instant),
+}
+
+#[derive(Debug, Default)]
+struct PhaseState {
+    phase: Phase,
+    /// Set by a click until the check it asked for starts
+    requested: bool,
+    /// Shutting down, nothing waits for the cooldown anymore
+    closed: bool,
+}
+
+/// Shared between the updater thread and the gui, so a click while a check
+/// runs or is already requested doesn't queue another one
+#[derive(Debug, Clone, Default)]
+pub struct UpdaterState {
+    inner: Arc<(Mutex<PhaseState>, Condvar)>,
+}
+
+impl UpdaterState {
+    pub fn phase(&self) -> Phase {
+        match self.inner.0.lock().unwrap().phase {
+            Phase::Cooldown(until) if until <= Instant::now() => Phase::Idle,
+            phase => phase,
+        }
+    }
+
+    fn set(&self, phase: Phase) {
+        let (lock, condvar) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        state.phase = phase;
+        state.requested = false;
+        condvar.notify_all();
+    }
+
+    /// Claim a check for a click, false if the running or an already
+    /// requested check answers it anyway
+    pub fn request_check(&self) -> bool {
+        let (lock, condvar) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        if state.phase == Phase::Checking || state.requested {
+            debug!("A check is already running or requested, ignoring the click");
+            return false;
+        }
+        state.requested = true;
+        condvar.notify_all();
+        true
+    }
+
+    /// Block the updater thread until the cooldown after the last result
+    /// ended, returns early once the state is closed
+    pub fn wait_cooldown(&self) {
+        let (lock, condvar) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        if let Phase::Cooldown(until) = state.phase {
+            if until > Instant::now() {
+                debug!("Waiting for the cooldown after the last check");
+            }
+        }
+        while !state.closed {
+            let Phase::Cooldown(until) = state.phase else {
+                break;
+            };
+            let Some(remaining) = until.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            state = condvar.wait_timeout(state, remaining).unwrap().0;
+        }
+        if matches!(state.phase, Phase::Cooldown(_)) {
+            state.phase = Phase::Idle;
+        }
+    }
+
+    /// Wake up the updater thread on quit
+    pub fn close(&self) {
+        let (lock, condvar) = &*self.inner;
+        lock.lock().unwrap().closed = true;
+        condvar.notify_all();
+    }
+}
+
+/// Shared between the updater thread and the quit path so an in-flight
+/// arch-audit process can be killed instead of waiting for it to finish
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(child) = self.child.lock().unwrap().as_mut() {
+            info!("Killing running arch-audit process");
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// How long alerts are snoozed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnoozeUntil {
+    /// Until the pacman database changes
+    Upgrade,
+    Time(SystemTime),
+}
+
+/// Set from the gui to hide alerts for a while. A snooze until the next
+/// upgrade is cleared by the updater on the next inotify event, which then
+/// checks again.
+///
+/// The gui persists the snooze in the data directory, so it survives a
+/// restart.
+#[derive(Debug, Clone, Default)]
+pub struct Snooze {
+    until: Arc<Mutex<Option<SnoozeUntil>>>,
+    path: Option<PathBuf>,
+}
+
+impl Snooze {
+    /// Restore a snooze from a previous run, expired ones are ignored
+    pub fn load() -> Self {
+        let Some(path) = dirs_next::data_local_dir().map(|dir| dir.join(SNOOZE_FILE)) else {
+            return Snooze::default();
+        };
+        let until = match fs::read_to_string(&path) {
+            Ok(s) => match s.trim() {
+                "upgrade" => Some(SnoozeUntil::Upgrade),
+                s => s
+                    .parse()
+                    .ok()
+                    .map(|secs| SnoozeUntil::Time(UNIX_EPOCH + Duration::from_secs(secs))),
+            },
+            Err(_) => None,
+        };
+        let snooze = Snooze {
+            until: Arc::new(Mutex::new(until)),
+            path: Some(path),
+        };
+        if snooze.is_snoozed() {
+            info!("Restored snooze from the last run: {:?}", until);
+        }
+        snooze
+    }
+
+    pub fn snooze(&self, until: SnoozeUntil) {
+        *self.until.lock().unwrap() = Some(until);
+        self.save(Some(until));
+    }
+
+    /// Returns true if we were snoozed until the next upgrade
+    pub fn clear_upgrade(&self) -> bool {
+        let mut current = self.until.lock().unwrap();
+        if *current != Some(SnoozeUntil::Upgrade) {
+            return false;
+        }
+        *current = None;
+        self.save(None);
+        true
+    }
+
+    pub fn is_snoozed(&self) -> bool {
+        match *self.until.lock().unwrap() {
+            Some(SnoozeUntil::Upgrade) => true,
+            Some(SnoozeUntil::Time(time)) => time > SystemTime::now(),
+            None => false,
+        }
+    }
+
+    /// Time until a snooze for a fixed duration ends
+    pub fn remaining(&self) -> Option<Duration> {
+        match *self.until.lock().unwrap() {
+            Some(SnoozeUntil::Time(time)) => time.duration_since(SystemTime::now()).ok(),
+            _ => None,
+        }
+    }
+
+    fn save(&self, until: Option<SnoozeUntil>) {
+        let Some(path) = &self.path else { return };
+        let result = match until {
+            Some(until) => {
+                let s = match until {
+                    SnoozeUntil::Upgrade => "upgrade".to_string(),
+                    SnoozeUntil::Time(time) => time
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0)
+                        .to_string(),
+                };
+                path.parent()
+                    .map(fs::create_dir_all)
+                    .transpose()
+                    .and_then(|_| fs::write(path, s + "\n"))
+            }
+            None => fs::remove_file(path),
+        };
+        if let Err(err) = result {
+            warn!("Failed to save snooze to {:?}: {:#}", path, err);
+        }
+    }
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buf).ok();
+        }
+        buf
+    })
+}
+
+/// Like `Command::output`, but the child is killed if the check is cancelled
+/// or it takes longer than `timeout`
+fn run_cancelable(
+    cmd: &mut Command,
+    cancel: &CancelToken,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    let started = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // drain the pipes in the background so the child can't block on a full pipe
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    *cancel.child.lock().unwrap() = Some(child);
+    let status = loop {
+        if cancel.is_cancelled() {
+            // cancel() may have raced with us storing the child
+            cancel.cancel();
+            cancel.child.lock().unwrap().take();
+            bail!("Check has been cancelled");
+        }
+
+        let mut slot = cancel.child.lock().unwrap();
+        if let Some(status) = slot.as_mut().map(|c| c.try_wait()).transpose()?.flatten() {
+            slot.take();
+            break status;
+        }
+        if let Some(timeout) = timeout.filter(|timeout| started.elapsed() >= *timeout) {
+            if let Some(mut child) = slot.take() {
+                let timeout = humantime::format_duration(timeout);
+                warn!("Killing {:?} after {}", cmd.get_program(), timeout);
+                child.kill().ok();
+                child.wait().ok();
+            }
+            bail!("timed out after {}", humantime::format_duration(timeout));
+        }
+        drop(slot);
+
+        thread::sleep(CHILD_POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Timeout of the commands of a check, `check_timeout = 0` disables it
+fn check_timeout(config: &Config) -> Option<Duration> {
+    Some(config.check_timeout).filter(|timeout| !timeout.is_zero())
 }
 
-pub fn check_for_updates() -> Result<Vec<Update>> {
-    // Select the arch-audit binary
-    let bin = env::var("ARCH_AUDIT_BIN");
-    let bin = bin.as_ref().map(|x| x.as_str()).unwrap_or("arch-audit");
-    let dbpath = env::var("ARCH_AUDIT_DBPATH");
-    let dbpath = dbpath
-        .as_ref()
-        .map(|x| x.as_str())
-        .unwrap_or("/var/lib/pacman");
-
-    // Run the arch-audit binary
-    let output = Command::new(bin)
-        .args(&["-u", "--json"])
-        .args(&["--dbpath", dbpath])
+/// Number of regular updates reported by checkupdates of pacman-contrib. It
+/// syncs a temporary copy of the databases, so the real ones aren't touched.
+pub fn pending_updates(config: &Config, cancel: &CancelToken) -> Result<usize> {
+    let output = run_cancelable(
+        &mut Command::new("checkupdates"),
+        cancel,
+        check_timeout(config),
+    )
+    .context("Failed to run checkupdates, is pacman-contrib installed?")?;
+    // exits with 2 if there are no updates
+    match output.status.code() {
+        Some(0) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Ok(stdout
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .count())
+        }
+        Some(2) => Ok(0),
+        _ => bail!(
+            "checkupdates failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// Upgrades of the local system that need a reboot, remote hosts aren't
+/// checked
+pub fn reboot_required(config: &Config) -> Vec<String> {
+    if !config.detect_reboot || config.ssh_target.is_some() {
+        return Vec::new();
+    }
+    match reboot::required(&pacman::dbpath()) {
+        Ok(reasons) => reasons,
+        Err(err) => {
+            warn!("Failed to check if a reboot is required: {:#}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// Result of a host in `[remotes]`
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteResult {
+    pub name: String,
+    pub target: String,
+    pub status: Status,
+}
+
+/// Run arch-audit on every remote host, one after the other
+pub fn check_remotes(config: &Config, cancel: &CancelToken) -> Vec<RemoteResult> {
+    let mut results = Vec::new();
+    for remote in &config.remotes {
+        if cancel.is_cancelled() {
+            break;
+        }
+        info!("Checking remote host {:?} ({})", remote.name, remote.target);
+        let status = match check_for_updates(&config.for_remote(remote), cancel) {
+            Ok(updates) => Status::MissingUpdates(updates),
+            Err(err) => {
+                let err = CheckError::new(&err);
+                warn!(
+                    "Check of {:?} failed ({:?}): {}",
+                    remote.name, err.kind, err.message
+                );
+                Status::Error(err)
+            }
+        };
+        results.push(RemoteResult {
+            name: remote.name.clone(),
+            target: remote.target.clone(),
+            status,
+        });
+    }
+    results
+}
+
+/// Select the arch-audit binary, `$ARCH_AUDIT_BIN` takes precedence over
+/// the config
+pub fn arch_audit_bin(config: &Config) -> String {
+    env::var("ARCH_AUDIT_BIN")
+        .ok()
+        .or_else(|| config.arch_audit_bin.clone())
+        .unwrap_or_else(|| "arch-audit".to_string())
+}
+
+/// Parse the output of `arch-audit --version`, eg. `arch-audit 0.1.20`
+pub fn parse_version(output: &str) -> Option<(u64, u64, u64)> {
+    let version = output.split_whitespace().last()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u64>().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+pub fn arch_audit_version(config: &Config) -> Result<(u64, u64, u64)> {
+    let output = Command::new(arch_audit_bin(config))
+        .arg("--version")
         .output()
         .context("Failed to run arch-audit")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_version(&stdout)
+        .with_context(|| anyhow!("Failed to parse arch-audit version: {:?}", stdout.trim()))
+}
 
-    info!("arch-audit exited: {}", output.status);
+/// Returns a warning if the installed arch-audit is older than what we
+/// know to work
+pub fn check_arch_audit_version(config: &Config) -> Option<String> {
+    match arch_audit_version(config) {
+        Ok(version) if version < MIN_ARCH_AUDIT_VERSION => {
+            let (major, minor, patch) = MIN_ARCH_AUDIT_VERSION;
+            let msg = format!(
+                "arch-audit {}.{}.{} is outdated, please upgrade to {}.{}.{} or newer",
+                version.0, version.1, version.2, major, minor, patch
+            );
+            warn!("{}", msg);
+            Some(msg)
+        }
+        Ok(version) => {
+            debug!("Detected arch-audit version: {:?}", version);
+            None
+        }
+        Err(err) => {
+            warn!("Failed to detect arch-audit version: {:#}", err);
+            None
+        }
+    }
+}
 
-    if output.status.success() {
-        // if arch-audit didn't indicate an error, parse the output as json
-        let affected: Vec<Avg> = serde_json::from_slice(&output.stdout)
-            .context("Failed to parse arch-audit json output")?;
+fn is_io_error(err: &Error, kind: std::io::ErrorKind) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == kind)
+}
 
-        let mut updates = affected
-            .into_iter()
-            .flat_map(|avg| {
-                avg.packages
-                    .iter()
-                    .map(|pkg| {
-                        let text = format!("{}: {} ({})", avg.severity, pkg, avg.kind);
-                        Update {
-                            severity: avg.severity,
-                            pkg: pkg.to_string(),
-                            text,
-                            link: format!("https://security.archlinux.org/{}", avg.name),
-                        }
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
+/// Messages of curl and ssh if the network is the problem
+const NETWORK_ERRORS: &[&str] = &[
+    "Could not resolve host",
+    "Failed to connect",
+    "Connection refused",
+    "Connection timed out",
+    "Timeout was reached",
+    "Network is unreachable",
+];
 
-        updates.sort_by(|a, b| {
-            a.severity
-                .cmp(&b.severity)
-                .reverse()
-                .then(a.pkg.cmp(&b.pkg))
-        });
+/// arch-audit succeeded, but its output couldn't be parsed. The raw output
+/// is kept so it can be attached to bug reports.
+#[derive(Debug)]
+pub struct InvalidOutput {
+    pub raw: String,
+}
+
+impl fmt::Display for InvalidOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Failed to parse arch-audit json output")
+    }
+}
+
+pub fn check_for_updates(config: &Config, cancel: &CancelToken) -> Result<Vec<Update>> {
+    let dbpath = pacman::dbpath();
+    let dbpath = dbpath.as_str();
+
+    let affected = match config.backend {
+        #[cfg(feature = "native")]
+        BackendKind::Native => {
+            crate::avg::affected(dbpath, config.show_unfixed, config.proxy.as_deref())?
+        }
+        _ => run_arch_audit(config, cancel, dbpath)?,
+    };
+
+    // foreign packages are never in the sync repositories arch-audit looks at
+    #[cfg(feature = "native")]
+    let foreign = if config.aur {
+        match crate::avg::affected_foreign(dbpath, config.show_unfixed, config.proxy.as_deref()) {
+            Ok(foreign) => foreign,
+            Err(err) => {
+                warn!("Failed to check foreign packages: {:#}", err);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    #[cfg(not(feature = "native"))]
+    let foreign = Vec::new();
+
+    let affected = affected.into_iter().map(|avg| (avg, false));
+    let foreign = foreign.into_iter().map(|avg| (avg, true));
+    let mut updates = affected
+        .chain(foreign)
+        .flat_map(|(avg, aur)| {
+            avg.packages
+                .iter()
+                .map(|pkg| {
+                    let severity = config.map_severity(pkg, avg.severity);
+                    let text = if aur {
+                        format!("{}: {} ({}, AUR)", severity, pkg, avg.kind)
+                    } else {
+                        format!("{}: {} ({})", severity, pkg, avg.kind)
+                    };
+                    Update {
+                        severity,
+                        pkg: pkg.to_string(),
+                        avg: avg.name.to_string(),
+                        repo: None,
+                        installed: None,
+                        text,
+                        link: format!("https://security.archlinux.org/{}", avg.name),
+                        kind: avg.kind.to_string(),
+                        avg_status: avg.status,
+                        fixed: avg.fixed.clone(),
+                        issues: avg.issues.clone(),
+                        cves: avg.issues.iter().map(|id| Cve::new(id)).collect(),
+                        new: false,
+                        aur,
+                        acknowledged: false,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    updates.retain(|u| !config.ignore.contains(&u.pkg));
 
-        if !updates.is_empty() {
-            info!("Missing security updates: {:?}", output);
+    // the local database doesn't know about remote packages
+    if config.ssh_target.is_none() {
+        match pacman::installed_versions(dbpath, updates.iter().map(|u| u.pkg.as_str())) {
+            Ok(versions) => {
+                for update in &mut updates {
+                    update.installed = versions.get(&update.pkg).cloned();
+                }
+            }
+            Err(err) => warn!("Failed to detect installed versions: {:#}", err),
+        }
+        match pacman::package_repos(dbpath, updates.iter().map(|u| u.pkg.as_str())) {
+            Ok(repos) => {
+                for update in &mut updates {
+                    update.repo = repos.get(&update.pkg).cloned();
+                }
+                // packages that aren't in any sync repository are dropped too,
+                // except the foreign ones that were asked for explicitly
+                if let Some(allowed) = &config.repos {
+                    updates
+                        .retain(|u| u.aur || u.repo.as_ref().is_some_and(|r| allowed.contains(r)));
+                }
+            }
+            // without them the allowlist can't be applied, reporting the
+            // packages of every repository would be wrong
+            Err(err) if config.repos.is_some() => {
+                return Err(err.context("Failed to detect repositories for [updater] repos"));
+            }
+            Err(err) => warn!("Failed to detect package repositories: {:#}", err),
         }
+    }
+
+    #[cfg(feature = "native")]
+    if config.cve_details {
+        crate::cve::enrich(&mut updates, config.proxy.as_deref(), cancel);
+    }
+
+    updates.sort_by(|a, b| {
+        a.severity
+            .cmp(&b.severity)
+            .reverse()
+            .then(a.pkg.cmp(&b.pkg))
+    });
+
+    if !updates.is_empty() {
+        info!("Missing security updates: {:?}", updates);
+    }
 
-        Ok(updates)
+    Ok(updates)
+}
+
+/// Name of the configured backend, as reported in the check result
+pub fn backend_name(config: &Config) -> &'static str {
+    if config.mock.is_some() {
+        return "mock";
+    }
+    match (&config.backend, &config.ssh_target) {
+        (BackendKind::Native, _) => "native",
+        (BackendKind::ArchAudit, Some(_)) => "arch-audit over ssh",
+        (BackendKind::ArchAudit, None) => "arch-audit",
+    }
+}
+
+/// Without `-u` arch-audit also reports advisories that have no fix yet
+fn arch_audit_flags(config: &Config) -> &'static [&'static str] {
+    if config.show_unfixed {
+        &["--json"]
+    } else {
+        &["-u", "--json"]
+    }
+}
+
+/// Run arch-audit and parse the advisories it reports
+fn run_arch_audit(config: &Config, cancel: &CancelToken, dbpath: &str) -> Result<Vec<Avg>> {
+    let bin = arch_audit_bin(config);
+
+    // Run the arch-audit binary, optionally through a wrapper like sudo or
+    // on a remote host, the remote side uses its default dbpath
+    let mut cmd = if let Some(target) = &config.ssh_target {
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-o", "BatchMode=yes", "--", target, "arch-audit"]);
+        cmd.args(arch_audit_flags(config))
+            .args(&config.arch_audit_args);
+        cmd
+    } else {
+        let mut cmd = match config.wrapper.split_first() {
+            Some((wrapper, args)) => {
+                let mut cmd = Command::new(wrapper);
+                cmd.args(args).arg(&bin);
+                cmd
+            }
+            None => Command::new(&bin),
+        };
+        cmd.args(arch_audit_flags(config))
+            .args(["--dbpath", dbpath]);
+        // passed explicitly, wrappers like sudo drop the proxy variables
+        if let Some(proxy) = &config.proxy {
+            cmd.args(["--proxy", proxy]);
+        }
+        cmd.args(&config.arch_audit_args);
+        cmd
+    };
+    let output = match run_cancelable(&mut cmd, cancel, check_timeout(config)) {
+        Ok(output) => output,
+        Err(err) if is_io_error(&err, std::io::ErrorKind::PermissionDenied) => {
+            return Err(known_error(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Permission denied while running {:?}, check the permissions of arch-audit and the wrapper command",
+                    bin
+                ),
+            ));
+        }
+        Err(err) if is_io_error(&err, std::io::ErrorKind::NotFound) => {
+            let program = cmd.get_program().to_string_lossy().into_owned();
+            return Err(known_error(
+                ErrorKind::MissingBinary,
+                format!("Failed to run arch-audit: {:?} not found", program),
+            ));
+        }
+        Err(err) => return Err(err.context("Failed to run arch-audit")),
+    };
+
+    info!("arch-audit exited: {}", output.status);
+
+    if output.status.success() {
+        // if arch-audit didn't indicate an error, parse the output as json
+        serde_json::from_slice(&output.stdout).with_context(|| InvalidOutput {
+            raw: String::from_utf8_lossy(&output.stdout).into_owned(),
+        })
     } else {
         let err = String::from_utf8_lossy(&output.stderr);
         let err = err.trim();
+        // ssh exits with 255 if the connection failed
+        if let (Some(target), Some(255)) = (&config.ssh_target, output.status.code()) {
+            return Err(known_error(
+                ErrorKind::Network,
+                format!("Failed to connect to {:?} over ssh: {}", target, err),
+            ));
+        }
+        if err.contains("Permission denied") {
+            return Err(known_error(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Permission denied, arch-audit needs read access to {:?}: {}",
+                    dbpath, err
+                ),
+            ));
+        }
+        if NETWORK_ERRORS.iter().any(|msg| err.contains(msg)) {
+            return Err(known_error(ErrorKind::Network, err.to_string()));
+        }
         bail!("{}", err);
     }
 }
 
-pub fn background(update_rx: mpsc::Receiver<Event>, result_tx: glib::Sender<Status>) {
-    loop {
+/// Block until pacman released its database lock, or until the timeout
+/// expired. Returns false if pacman still holds the lock after that, a
+/// timeout of 0 doesn't wait and always returns true.
+pub fn wait_for_pacman_lock(lock: &Path, timeout: Duration, cancel: &CancelToken) -> bool {
+    if timeout.is_zero() {
+        return true;
+    }
+    let start = Instant::now();
+    let mut logged = false;
+
+    while lock.exists() {
+        if start.elapsed() >= timeout || cancel.is_cancelled() {
+            return false;
+        }
+        if !logged {
+            info!("Pacman is running ({:?} exists), deferring check", lock);
+            logged = true;
+        }
+        thread::sleep(LOCK_POLL_INTERVAL);
+    }
+
+    if logged {
+        info!("Pacman lock has been released");
+    }
+    true
+}
+
+/// What woke up the updater
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trigger {
+    /// The schedule, a pacman transaction or the network coming back
+    Automatic,
+    /// A click in the menu or from another client
+    Manual,
+}
+
+/// Check for updates until shutdown
+///
+/// `seen` are the advisories of the last result before a restart, updates
+/// that aren't part of the previous result are marked as new. Updates in
+/// `acknowledged` are marked in every result and never new.
+#[allow(clippy::too_many_arguments)]
+pub fn background(
+    mut config: Config,
+    cancel: CancelToken,
+    snooze: Snooze,
+    acknowledged: Acknowledged,
+    mut seen: HashSet<(String, String)>,
+    mut online: bool,
+    update_rx: mpsc::Receiver<Event>,
+    result_tx: ResultSender,
+) {
+    let mut last_error = None::<String>;
+    let mut failures = 0;
+    let mut inotify_failed = None::<String>;
+    let backend = backend_name(&config);
+    let mut trigger = Trigger::Automatic;
+    // without a result yet any pacman transaction is worth a check
+    if !config.check_on_startup {
+        info!("Not checking on startup, waiting for the first scheduled check");
+        let delay = next_check_delay(&config, None);
+        let Some(next) = sleep_until_next_check(
+            &mut config,
+            &update_rx,
+            &snooze,
+            true,
+            delay,
+            &mut inotify_failed,
+            &mut online,
+        ) else {
+            return;
+        };
+        trigger = next;
+    }
+    while !cancel.is_cancelled() {
+        // a check would only fail, wait until the network is back instead
+        if !online {
+            info!("Offline, waiting for the network before checking");
+            let mut msg = CheckResult::new(Status::Offline, Duration::ZERO, backend);
+            msg.warnings.extend(inotify_failed.clone());
+            if let Err(err) = result_tx.send(msg) {
+                warn!("Failed to send result to gui: {:#}", err);
+            }
+            let delay = config.check_interval;
+            let Some(next) = sleep_until_next_check(
+                &mut config,
+                &update_rx,
+                &snooze,
+                false,
+                delay,
+                &mut inotify_failed,
+                &mut online,
+            ) else {
+                return;
+            };
+            trigger = next;
+        }
+
+        // clicks always check, the policy only holds back automatic checks
+        if trigger == Trigger::Automatic {
+            if let Some(reason) = postpone_reason(&config) {
+                info!(
+                    "Postponing the check for {}, {}",
+                    humantime::format_duration(POSTPONE_DELAY),
+                    reason
+                );
+                let Some(next) = sleep_until_next_check(
+                    &mut config,
+                    &update_rx,
+                    &snooze,
+                    false,
+                    POSTPONE_DELAY,
+                    &mut inotify_failed,
+                    &mut online,
+                ) else {
+                    return;
+                };
+                trigger = next;
+                continue;
+            }
+        }
+
+        // the local pacman lock is irrelevant for remote hosts and mock results
+        if config.ssh_target.is_none()
+            && config.mock.is_none()
+            && !wait_for_pacman_lock(&config.pacman_lock, config.pacman_lock_timeout, &cancel)
+        {
+            warn!(
+                "Pacman lock still present after {}, checking anyway",
+                humantime::format_duration(config.pacman_lock_timeout)
+            );
+        }
+
+        result_tx.state.wait_cooldown();
+        if cancel.is_cancelled() {
+            break;
+        }
         info!("Checking for security updates...");
-        let arch_audit_result = check_for_updates();
-        let (needs_updates, msg) = match arch_audit_result {
-            Ok(updates) => (!updates.is_empty(), Status::MissingUpdates(updates)),
-            Err(e) => (true, Status::Error(format!("{:#}", e))),
+        if let Err(err) = result_tx.checking() {
+            warn!("Failed to notify gui about the check: {:#}", err);
+        }
+        let started = Instant::now();
+        let arch_audit_result = backend::for_config(&config).check(&cancel);
+        if cancel.is_cancelled() {
+            break;
+        }
+        let raw_output = arch_audit_result
+            .as_ref()
+            .err()
+            .and_then(|err| err.downcast_ref::<InvalidOutput>())
+            .map(|output| output.raw.clone());
+        let (needs_updates, status) = match arch_audit_result {
+            Ok(Status::MissingUpdates(mut updates)) => {
+                last_error = None;
+                failures = 0;
+                // only successful checks replace the known advisories. Advisories
+                // without a fix aren't remembered, so they're new once it's released.
+                acknowledged.prune(&updates);
+                acknowledged.mark(&mut updates);
+                for update in &mut updates {
+                    update.new = update.alerts(&config)
+                        && !seen.contains(&(update.avg.clone(), update.pkg.clone()));
+                }
+                seen = updates
+                    .iter()
+                    .filter(|update| update.alerts(&config))
+                    .map(|update| (update.avg.clone(), update.pkg.clone()))
+                    .collect();
+                (!updates.is_empty(), Status::MissingUpdates(updates))
+            }
+            Ok(status) => (false, status),
+            Err(e) => {
+                let err = CheckError::new(&e);
+                // don't spam the log if the same misconfiguration fails every check
+                if last_error.as_ref() == Some(&err.message) {
+                    debug!("Check failed again with the same error: {}", err.message);
+                } else {
+                    warn!("Check failed ({:?}): {}", err.kind, err.message);
+                }
+                last_error = Some(err.message.clone());
+                failures += 1;
+                (true, Status::Error(err))
+            }
+        };
+        let duration = started.elapsed();
+        let pending = match &status {
+            Status::MissingUpdates(_) if config.pending_updates => {
+                pending_updates(&config, &cancel)
+                    .map_err(|err| warn!("Failed to count pending updates: {:#}", err))
+                    .ok()
+            }
+            _ => None,
         };
-        result_tx.send(msg).ok();
+        if cancel.is_cancelled() {
+            break;
+        }
+        let mut msg = CheckResult::new(status, duration, backend);
+        msg.pending = pending;
+        msg.reboot_required = reboot_required(&config);
+        msg.remotes = check_remotes(&config, &cancel);
+        if cancel.is_cancelled() {
+            break;
+        }
+        msg.warnings.extend(inotify_failed.clone());
+        msg.raw_output = raw_output;
+        msg.retry_in = retry_delay(&config, failures);
+        if let Some(retry) = msg.retry_in {
+            info!("Retrying in {}", humantime::format_duration(retry));
+        }
+        let delay = next_check_delay(&config, msg.retry_in);
+        msg.next_check_at = Some(SystemTime::now() + delay);
+        if let Err(err) = result_tx.send(msg) {
+            warn!("Failed to send result to gui: {:#}", err);
+        }
         info!("Finished checking for security updates");
 
+        let Some(next) = sleep_until_next_check(
+            &mut config,
+            &update_rx,
+            &snooze,
+            needs_updates,
+            delay,
+            &mut inotify_failed,
+            &mut online,
+        ) else {
+            return;
+        };
+        trigger = next;
+    }
+}
+
+/// Automatic checks are skipped on metered connections and in power saver
+/// mode, if configured
+fn postpone_reason(config: &Config) -> Option<&'static str> {
+    if config.skip_metered && crate::dbus::is_metered() {
+        Some("the connection is metered")
+    } else if config.skip_power_saver && crate::dbus::is_power_saver() {
+        Some("power saver mode is active")
+    } else {
+        None
+    }
+}
+
+/// Delay before the next retry after `failures` consecutive failed checks,
+/// if retrying is enabled
+fn retry_delay(config: &Config, failures: u32) -> Option<Duration> {
+    if failures == 0 || config.retry_delay.is_zero() {
+        return None;
+    }
+    let factor = 2u32.saturating_pow(failures - 1);
+    let delay = config
+        .retry_delay
+        .saturating_mul(factor)
+        .min(config.retry_max)
+        .min(config.check_interval);
+    Some(delay)
+}
+
+/// Events that queued up while checking, duplicates are dropped so a busy
+/// pacman transaction doesn't trigger one check after another. Clicks are
+/// dropped too since the check that just finished answers them.
+fn queued_events(update_rx: &mpsc::Receiver<Event>) -> VecDeque<Event> {
+    let mut queued = VecDeque::new();
+    for event in update_rx.try_iter() {
+        match event {
+            Event::Shutdown => queued.push_front(event),
+            Event::Click => debug!("Ignoring click that arrived during the check"),
+            Event::Inotify if queued.iter().any(|e| matches!(e, Event::Inotify)) => (),
+            // only the latest config and network state matter
+            Event::Reload(_) => {
+                queued.retain(|e| !matches!(e, Event::Reload(_)));
+                queued.push_back(event);
+            }
+            Event::Network(_) => {
+                queued.retain(|e| !matches!(e, Event::Network(_)));
+                queued.push_back(event);
+            }
+            event => queued.push_back(event),
+        }
+    }
+    queued
+}
+
+/// Only the gui reloads its config, in daemon mode the event ends up here
+fn config_changed() {
+    info!("Restart arch-audit-gtk to apply the changed config");
+}
+
+/// Wait until no events arrived for the debounce window, so all changes of a
+/// transaction result in a single check. Returns `Manual` if a click or a
+/// reload ended the wait, and `None` on shutdown.
+fn debounce(
+    config: &mut Config,
+    update_rx: &mpsc::Receiver<Event>,
+    inotify_failed: &mut Option<String>,
+    online: &mut bool,
+) -> Option<Trigger> {
+    if config.debounce.is_zero() {
+        return Some(Trigger::Automatic);
+    }
+    let mut coalesced = 0;
+    loop {
+        match update_rx.recv_timeout(config.debounce) {
+            Ok(Event::Inotify) => coalesced += 1,
+            Ok(Event::InotifyFailed(err)) => *inotify_failed = Some(err),
+            Ok(Event::ConfigChanged) => config_changed(),
+            Ok(Event::Network(up)) => *online = up,
+            // the user doesn't want to wait, and the click has to be answered
+            // even if the pacman events alone wouldn't cause a check
+            Ok(Event::Click) => return Some(Trigger::Manual),
+            Ok(Event::Reload(new)) => {
+                info!("Config has changed, checking for updates");
+                *config = *new;
+                return Some(Trigger::Manual);
+            }
+            Ok(Event::Shutdown) | Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+            Err(mpsc::RecvTimeoutError::Timeout) => break,
+        }
+    }
+    if coalesced > 0 {
+        debug!("Coalesced {} more pacman events", coalesced);
+    }
+    Some(Trigger::Automatic)
+}
+
+/// Time until the next scheduled check, the check interval with a random
+/// jitter or `retry` after a failed check
+fn next_check_delay(config: &Config, retry: Option<Duration>) -> Duration {
+    retry.unwrap_or_else(|| {
         let mut rng = rand::thread_rng();
-        let jitter = rng.gen_range(0..CHECK_JITTER);
-        let delay = Duration::from_secs(CHECK_INTERVAL + jitter);
-
-        let start = Instant::now();
-        while let Some(remaining) = delay.checked_sub(start.elapsed()) {
-            info!("Sleeping for {}", humantime::format_duration(remaining));
-            if let Ok(event) = update_rx.recv_timeout(remaining) {
-                debug!("Received event: {:?}", event);
-                match event {
-                    Event::Click => break,
-                    Event::Inotify => {
-                        if needs_updates {
-                            break;
-                        } else {
-                            info!("There are no missing security updates so we aren't checking if we're missing any");
-                        }
-                    }
+        let jitter = if config.check_jitter > 0.0 {
+            rng.gen_range(-config.check_jitter..=config.check_jitter)
+        } else {
+            0.0
+        };
+        config.check_interval.mul_f64(1.0 + jitter)
+    })
+}
+
+/// Sleeps for `delay`, unless an event triggers a check earlier. Returns
+/// what triggered the check, or None if the updater should shut down.
+fn sleep_until_next_check(
+    config: &mut Config,
+    update_rx: &mpsc::Receiver<Event>,
+    snooze: &Snooze,
+    needs_updates: bool,
+    delay: Duration,
+    inotify_failed: &mut Option<String>,
+    online: &mut bool,
+) -> Option<Trigger> {
+    let mut queued = queued_events(update_rx);
+    let start = Instant::now();
+    while let Some(remaining) = delay.checked_sub(start.elapsed()) {
+        let event = match queued.pop_front() {
+            Some(event) => event,
+            None => {
+                info!("Sleeping for {}", humantime::format_duration(remaining));
+                match update_rx.recv_timeout(remaining) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return None,
                 }
-            } else {
+            }
+        };
+
+        debug!("Received event: {:?}", event);
+        match event {
+            Event::Shutdown => return None,
+            Event::Click => return Some(Trigger::Manual),
+            Event::Reload(new) => {
+                info!("Config has changed, checking for updates");
+                *config = *new;
+                return Some(Trigger::Manual);
+            }
+            Event::ConfigChanged => config_changed(),
+            Event::Network(true) if !*online => {
+                info!("Network is back, checking for updates");
+                *online = true;
+                break;
+            }
+            Event::Network(up) => *online = up,
+            Event::InotifyFailed(err) => {
+                // check again so the warning is shown with a fresh result
+                *inotify_failed = Some(err);
                 break;
             }
+            Event::Inotify => {
+                match debounce(config, update_rx, inotify_failed, online)? {
+                    Trigger::Manual => return Some(Trigger::Manual),
+                    Trigger::Automatic => (),
+                }
+                if snooze.clear_upgrade() {
+                    info!("Pacman database has changed, ending snooze");
+                    break;
+                } else if needs_updates {
+                    break;
+                } else if config.detect_reboot && config.ssh_target.is_none() {
+                    // the upgrade may have installed a new kernel
+                    info!("Pacman database has changed, checking if a reboot is required");
+                    break;
+                } else {
+                    info!("There are no missing security updates so we aren't checking if we're missing any");
+                }
+            }
         }
     }
+    Some(Trigger::Automatic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{mock_update, MockScenario};
+
+    /// Longest a test waits for the updater thread
+    const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn wait_until(what: &str, f: impl Fn() -> bool) {
+        let started = Instant::now();
+        while !f() {
+            assert!(
+                started.elapsed() < TEST_TIMEOUT,
+                "timed out waiting until {}",
+                what
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Config with mock results, like `--mock <scenario>`
+    fn mock_config(scenario: MockScenario) -> Config {
+        let mut config = Config::for_test("");
+        config.mock = Some(scenario);
+        config
+    }
+
+    fn spawn_updater(
+        config: Config,
+    ) -> (mpsc::Sender<Event>, ResultReceiver, thread::JoinHandle<()>) {
+        let (update_tx, update_rx) = mpsc::channel();
+        let (result_tx, result_rx) = result_channel();
+        let updater = thread::spawn(move || {
+            background(
+                config,
+                CancelToken::default(),
+                Snooze::default(),
+                Acknowledged::default(),
+                HashSet::new(),
+                true,
+                update_rx,
+                result_tx,
+            )
+        });
+        (update_tx, result_rx, updater)
+    }
+
+    #[test]
+    fn json_export() {
+        let updates = vec![mock_update(0, Severity::High, true)];
+        let duration = Duration::from_millis(1500);
+        let mut result = CheckResult::new(Status::MissingUpdates(updates), duration, "mock");
+        result.checked_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let json = serde_json::from_str::<serde_json::Value>(&result.to_json().unwrap()).unwrap();
+        let expected = serde_json::json!({
+            "schema_version": 1,
+            "status": {
+                "missing_updates": [{
+                    "severity": "High",
+                    "pkg": "openssl",
+                    "avg": "AVG-9000",
+                    "repo": "core",
+                    "installed": "1.0.0-1",
+                    "text": "High risk: openssl (arbitrary code execution)",
+                    "link": "https://security.archlinux.org/AVG-9000",
+                    "kind": "arbitrary code execution",
+                    "avg_status": "Fixed",
+                    "fixed": "1.0.1-1",
+                    "issues": ["CVE-2099-0000", "CVE-2099-0001"],
+                    "cves": [
+                        {
+                            "id": "CVE-2099-0000",
+                            "link": "https://security.archlinux.org/CVE-2099-0000",
+                        },
+                        {
+                            "id": "CVE-2099-0001",
+                            "link": "https://security.archlinux.org/CVE-2099-0001",
+                        },
+                    ],
+                    "new": false,
+                    "aur": false,
+                    "acknowledged": false,
+                }],
+            },
+            "error_kind": null,
+            "checked_at": "2023-11-14T22:13:20Z",
+            "duration": 1.5,
+            "backend": "mock",
+            "partial": false,
+            "severities": { "critical": 0, "high": 1, "medium": 0, "low": 0, "unknown": 0 },
+            "total": 1,
+            "advisories": 1,
+            "packages": 1,
+            "fixable": 1,
+            "pending": null,
+            "reboot_required": [],
+            "remotes": [],
+            "warnings": [],
+            "retry_in": null,
+            "next_check_at": null,
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn json_export_of_a_failed_check() {
+        let err = known_error(ErrorKind::Network, "tracker unreachable".to_string());
+        let status = Status::Error(CheckError::new(&err));
+        let mut result = CheckResult::new(status, Duration::ZERO, "mock");
+        result.retry_in = Some(Duration::from_secs(120));
+
+        let json = serde_json::from_str::<serde_json::Value>(&result.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["status"],
+            serde_json::json!({ "error": "tracker unreachable" })
+        );
+        assert_eq!(json["error_kind"], "network");
+        assert_eq!(json["retry_in"], 120);
+        assert_eq!(json["total"], 0);
+    }
+
+    #[test]
+    fn cancel_kills_the_running_child() {
+        let cancel = CancelToken::default();
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                cancel.cancel();
+            })
+        };
+        let started = Instant::now();
+        let err = run_cancelable(Command::new("sleep").arg("60"), &cancel, None).unwrap_err();
+        canceller.join().unwrap();
+
+        assert_eq!(err.to_string(), "Check has been cancelled");
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(cancel.child.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn timeout_kills_the_child() {
+        let cancel = CancelToken::default();
+        let timeout = Some(Duration::from_millis(200));
+        let started = Instant::now();
+        let err = run_cancelable(Command::new("sleep").arg("60"), &cancel, timeout).unwrap_err();
+
+        assert!(err.to_string().starts_with("timed out after"));
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(cancel.child.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn finished_child_is_not_cancelled() {
+        let cancel = CancelToken::default();
+        let output = run_cancelable(Command::new("echo").arg("ok"), &cancel, None).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"ok\n");
+        assert!(!cancel.is_cancelled());
+    }
+
+    #[test]
+    fn severity_breakdown_adds_up_to_the_total() {
+        let mut updates = vec![
+            mock_update(0, Severity::Critical, true),
+            mock_update(1, Severity::High, true),
+            mock_update(2, Severity::High, false),
+            mock_update(3, Severity::Low, true),
+            mock_update(4, Severity::Medium, true),
+        ];
+        // acknowledged advisories are neither in the breakdown nor the total
+        updates[4].acknowledged = true;
+        let result = CheckResult::new(Status::MissingUpdates(updates), Duration::ZERO, "mock");
+
+        assert_eq!(result.total, 4);
+        assert_eq!(result.severities.values().sum::<usize>(), result.total);
+        assert_eq!(result.severities["critical"], 1);
+        assert_eq!(result.severities["high"], 2);
+        assert_eq!(result.severities["medium"], 0);
+        assert_eq!(result.severities["low"], 1);
+        assert_eq!(result.severities["unknown"], 0);
+        assert_eq!(result.fixable, 3);
+    }
+
+    #[test]
+    fn severity_breakdown_of_a_failed_check_is_empty() {
+        let status = Status::Error(CheckError::from("failed".to_string()));
+        let result = CheckResult::new(status, Duration::ZERO, "mock");
+        assert_eq!(result.total, 0);
+        assert!(result.severities.values().all(|count| *count == 0));
+    }
+
+    #[test]
+    fn updater_exits_once_the_channel_closes() {
+        let (update_tx, result_rx, updater) = spawn_updater(mock_config(MockScenario::Clean));
+        wait_until("the first result", || {
+            result_rx.latest.lock().unwrap().is_some()
+        });
+
+        drop(update_tx);
+        wait_until("the updater exited", || updater.is_finished());
+        updater.join().unwrap();
+    }
+
+    #[test]
+    fn updater_exits_on_shutdown() {
+        let (update_tx, result_rx, updater) = spawn_updater(mock_config(MockScenario::Clean));
+        wait_until("the first result", || {
+            result_rx.latest.lock().unwrap().is_some()
+        });
+
+        // the sender is still open, only the event ends the loop
+        update_tx.send(Event::Shutdown).unwrap();
+        wait_until("the updater exited", || updater.is_finished());
+        updater.join().unwrap();
+    }
+
+    #[test]
+    fn click_while_debouncing_checks() {
+        // pacman events alone don't cause a check on a clean system
+        let mut config = mock_config(MockScenario::Clean);
+        config.detect_reboot = false;
+        let (update_tx, result_rx, updater) = spawn_updater(config);
+        wait_until("the first result", || {
+            result_rx.latest.lock().unwrap().take().is_some()
+        });
+
+        update_tx.send(Event::Inotify).unwrap();
+        update_tx.send(Event::Click).unwrap();
+        wait_until("the clicked result", || {
+            result_rx.latest.lock().unwrap().is_some()
+        });
+        assert_ne!(result_rx.state().phase(), Phase::Checking);
+
+        update_tx.send(Event::Shutdown).unwrap();
+        updater.join().unwrap();
+    }
+
+    #[test]
+    fn advisory_link_falls_back_to_the_package() {
+        let advisory = "https://security.archlinux.org/AVG-9000";
+        let package = "https://security.archlinux.org/package/openssl";
+        for (avg, link, expected) in [
+            ("AVG-9000", advisory, advisory),
+            ("", "https://security.archlinux.org/", package),
+            ("AVG-", "https://security.archlinux.org/AVG-", package),
+            ("AVG-12a", "https://security.archlinux.org/AVG-12a", package),
+            (
+                "CVE-2099-0000",
+                "https://security.archlinux.org/CVE-2099-0000",
+                package,
+            ),
+            ("AVG-9000", "AVG-9000", package),
+        ] {
+            let mut update = mock_update(0, Severity::High, true);
+            update.avg = avg.to_string();
+            update.link = link.to_string();
+            assert_eq!(update.advisory_link(), expected, "{:?} {:?}", avg, link);
+        }
+    }
+
+    #[test]
+    fn advisories_below_min_severity_look_clean() {
+        let config = Config::for_test("[alerts]\nmin_severity = \"high\"\n");
+        let low = Status::MissingUpdates(vec![mock_update(0, Severity::Low, true)]);
+        assert_eq!(low.alert_severity(&config), None);
+        assert!(matches!(low.icon(&config), Icon::Check));
+        let high = Status::MissingUpdates(vec![mock_update(1, Severity::High, true)]);
+        assert!(matches!(high.icon(&config), Icon::Severity(Severity::High)));
+
+        let high = Status::MissingUpdates(vec![
+            mock_update(0, Severity::High, true),
+            mock_update(1, Severity::Low, true),
+        ]);
+        assert_eq!(high.alert_severity(&config), Some(Severity::High));
+    }
+
+    #[test]
+    fn critical_only_ignores_everything_else() {
+        let config = Config::for_test("[alerts]\ncritical_only = true\n");
+        let high = Status::MissingUpdates(vec![mock_update(0, Severity::High, true)]);
+        assert_eq!(high.alert_severity(&config), None);
+    }
+
+    #[test]
+    fn zero_check_timeout_disables_it() {
+        let mut config = Config::for_test("[updater]\ncheck_timeout = 0\n");
+        assert_eq!(check_timeout(&config), None);
+        config.check_timeout = Duration::from_secs(30);
+        assert_eq!(check_timeout(&config), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn zero_lock_timeout_does_not_wait() {
+        let lock = env::temp_dir().join(format!("arch-audit-gtk-{}.lck", std::process::id()));
+        fs::write(&lock, "").unwrap();
+        let cancel = CancelToken::default();
+        let waited = wait_for_pacman_lock(&lock, Duration::ZERO, &cancel);
+        let timed_out = !wait_for_pacman_lock(&lock, Duration::from_millis(1), &cancel);
+        fs::remove_file(&lock).unwrap();
+        assert!(waited);
+        assert!(timed_out);
+    }
+
+    #[test]
+    fn only_the_latest_result_is_delivered() {
+        let (tx, rx) = result_channel();
+        tx.send(CheckResult::new(Status::Offline, Duration::ZERO, "first"))
+            .unwrap();
+        tx.send(CheckResult::new(Status::Offline, Duration::ZERO, "second"))
+            .unwrap();
+
+        let latest = rx.latest.lock().unwrap().take().unwrap();
+        assert_eq!(latest.backend, "second");
+        // the stale first result is gone, not queued behind the second
+        assert!(rx.latest.lock().unwrap().is_none());
+        assert!(matches!(rx.state().phase(), Phase::Cooldown(_)));
+    }
 }
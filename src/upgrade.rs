@@ -0,0 +1,69 @@
+use crate::config::Config;
+use crate::errors::*;
+use std::env;
+use std::path::Path;
+use std::process::{Child, Command};
+
+/// Terminals that are detected if none is configured, with the arguments
+/// that make them run a command and wait for it, without handing it to an
+/// already running instance that would return right away
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("gnome-terminal", &["--wait", "--"]),
+    ("konsole", &["--nofork", "-e"]),
+    ("xfce4-terminal", &["--disable-server", "-x"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("foot", &[]),
+    ("wezterm", &["start", "--always-new-process", "--"]),
+    ("xterm", &["-e"]),
+];
+
+/// If `bin` can be executed, paths are checked directly
+pub fn in_path(bin: &str) -> bool {
+    if bin.contains('/') {
+        return Path::new(bin).is_file();
+    }
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|dir| dir.join(bin).is_file()))
+        .unwrap_or(false)
+}
+
+/// The configured terminal command, or the first known terminal that's
+/// installed
+fn terminal(config: &Config) -> Result<Vec<String>> {
+    if let Some(terminal) = &config.upgrade_terminal {
+        return Ok(terminal.split_whitespace().map(String::from).collect());
+    }
+    TERMINALS
+        .iter()
+        .find(|(bin, _)| in_path(bin))
+        .map(|(bin, args)| {
+            let mut cmd = vec![bin.to_string()];
+            cmd.extend(args.iter().map(|arg| arg.to_string()));
+            cmd
+        })
+        .context("No terminal emulator found, configure one in [upgrade]")
+}
+
+/// Open a terminal running the update command
+///
+/// The terminal is kept open until enter is pressed, so the output of pacman
+/// can be read. It exits with the status of the update command, unless the
+/// terminal emulator doesn't pass it on.
+pub fn spawn(config: &Config) -> Result<Child> {
+    let terminal = terminal(config)?;
+    let (bin, args) = terminal
+        .split_first()
+        .context("[upgrade] terminal is empty")?;
+    let script = format!(
+        "{}; status=$?; printf '\\nPress enter to close '; read -r _; exit $status",
+        config.upgrade_command
+    );
+
+    info!("Running {:?} in {:?}", config.upgrade_command, bin);
+    Command::new(bin)
+        .args(args)
+        .args(["sh", "-c", &script])
+        .spawn()
+        .with_context(|| anyhow!("Failed to start terminal {:?}", bin))
+}
@@ -0,0 +1,66 @@
+//! Runs the binary with `--mock`, so the json of `check` is tested without
+//! arch-audit or a pacman database
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Config, cache, state and data dirs of their own, so the user's files
+/// neither change the results nor get written to
+fn home(name: &str) -> PathBuf {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if home.exists() {
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+    std::fs::create_dir_all(&home).unwrap();
+    home
+}
+
+fn check_json(scenario: &str) -> (Output, serde_json::Value) {
+    let home = home(scenario);
+    let output = Command::new(env!("CARGO_BIN_EXE_arch-audit-gtk"))
+        .args(["--mock", scenario, "check", "--json"])
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_STATE_HOME", home.join("state"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .output()
+        .unwrap();
+    let json = serde_json::from_slice(&output.stdout).unwrap_or_else(|err| {
+        panic!(
+            "Invalid json ({}): {}",
+            err,
+            String::from_utf8_lossy(&output.stdout)
+        )
+    });
+    (output, json)
+}
+
+#[test]
+fn vulnerable() {
+    let (output, json) = check_json("vulnerable");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(json["backend"], "mock");
+    assert_eq!(json["error_kind"], serde_json::Value::Null);
+
+    let updates = json["status"]["missing_updates"].as_array().unwrap();
+    assert!(!updates.is_empty());
+    assert_eq!(json["total"], updates.len());
+    assert!(updates
+        .iter()
+        .any(|update| update["severity"] == "Critical"));
+}
+
+#[test]
+fn clean() {
+    let (output, json) = check_json("clean");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json["status"]["missing_updates"], serde_json::json!([]));
+    assert_eq!(json["total"], 0);
+}
+
+#[test]
+fn failed_check() {
+    let (output, json) = check_json("network-error");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(json["error_kind"], "network");
+}